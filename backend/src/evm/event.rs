@@ -1,14 +1,26 @@
 use candid::CandidType;
 use ethers_core::abi::Address;
-use evm_rpc_canister_types::LogEntry;
+use evm_rpc_canister_types::{BlockTag, GetLogsArgs, GetLogsResult, LogEntry, MultiGetLogsResult};
 use num_traits::ToPrimitive;
 
-use crate::errors::{BlockchainError, Result, SystemError};
+use super::rpc::EVM_RPC;
+use crate::{
+    errors::{BlockchainError, Result, SystemError},
+    types::evm::{chains, transaction::TransactionAction},
+};
 
 const TXS_THRESHOLD_DISCARD_BLOCKS: u128 = 30 * 7 * 24 * 60 * 5; // Assuming 5 blocks per minute
 
 const DEPOSIT_EVENT_SIGNATURE: &str =
     "0x5548c837ab068cf56a2c2479df0882a4922fd203edb7517321831d95078c5f62";
+const COMMIT_EVENT_SIGNATURE: &str =
+    "0xc48642bbbd374aabe34d3f7a78a8dbc642c1b6a76c086a0090cefb037a636543";
+const UNCOMMIT_EVENT_SIGNATURE: &str =
+    "0x3547fd66bf08a4ff7f70f8040e10b8767d3db1e8d8f6144cd2ffd41877cf4151";
+const RELEASE_EVENT_SIGNATURE: &str =
+    "0x228423d8a9eb6071cccafa67a43b586470927c25af6d26b07824f1a3e4a1c9a8";
+const WITHDRAW_EVENT_SIGNATURE: &str =
+    "0xf341246adaac6f497bc2a656f546ab9e182111d630394f0c57c710a59a2cb567";
 
 #[derive(CandidType, Debug)]
 pub struct DepositEvent {
@@ -38,91 +50,349 @@ impl DepositEvent {
     }
 }
 
-/// Represents different types of log events that can occur.
-/// In this case, we are only handling the `Deposit` event.
+/// Deposit of an offramper committed to, or uncommitted from, an order.
+#[derive(CandidType, Debug)]
+pub struct CommitEvent {
+    pub offramper: String,
+    pub token: Option<String>,
+    pub amount: u128,
+}
+
+#[derive(CandidType, Debug)]
+pub struct ReleaseEvent {
+    pub offramper: String,
+    pub onramper: String,
+    pub token: Option<String>,
+    pub amount: u128,
+    pub fees: u128,
+}
+
+#[derive(CandidType, Debug)]
+pub struct WithdrawEvent {
+    pub offramper: String,
+    pub token: Option<String>,
+    pub amount: u128,
+    pub fees: u128,
+}
+
+/// Events emitted by the vault manager contract.
+///
+/// Native currency movements carry the zero address as token, decoded as `None`.
 #[derive(CandidType, Debug)]
 pub enum LogEvent {
+    /// `event Deposit(address indexed user, address indexed token, uint256 amount)`
     Deposit(DepositEvent),
+    /// `event Commit(address indexed offramper, address indexed token, uint256 amount)`
+    Commit(CommitEvent),
+    /// `event Uncommit(address indexed offramper, address indexed token, uint256 amount)`
+    Uncommit(CommitEvent),
+    /// `event Release(address indexed offramper, address indexed onramper,
+    /// address indexed token, uint256 amount, uint256 fees)`
+    Release(ReleaseEvent),
+    /// `event Withdraw(address indexed offramper, address indexed token, uint256 amount,
+    /// uint256 fees)`
+    Withdraw(WithdrawEvent),
 }
 
-/// Parses a `LogEntry` from an Ethereum transaction log and attempts to extract
-/// a `Deposit` event if the log matches the expected event signature and format.
-///
-/// The expected event signature corresponds to the following Solidity event:
-///
-/// ```solidity
-/// event Deposit(address indexed user, address indexed token, uint256 amount);
-/// ```
+/// Funds a vault transaction is expected to move out of an offramper's deposit.
+#[derive(Clone, Debug)]
+pub struct VaultTransfer {
+    pub offramper: String,
+    pub token: Option<String>,
+    pub amount: u128,
+}
+
+impl LogEvent {
+    /// Whether this is the event the vault emits when `action` succeeds.
+    pub fn is_emitted_by(&self, action: &TransactionAction) -> bool {
+        matches!(
+            (self, action),
+            (LogEvent::Commit(_), TransactionAction::Commit)
+                | (LogEvent::Uncommit(_), TransactionAction::Uncommit)
+                | (LogEvent::Release(_), TransactionAction::Release(_))
+                | (LogEvent::Withdraw(_), TransactionAction::Cancel(_))
+        )
+    }
+
+    /// Whether this event moved `transfer`, addresses compared case-insensitively.
+    pub fn moves(&self, transfer: &VaultTransfer) -> bool {
+        let (offramper, token, amount) = match self {
+            LogEvent::Deposit(event) => (&event.user, &event.token, event.amount),
+            LogEvent::Commit(event) | LogEvent::Uncommit(event) => {
+                (&event.offramper, &event.token, event.amount)
+            }
+            LogEvent::Release(event) => (&event.offramper, &event.token, event.amount),
+            LogEvent::Withdraw(event) => (&event.offramper, &event.token, event.amount),
+        };
+        offramper.eq_ignore_ascii_case(&transfer.offramper)
+            && token.as_ref().map(|t| t.to_lowercase())
+                == transfer.token.as_ref().map(|t| t.to_lowercase())
+            && amount == transfer.amount
+    }
+}
+
+fn expect_topics(log: &LogEntry, count: usize) -> Result<()> {
+    if log.topics.len() != count {
+        return Err(BlockchainError::EvmLogError("Invalid number of topics".to_string()).into());
+    }
+    Ok(())
+}
+
+/// Address in the indexed parameter `index` of the log, i.e. `topics[index + 1]`.
+fn indexed_address(log: &LogEntry, index: usize) -> Result<String> {
+    let topic = log
+        .topics
+        .get(index + 1)
+        .filter(|topic| topic.len() == 66)
+        .ok_or_else(|| BlockchainError::EvmLogError("Invalid log topic".to_string()))?;
+    Ok(format!("0x{}", &topic[26..]))
+}
+
+/// Token address in the indexed parameter `index`, `None` for the native currency.
+fn indexed_token(log: &LogEntry, index: usize) -> Result<Option<String>> {
+    let token_address = indexed_address(log, index)?;
+    if token_address == format!("{:#x}", Address::zero()) {
+        Ok(None)
+    } else {
+        Ok(Some(token_address))
+    }
+}
+
+/// `uint256` in the 32-byte word `index` of the log's non-indexed data.
+fn data_word(log: &LogEntry, index: usize) -> Result<u128> {
+    let data = log.data.strip_prefix("0x").unwrap_or(&log.data);
+    let word = data
+        .get(index * 64..(index + 1) * 64)
+        .ok_or_else(|| BlockchainError::EvmLogError("Invalid log data".to_string()))?;
+    u128::from_str_radix(word, 16).map_err(|e| SystemError::ParseError(e.to_string()).into())
+}
+
+/// Parses a `LogEntry` from an Ethereum transaction log into the vault event it
+/// represents.
 ///
 /// ## Topics:
 ///
-/// - `topics[0]`: The hashed event signature for the `Deposit` event. This is a unique
-///   identifier for the event derived from its name and parameters.
-/// - `topics[1]`: The `offramper` (user) address involved in the deposit.
-///   This is a 32-byte hex string that represents the Ethereum address of the user.
-/// - `topics[2]`: The `token` address involved in the transaction. If this is a native
-///   token like Ether (ETH), this address will be `0x0000000000000000000000000000000000000000`.
+/// - `topics[0]`: The hashed event signature, which identifies the event. Logs of
+///   other events, e.g. ERC-20 `Transfer` or `Approval`, are rejected.
+/// - `topics[1..]`: The indexed addresses of the event, left-padded to 32 bytes. The
+///   `token` is the zero address when the native currency (e.g. ETH) is moved.
 ///
 /// ## Data:
 ///
-/// - `data`: A non-indexed parameter that represents the amount of the deposit in `uint256`.
-///
-/// ## Parameters:
-///
-/// - `log`: A `LogEntry` that contains the event data emitted by the Ethereum blockchain.
-///
-/// ## Returns:
-///
-/// Returns a `Result` containing the parsed `LogEvent::Deposit` event or an error if the log entry
-/// does not match the expected format.
+/// - `data`: The non-indexed `uint256` parameters (amount, fees), one 32-byte word each.
 ///
 /// ## Errors:
 ///
 /// - Returns `BlockchainError::EvmLogError` if:
-///   - The log does not contain exactly 3 topics.
-///   - The event signature does not match the expected `Deposit` event signature.
-///   - There is an issue parsing the log data (e.g., invalid hexadecimal format).
+///   - The event signature is not one of the vault events.
+///   - The log does not contain the number of topics the event has.
+///   - The data is shorter than the event's non-indexed parameters.
+/// - Returns `SystemError::ParseError` if an amount is not valid hexadecimal or
+///   does not fit in a `u128`.
 ///
 /// ## Example Usage:
 ///
 /// ```rust
-/// let log_event = parse_deposit_event(&log_entry)?;
-/// match log_event {
-///     LogEvent::Deposit(deposit) => {
-///         println!("Deposit Event: {:?}", deposit);
-///     }
+/// match parse_log_event(&log_entry)? {
+///     LogEvent::Deposit(deposit) => println!("Deposit Event: {:?}", deposit),
+///     event => println!("Vault Event: {:?}", event),
 /// }
 /// ```
-pub fn parse_deposit_event(log: &LogEntry) -> Result<LogEvent> {
-    if log.topics.len() != 3 {
-        return Err(BlockchainError::EvmLogError("Invalid number of topics".to_string()).into());
-    }
+pub fn parse_log_event(log: &LogEntry) -> Result<LogEvent> {
+    let signature = log
+        .topics
+        .first()
+        .ok_or_else(|| BlockchainError::EvmLogError("Log has no topics".to_string()))?;
+    let event = match signature.as_str() {
+        DEPOSIT_EVENT_SIGNATURE => {
+            expect_topics(log, 3)?;
+            LogEvent::Deposit(DepositEvent::new(
+                &indexed_address(log, 0)?,
+                indexed_token(log, 1)?,
+                data_word(log, 0)?,
+                log.blockNumber.clone().and_then(|block| block.0.to_u128()),
+            ))
+        }
+        COMMIT_EVENT_SIGNATURE | UNCOMMIT_EVENT_SIGNATURE => {
+            expect_topics(log, 3)?;
+            let commit = CommitEvent {
+                offramper: indexed_address(log, 0)?,
+                token: indexed_token(log, 1)?,
+                amount: data_word(log, 0)?,
+            };
+            if signature == COMMIT_EVENT_SIGNATURE {
+                LogEvent::Commit(commit)
+            } else {
+                LogEvent::Uncommit(commit)
+            }
+        }
+        RELEASE_EVENT_SIGNATURE => {
+            expect_topics(log, 4)?;
+            LogEvent::Release(ReleaseEvent {
+                offramper: indexed_address(log, 0)?,
+                onramper: indexed_address(log, 1)?,
+                token: indexed_token(log, 2)?,
+                amount: data_word(log, 0)?,
+                fees: data_word(log, 1)?,
+            })
+        }
+        WITHDRAW_EVENT_SIGNATURE => {
+            expect_topics(log, 3)?;
+            LogEvent::Withdraw(WithdrawEvent {
+                offramper: indexed_address(log, 0)?,
+                token: indexed_token(log, 1)?,
+                amount: data_word(log, 0)?,
+                fees: data_word(log, 1)?,
+            })
+        }
+        _ => return Err(BlockchainError::EvmLogError("Not a vault event".to_string()).into()),
+    };
+    Ok(event)
+}
 
-    // Event signature: first topic, we compare it to the known Deposit event signature
-    if log.topics[0] != DEPOSIT_EVENT_SIGNATURE {
-        return Err(BlockchainError::EvmLogError("Not a Deposit event".to_string()).into());
-    }
+/// Decodes the vault events among `logs`, skipping logs emitted by other
+/// contracts (tokens, routers...) and logs removed by a reorg.
+pub fn parse_vault_events(logs: &[LogEntry], vault_manager_address: &str) -> Vec<LogEvent> {
+    logs.iter()
+        .filter(|log| !log.removed && log.address.eq_ignore_ascii_case(vault_manager_address))
+        .filter_map(|log| match parse_log_event(log) {
+            Ok(event) => Some(event),
+            Err(e) => {
+                ic_cdk::println!("[parse_vault_events] skipping log: {:?}", e);
+                None
+            }
+        })
+        .collect()
+}
 
-    // Indexed parameter 1: user address
-    let user_address = format!("0x{}", &log.topics[1][26..]);
+/// Fetches the `Deposit` events the vault manager of `chain_id` emitted between
+/// `from_block` and `to_block` (inclusive), with the hash of their transaction.
+pub async fn get_deposit_events(
+    chain_id: u64,
+    from_block: u64,
+    to_block: u64,
+) -> Result<Vec<(String, DepositEvent)>> {
+    let rpc_providers = chains::get_rpc_providers(chain_id)?;
+    let vault_manager_address = chains::get_vault_manager_address(chain_id)?;
 
-    // Indexed parameter 2: token address (in case it's native token, it's 0x0 address)
-    let token_address = format!("0x{}", &log.topics[2][26..]);
-    let token_address = if token_address == format!("{:#x}", Address::zero()) {
-        None
-    } else {
-        Some(token_address)
+    let args = GetLogsArgs {
+        fromBlock: Some(BlockTag::Number(from_block.into())),
+        toBlock: Some(BlockTag::Number(to_block.into())),
+        addresses: vec![vault_manager_address],
+        topics: Some(vec![vec![DEPOSIT_EVENT_SIGNATURE.to_string()]]),
     };
 
-    // Non-indexed data: amount (hexadecimal to u128)
-    let amount_hex = &log.data[2..]; // Remove the '0x' prefix
-    let amount =
-        u128::from_str_radix(amount_hex, 16).map_err(|e| SystemError::ParseError(e.to_string()))?;
-
-    Ok(LogEvent::Deposit(DepositEvent::new(
-        &user_address,
-        token_address,
-        amount,
-        log.blockNumber.clone().and_then(|block| block.0.to_u128()),
-    )))
+    let cycles = 10_000_000_000;
+    let logs = match EVM_RPC
+        .eth_get_logs(rpc_providers, None, args, cycles)
+        .await
+    {
+        Ok((res,)) => match res {
+            MultiGetLogsResult::Consistent(GetLogsResult::Ok(logs)) => logs,
+            MultiGetLogsResult::Consistent(GetLogsResult::Err(e)) => {
+                Err(SystemError::RpcError(format!("{:?}", e)))?
+            }
+            MultiGetLogsResult::Inconsistent(_) => Err(SystemError::InternalError(
+                "Logs Result is inconsistent".to_string(),
+            ))?,
+        },
+        Err((code, message)) => Err(SystemError::ICRejectionError(code, message))?,
+    };
+
+    Ok(logs
+        .iter()
+        .filter(|log| !log.removed)
+        .filter_map(|log| {
+            let tx_hash = log.transactionHash.clone()?;
+            match parse_log_event(log) {
+                Ok(LogEvent::Deposit(deposit)) => Some((tx_hash, deposit)),
+                Ok(_) => None,
+                Err(e) => {
+                    ic_cdk::println!("[get_deposit_events] skipping log of {}: {:?}", tx_hash, e);
+                    None
+                }
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use evm_rpc_canister_types::LogEntry;
+
+    use super::{parse_log_event, parse_vault_events, LogEvent, VaultTransfer};
+    use crate::model::types::evm::transaction::{TransactionAction, TransactionVariant};
+
+    #[test]
+    fn test_vault_event_decoding() {
+        let vault = "0x42ad57ab757ea55960f7d9805d82fa818683096b";
+        let word = |hex: &str| format!("{:0>64}", hex);
+        let log = |address: &str, topics: Vec<String>, data: String| LogEntry {
+            transactionHash: None,
+            blockNumber: None,
+            data,
+            blockHash: None,
+            transactionIndex: None,
+            topics,
+            address: address.to_string(),
+            logIndex: None,
+            removed: false,
+        };
+
+        // ERC-20 Transfer emitted by the token before the vault's Release
+        let transfer = log(
+            "0xdef0000000000000000000000000000000000002",
+            vec![
+                "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef".to_string(),
+                format!("0x{}", word("42ad57ab757ea55960f7d9805d82fa818683096b")),
+                format!("0x{}", word("b0")),
+            ],
+            format!("0x{}", word("3e8")),
+        );
+        let release = log(
+            "0x42AD57AB757EA55960F7D9805D82FA818683096B",
+            vec![
+                "0x228423d8a9eb6071cccafa67a43b586470927c25af6d26b07824f1a3e4a1c9a8".to_string(),
+                format!("0x{}", word("a0")),
+                format!("0x{}", word("b0")),
+                format!("0x{}", word("def0000000000000000000000000000000000002")),
+            ],
+            format!("0x{}{}", word("3e8"), word("a")),
+        );
+        assert!(parse_log_event(&transfer).is_err());
+
+        let events = parse_vault_events(&[transfer, release], vault);
+        assert_eq!(events.len(), 1);
+        let LogEvent::Release(release) = &events[0] else {
+            panic!("expected a Release event");
+        };
+        assert_eq!(release.onramper, format!("0x{}", &word("b0")[24..]));
+        assert_eq!(
+            release.token.as_deref(),
+            Some("0xdef0000000000000000000000000000000000002")
+        );
+        assert_eq!((release.amount, release.fees), (1_000, 10));
+        assert!(events[0].is_emitted_by(&TransactionAction::Release(TransactionVariant::Token)));
+        assert!(!events[0].is_emitted_by(&TransactionAction::Cancel(TransactionVariant::Token)));
+
+        // Only the offramper, token and amount of the order match
+        let expected = VaultTransfer {
+            offramper: format!("0x{}", &word("A0")[24..]),
+            token: Some("0xDEF0000000000000000000000000000000000002".to_string()),
+            amount: 1_000,
+        };
+        assert!(events[0].moves(&expected));
+        assert!(!events[0].moves(&VaultTransfer {
+            offramper: format!("0x{}", &word("c0")[24..]),
+            ..expected.clone()
+        }));
+        assert!(!events[0].moves(&VaultTransfer {
+            token: None,
+            ..expected.clone()
+        }));
+        assert!(!events[0].moves(&VaultTransfer {
+            amount: 999,
+            ..expected
+        }));
+    }
 }
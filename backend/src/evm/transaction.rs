@@ -15,17 +15,21 @@ use super::{
 use crate::{
     errors::{BlockchainError, RampError, Result, SystemError},
     evm::{
+        event::{self, LogEvent, VaultTransfer},
         fees,
         helper::{empty_transaction_receipt, nat_to_u256},
         vault::Ic2P2ramp,
     },
-    management::vault,
+    management::{user, vault},
     model::{
         memory::{
             heap::{logs, read_state},
             stable::orders::unset_processing_order,
         },
-        types::evm::{chains, nonce::NonceFeeEstimates},
+        types::{
+            evm::{chains, nonce::NonceFeeEstimates},
+            referral,
+        },
     },
     types::{
         evm::{
@@ -43,6 +47,18 @@ pub(crate) const MAX_RETRY_ATTEMPTS: u8 = 3;
 pub(crate) const MAX_ATTEMPTS_PER_RETRY: u16 = 20;
 pub(crate) const ATTEMPT_INTERVAL_SECONDS: u64 = 4;
 
+// gives back what was held for a transaction that was never broadcast
+fn release_failed_broadcast(order_id: u64, lock_input: &Option<LockInput>) {
+    let _ = unset_processing_order(&order_id);
+    user::fail_referral_claim(order_id);
+    if let Some(lock_input) = lock_input {
+        user::release_kyc_volume(
+            lock_input.onramper_user_id,
+            &lock_input.onramper_kyc_reservation,
+        );
+    }
+}
+
 pub fn broadcast_transaction(
     order_id: u64,
     chain_id: u64,
@@ -130,7 +146,15 @@ pub fn broadcast_transaction(
                                 sign_request,
                             ),
                             TransactionAction::Cancel(cancel_variant) => {
-                                if order_id != 0 {
+                                if referral::get_claim(order_id).is_some() {
+                                    vault::spawn_claim_withdraw_listener(
+                                        order_id,
+                                        chain_id,
+                                        cancel_variant,
+                                        &tx_hash,
+                                        sign_request,
+                                    )
+                                } else if order_id != 0 {
                                     vault::spawn_cancel_listener(
                                         order_id,
                                         chain_id,
@@ -155,6 +179,16 @@ pub fn broadcast_transaction(
                                     sign_request,
                                 )
                             }
+                            TransactionAction::Transfer(..)
+                                if referral::get_claim(order_id).is_some() =>
+                            {
+                                vault::spawn_claim_transfer_listener(
+                                    order_id,
+                                    chain_id,
+                                    &tx_hash,
+                                    sign_request,
+                                )
+                            }
                             TransactionAction::Transfer(..) => {
                                 ic_cdk::println!("Broadcasted tx: {}", tx_hash);
                                 logs::update_transaction_log(
@@ -184,7 +218,7 @@ pub fn broadcast_transaction(
                             }
                             Err(e) => {
                                 release_nonce(chain_id);
-                                let _ = unset_processing_order(&order_id);
+                                release_failed_broadcast(order_id, &lock_input);
                                 logs::update_transaction_log(
                                     order_id,
                                     TransactionStatus::BroadcastError(e),
@@ -214,7 +248,7 @@ pub fn broadcast_transaction(
 
                     Err(e) => {
                         release_nonce(chain_id);
-                        let _ = unset_processing_order(&order_id);
+                        release_failed_broadcast(order_id, &lock_input);
                         logs::update_transaction_log(
                             order_id,
                             TransactionStatus::BroadcastError(e),
@@ -223,7 +257,7 @@ pub fn broadcast_transaction(
                 }
             } else if attempt > 20 {
                 release_nonce(chain_id);
-                let _ = unset_processing_order(&order_id);
+                release_failed_broadcast(order_id, &lock_input);
                 logs::update_transaction_log(
                     order_id,
                     TransactionStatus::BroadcastError(
@@ -353,7 +387,7 @@ pub async fn check_transaction_status(tx_hash: &String, chain_id: u64) -> Transa
                     if receipt.status == 1_u32 {
                         TransactionStatus::Confirmed(receipt)
                     } else {
-                        TransactionStatus::Failed(format!("Transaction failed: {:?}", receipt))
+                        TransactionStatus::Reverted(receipt)
                     }
                 } else {
                     TransactionStatus::Pending
@@ -368,6 +402,30 @@ pub async fn check_transaction_status(tx_hash: &String, chain_id: u64) -> Transa
     }
 }
 
+/// Checks that a confirmed vault transaction emitted the event of `action` for
+/// `transfer`, as a call can succeed without the vault moving these funds.
+pub fn check_vault_event(
+    chain_id: u64,
+    receipt: &TransactionReceipt,
+    action: &TransactionAction,
+    transfer: &VaultTransfer,
+) -> Result<LogEvent> {
+    let vault_manager_address = chains::get_vault_manager_address(chain_id)?;
+    event::parse_vault_events(&receipt.logs, &vault_manager_address)
+        .into_iter()
+        .find(|event| event.is_emitted_by(action) && event.moves(transfer))
+        .ok_or_else(|| {
+            BlockchainError::EvmLogError(format!(
+                "{} emitted no {:?} event of {:?}",
+                receipt.transactionHash, action, transfer
+            ))
+            .into()
+        })
+}
+
+/// Tracks `tx_hash` until it is confirmed, bumping its fees while it stays
+/// pending. `on_fail` is told whether the transaction was mined and reverted, as
+/// opposed to never being confirmed.
 pub fn spawn_transaction_checker<F, G>(
     retry_attempt: u8,
     tx_hash: String,
@@ -378,7 +436,7 @@ pub fn spawn_transaction_checker<F, G>(
     on_fail: G,
 ) where
     F: Fn(TransactionReceipt) + 'static,
-    G: Fn() + 'static,
+    G: Fn(bool) + 'static,
 {
     fn schedule_check<F, G>(
         tx_hash: String,
@@ -391,7 +449,7 @@ pub fn spawn_transaction_checker<F, G>(
         on_fail: G,
     ) where
         F: Fn(TransactionReceipt) + 'static,
-        G: Fn() + 'static,
+        G: Fn(bool) + 'static,
     {
         ic_cdk_timers::set_timer(Duration::from_secs(ATTEMPT_INTERVAL_SECONDS), move || {
             ic_cdk::println!("[schedule_check] spawning attempt number: {}", attempt);
@@ -434,16 +492,24 @@ pub fn spawn_transaction_checker<F, G>(
                             .await;
                         });
                     }
+                    TransactionStatus::Reverted(receipt) => {
+                        ic_cdk::println!("[schedule_check] TransactionStatus::Reverted");
+                        on_fail(true);
+                        logs::update_transaction_log(
+                            order_id,
+                            TransactionStatus::Reverted(receipt),
+                        );
+                    }
                     TransactionStatus::Failed(err) => {
                         ic_cdk::println!("[schedule_check] TransactionStatus::Failed: {:?}", err);
-                        on_fail();
+                        on_fail(false);
                         logs::update_transaction_log(order_id, TransactionStatus::Failed(err));
                     }
                     _ => {
                         ic_cdk::println!(
                             "[schedule_check] Transaction status check exceeded maximum attempts"
                         );
-                        on_fail();
+                        on_fail(false);
                         logs::remove_transaction_log(order_id);
                     }
                 }
@@ -465,7 +531,7 @@ pub fn spawn_transaction_checker<F, G>(
             );
         }
         Ordering::Equal => {
-            on_fail();
+            on_fail(false);
             ic_cdk::spawn(async move {
                 match bump_dummy_transaction(sign_request.clone(), chain_id).await {
                     Ok(tx_hash) => {
@@ -510,7 +576,7 @@ pub async fn retry_with_bumped_fees<F, G>(
     on_fail: G,
 ) where
     F: Fn(TransactionReceipt) + 'static,
-    G: Fn() + 'static,
+    G: Fn(bool) + 'static,
 {
     // Bump gas fees
     sign_request.max_fee_per_gas = Some(bump_fee(sign_request.max_fee_per_gas));
@@ -564,7 +630,7 @@ pub async fn retry_with_bumped_fees<F, G>(
                         .as_u128(),
                 },
             );
-            on_fail();
+            on_fail(false);
         }
     }
 }
@@ -1,19 +1,22 @@
 use ethers_core::abi::Token;
 use ethers_core::types::{Address, U256};
-use evm_rpc_canister_types::BlockTag;
+use evm_rpc_canister_types::{BlockTag, RequestResult, RpcService, RpcServices};
+use serde_json::json;
 
 use super::fees::{self, eth_get_latest_block};
 use super::helper::{self, load_contract_data};
+use super::rpc::EVM_RPC;
 use super::transaction::{broadcast_transaction, create_vault_sign_request};
 use super::{estimate_gas, EstimateGasParams};
 
-use crate::errors::{BlockchainError, Result};
+use crate::errors::{BlockchainError, Result, SystemError};
 use crate::model::{
     helpers,
     memory::heap::{logs, read_state},
 };
 use crate::types::{
     evm::{
+        chains,
         gas::get_average_gas,
         request::SignRequest,
         transaction::{TransactionAction, TransactionVariant},
@@ -21,6 +24,21 @@ use crate::types::{
     orders::{LockInput, LockedOrder},
 };
 
+const GET_DEPOSIT_ABI: &str = r#"
+    [
+        {
+            "inputs": [
+                {"internalType": "address", "name": "_user", "type": "address"},
+                {"internalType": "address", "name": "_token", "type": "address"}
+            ],
+            "name": "getDeposit",
+            "outputs": [{"internalType": "uint256", "name": "", "type": "uint256"}],
+            "stateMutability": "view",
+            "type": "function"
+        }
+    ]
+"#;
+
 pub struct Ic2P2ramp;
 
 impl Ic2P2ramp {
@@ -285,8 +303,65 @@ impl Ic2P2ramp {
         Ok(())
     }
 
+    /// Reads the vault balance of `user`, which for the canister address are the collected fees.
+    pub async fn get_deposit(chain_id: u64, user: String, token: Option<String>) -> Result<u128> {
+        let token = token.unwrap_or_else(|| format!("{:#x}", Address::zero()));
+        let data = load_contract_data(
+            GET_DEPOSIT_ABI,
+            "getDeposit",
+            &[
+                Token::Address(helpers::parse_address(user)?),
+                Token::Address(helpers::parse_address(token)?),
+            ],
+        )?;
+
+        let service = match chains::get_rpc_providers(chain_id)? {
+            RpcServices::Custom { services, .. } => RpcService::Custom(
+                services
+                    .into_iter()
+                    .next()
+                    .ok_or(BlockchainError::RpcProviderNotFound)?,
+            ),
+            _ => RpcService::Chain(chain_id),
+        };
+        let request = json!({
+            "jsonrpc": "2.0",
+            "method": "eth_call",
+            "params": [
+                {
+                    "to": chains::get_vault_manager_address(chain_id)?,
+                    "data": format!("0x{}", hex::encode(data)),
+                },
+                "latest"
+            ],
+            "id": 1
+        })
+        .to_string();
+
+        let cycles = 10_000_000_000;
+        let response = match EVM_RPC.request(service, request, 512, cycles).await {
+            Ok((RequestResult::Ok(response),)) => response,
+            Ok((RequestResult::Err(e),)) => Err(SystemError::RpcError(format!("{:?}", e)))?,
+            Err((code, message)) => Err(SystemError::ICRejectionError(code, message))?,
+        };
+        let response: serde_json::Value =
+            serde_json::from_str(&response).map_err(|e| SystemError::ParseError(e.to_string()))?;
+        let balance = response["result"]
+            .as_str()
+            .and_then(|result| U256::from_str_radix(result.trim_start_matches("0x"), 16).ok())
+            .ok_or_else(|| {
+                SystemError::ParseError(format!("Invalid eth_call result: {}", response))
+            })?;
+
+        if balance > U256::from(u128::MAX) {
+            return Err(SystemError::ParseError("Deposit exceeds u128".to_string()).into());
+        }
+        Ok(balance.as_u128())
+    }
+
     pub async fn transfer(
         chain_id: u64,
+        transaction_id: u64,
         to: &str,
         value: u128,
         token_address: Option<String>,
@@ -379,9 +454,17 @@ impl Ic2P2ramp {
             }
         };
 
-        logs::new_transaction_log(0, transaction_type.clone());
+        logs::new_transaction_log(transaction_id, transaction_type.clone());
 
-        broadcast_transaction(0, chain_id, transaction_type, request, None, 0, false);
+        broadcast_transaction(
+            transaction_id,
+            chain_id,
+            transaction_type,
+            request,
+            None,
+            0,
+            false,
+        );
         Ok(())
     }
 }
@@ -14,12 +14,16 @@ use icrc_ledger_types::icrc1::{account::Account, transfer::NumTokens};
 use evm::{fees, transaction, vault::Ic2P2ramp};
 use icp::vault::Ic2P2ramp as ICPRamp;
 use management::{
-    order as order_management, payment as payment_management, random, user as user_management,
+    order as order_management, payment as payment_management, providers::PayPal,
+    user as user_management, webhook,
 };
-use model::errors::{self, BlockchainError, OrderError, Result, SystemError, UserError};
+use model::errors::{self, BlockchainError, OrderError, RampError, Result, SystemError, UserError};
 use model::types::{
     self,
+    audit::{self, AdminAction, AuditEntry},
     evm::{
+        chains,
+        deposit::{DepositIntent, DepositIntentInput},
         gas::{self, ChainGasTracking},
         logs::{EvmTransactionLog, TransactionStatus},
         nonce,
@@ -27,10 +31,12 @@ use model::types::{
         transaction::{TransactionAction, TransactionVariant},
     },
     exchange_rate::{ExchangeRateCache, CACHE_DURATION},
+    gateway::{GatewayRequest, GatewayResponse},
     icp::{get_icp_token, IcpToken},
-    orders::{EvmOrderInput, OrderFilter, OrderState},
+    kyc::KycTier,
+    orders::{EvmOrderInput, OrderFilter, OrderState, PaymentTolerance, RefundStatus},
     session::Session,
-    user::{User, UserType},
+    user::{User, UserDataExport, UserType},
     AddressType, AuthenticationData, Blockchain, Crypto, LoginAddress, PaymentProvider,
     PaymentProviderType, TransactionAddress,
 };
@@ -42,7 +48,7 @@ use model::{
             self, initialize_state, logs, read_state, setup_timers, upgrade, InstallArg, State,
             STATE,
         },
-        stable::{self, orders, spent_transactions},
+        stable::{self, audit_log, login_attempts, orders, payment_ids, spent_transactions},
     },
 };
 use outcalls::{
@@ -107,6 +113,7 @@ fn init(install_arg: InstallArg) {
 
     ic_cdk::println!("[init] new state = {:?}", state);
     setup_timers();
+    heap::set_deposit_scan_timer();
 }
 
 #[ic_cdk::query]
@@ -174,16 +181,18 @@ async fn test_get_rates() -> HashMap<(String, String), ExchangeRateCache> {
 
 #[ic_cdk::update]
 async fn test_get_consent_url() -> Result<String> {
-    let consent_id = revolut::consent::create_account_access_consent(
-        "1.00",
-        "GBP",
-        "UK.OBIE.IBAN",
-        "GB14REVO04290956685580",
-        "UK.OBIE.SortCodeAccountNumber",
-        "04290956685580",
-        "Jan Smith",
-    )
-    .await?;
+    let consent_id =
+        revolut::consent::create_account_access_consent(&revolut::initiation::PaymentInitiation {
+            amount: "1.00".to_string(),
+            currency: "GBP".to_string(),
+            debtor_scheme: "UK.OBIE.IBAN".to_string(),
+            debtor_id: "GB14REVO04290956685580".to_string(),
+            creditor_scheme: "UK.OBIE.SortCodeAccountNumber".to_string(),
+            creditor_id: "04290956685580".to_string(),
+            creditor_name: "Jan Smith".to_string(),
+            reference: None,
+        })
+        .await?;
 
     revolut::authorize::get_authorization_url(&consent_id).await
 }
@@ -264,9 +273,8 @@ pub async fn create_evm_order_with_tx(
         Some(estimated_gas_lock),
         Some(estimated_gas_withdraw),
     )
-    .await?;
-
-    spent_transactions::mark_tx_hash_as_processed(tx_hash);
+    .await
+    .inspect_err(|_| spent_transactions::release_tx_hash(&tx_hash))?;
 
     Ok(order_id)
 }
@@ -303,6 +311,10 @@ pub async fn resolve_tx_status(order_id: u64, tx_hash: String, chain_id: u64) {
             ic_cdk::println!("Transaction {} failed: {}", tx_hash, reason);
             logs::update_transaction_log(order_id, TransactionStatus::Failed(reason));
         }
+        TransactionStatus::Reverted(receipt) => {
+            ic_cdk::println!("Transaction {} reverted!", tx_hash);
+            logs::update_transaction_log(order_id, TransactionStatus::Reverted(receipt));
+        }
         TransactionStatus::Pending => {
             ic_cdk::println!("Transaction {} is still pending.", tx_hash);
         }
@@ -328,7 +340,10 @@ fn print_constants() -> String {
         Onramper Crypto Fee = {}%\n\
         Evm Retry Attempts = {}\n\
         Evm Max Attempts per Retry = {}\n\
-        Evm Attempt Interval = {}",
+        Evm Attempt Interval = {}\n\
+        Bank Transfer Confirmation Time = {}s\n\
+        Payment Poll Interval = {}s to {}s\n\
+        Deposit Scan Interval = {}s",
         heap::LOCK_DURATION_TIME_SECONDS,
         CACHE_DURATION,
         nonce::LOCK_NONCE_TIME_SECONDS,
@@ -337,7 +352,11 @@ fn print_constants() -> String {
         (100. / types::orders::fees::ADMIN_CRYPTO_FEE_DENOM as f64),
         transaction::MAX_RETRY_ATTEMPTS,
         transaction::MAX_ATTEMPTS_PER_RETRY,
-        transaction::ATTEMPT_INTERVAL_SECONDS
+        transaction::ATTEMPT_INTERVAL_SECONDS,
+        heap::BANK_TRANSFER_CONFIRMATION_SECONDS,
+        heap::PAYMENT_POLL_INITIAL_SECONDS,
+        heap::PAYMENT_POLL_MAX_SECONDS,
+        heap::DEPOSIT_SCAN_INTERVAL_SECONDS
     )
 }
 
@@ -435,6 +454,14 @@ async fn withdraw_evm_fees(chain_id: u64, amount: u128, token: Option<String>) -
         token::evm_token_is_approved(chain_id, &token_address)?;
     }
 
+    // fees owed to referrers stay in the vault until they are claimed
+    let available = Ic2P2ramp::get_deposit(chain_id, canister_address.clone(), token.clone())
+        .await?
+        .saturating_sub(chains::get_reserved_fees(chain_id, &token));
+    if amount > available {
+        return Err(BlockchainError::FeesReserved(available).into());
+    }
+
     Ic2P2ramp::withdraw_deposit(chain_id, 0, canister_address, token, amount, 0).await?;
 
     Ok(())
@@ -454,7 +481,7 @@ async fn transfer_evm_funds(
     if let Some(token) = token.clone() {
         token::evm_token_is_approved(chain_id, &token)?;
     }
-    Ic2P2ramp::transfer(chain_id, &to, amount, token, estimated_gas).await
+    Ic2P2ramp::transfer(chain_id, 0, &to, amount, token, estimated_gas).await
 }
 
 // -----
@@ -467,8 +494,16 @@ async fn register_user(
     payment_providers: HashSet<PaymentProvider>,
     login_address: LoginAddress,
     password: Option<String>,
+    referrer_id: Option<u64>,
 ) -> Result<User> {
-    user_management::register_user(user_type, payment_providers, login_address, password).await
+    user_management::register_user(
+        user_type,
+        payment_providers,
+        login_address,
+        password,
+        referrer_id,
+    )
+    .await
 }
 
 #[ic_cdk::update]
@@ -479,11 +514,27 @@ async fn authenticate_user(
     login_address.validate()?;
     let user_id = stable::users::find_user_by_login_address(&login_address)?;
     let user = stable::users::get_user(&user_id)?;
-    user.verify_user_auth(auth_data)?;
+
+    login_attempts::check_login_allowed(&login_address)?;
+    user.verify_user_auth(&login_address, auth_data.clone())
+        .and_then(|_| user_management::verify_second_factor(user_id, &login_address, auth_data))
+        .inspect_err(|_| login_attempts::record_failed_login(&login_address))?;
+    login_attempts::clear_login_attempts(&login_address);
 
     user_management::set_session(user_id, &Session::new().await?)
 }
 
+#[ic_cdk::update]
+fn unlock_user_logins(user_id: u64) -> Result<()> {
+    guards::only_controller()?;
+    let user = stable::users::get_user(&user_id)?;
+    for login_address in user.logins() {
+        login_attempts::clear_login_attempts(&login_address);
+    }
+    audit_log::record_admin_action(user_id, AdminAction::UnlockLogins);
+    Ok(())
+}
+
 #[ic_cdk::update]
 async fn update_password(login_address: LoginAddress, new_password: Option<String>) -> Result<()> {
     // the token that is passed in the email,
@@ -495,43 +546,274 @@ async fn update_password(login_address: LoginAddress, new_password: Option<Strin
 #[ic_cdk::update]
 async fn generate_evm_auth_message(login_address: LoginAddress) -> Result<String> {
     login_address.validate()?;
-    let address = if let LoginAddress::EVM { address } = login_address.clone() {
-        Ok(address)
-    } else {
-        Err(SystemError::InvalidInput(
-            "Login address is not of type EVM".to_string(),
-        ))
-    }?;
-
     let user_id = stable::users::find_user_by_login_address(&login_address)?;
-    let auth_message = format!(
-        "Please sign this message to authenticate: {}\nNonce: {}",
-        address,
-        random::generate_token().await?
-    );
+    let auth_message = user_management::build_evm_auth_message(&login_address).await?;
 
     user_management::update_user_auth_message(user_id, &auth_message)?;
 
     Ok(auth_message)
 }
 
+#[ic_cdk::update]
+async fn generate_evm_link_message(
+    user_id: u64,
+    session_token: String,
+    login_address: LoginAddress,
+) -> Result<String> {
+    login_address.validate()?;
+    let user = stable::users::get_user(&user_id)?;
+    user.validate_session(&session_token)?;
+    let auth_message = user_management::build_evm_auth_message(&login_address).await?;
+
+    user_management::update_user_auth_message(user_id, &auth_message)?;
+
+    Ok(auth_message)
+}
+
+#[ic_cdk::update]
+async fn link_login_address(
+    user_id: u64,
+    session_token: String,
+    login_address: LoginAddress,
+    auth_data: Option<AuthenticationData>,
+) -> Result<User> {
+    user_management::link_login_address(user_id, &session_token, login_address, auth_data).await
+}
+
+#[ic_cdk::update]
+fn confirm_email_link(user_id: u64, session_token: String, code: String) -> Result<User> {
+    user_management::confirm_email_link(user_id, &session_token, &code)
+}
+
+// <user_id, email, code> for the mailer delivering verification codes
+#[ic_cdk::query]
+fn get_pending_email_links() -> Result<Vec<(u64, String, String)>> {
+    guards::only_controller()?;
+    Ok(stable::users::get_pending_email_links())
+}
+
+#[ic_cdk::update]
+fn unlink_login_address(
+    user_id: u64,
+    session_token: String,
+    login_address: LoginAddress,
+) -> Result<User> {
+    user_management::unlink_login_address(user_id, &session_token, &login_address)
+}
+
+// <secret, otpauth_uri>
+#[ic_cdk::update]
+async fn setup_totp(user_id: u64, session_token: String) -> Result<(String, String)> {
+    user_management::setup_totp(user_id, &session_token).await
+}
+
+#[ic_cdk::update]
+async fn enable_totp(user_id: u64, session_token: String, code: String) -> Result<Vec<String>> {
+    user_management::enable_totp(user_id, &session_token, &code).await
+}
+
+#[ic_cdk::update]
+fn disable_totp(user_id: u64, session_token: String, code: String) -> Result<()> {
+    user_management::disable_totp(user_id, &session_token, &code)
+}
+
+#[ic_cdk::update]
+async fn regenerate_totp_recovery_codes(
+    user_id: u64,
+    session_token: String,
+    code: String,
+) -> Result<Vec<String>> {
+    user_management::regenerate_recovery_codes(user_id, &session_token, &code).await
+}
+
+#[ic_cdk::update]
+async fn claim_referral_rewards(
+    user_id: u64,
+    session_token: String,
+    blockchain: Blockchain,
+    token: Option<String>,
+) -> Result<u128> {
+    user_management::claim_referral_rewards(user_id, &session_token, blockchain, token).await
+}
+
+// settles an EVM referral claim whose transaction outcome was not tracked, e.g. across an upgrade
+#[ic_cdk::update]
+fn settle_referral_claim(claim_id: u64, transferred: bool) -> Result<()> {
+    guards::only_controller()?;
+    if types::referral::get_claim(claim_id).is_none() {
+        return Err(SystemError::InvalidInput("Referral claim not found".to_string()).into());
+    }
+    if transferred {
+        user_management::complete_referral_claim(claim_id);
+    } else {
+        user_management::fail_referral_claim(claim_id);
+    }
+    Ok(())
+}
+
+#[ic_cdk::update]
+fn set_referral_fee_bps(referral_fee_bps: u16) -> Result<()> {
+    guards::only_controller()?;
+    if referral_fee_bps > types::orders::fees::MAX_REFERRAL_FEE_BPS {
+        return Err(SystemError::InvalidInput("Referral fee share is too high".to_string()).into());
+    }
+    heap::mutate_state(|state| state.referral_fee_bps = Some(referral_fee_bps));
+    Ok(())
+}
+
+#[ic_cdk::update]
+fn set_fx_tolerance_bps(fx_tolerance_bps: u16) -> Result<()> {
+    guards::only_controller()?;
+    if fx_tolerance_bps > types::orders::MAX_FX_TOLERANCE_BPS {
+        return Err(SystemError::InvalidInput("FX tolerance is too high".to_string()).into());
+    }
+    heap::mutate_state(|state| state.fx_tolerance_bps = Some(fx_tolerance_bps));
+    Ok(())
+}
+
+#[ic_cdk::update]
+fn set_payment_tolerance(payment_tolerance: PaymentTolerance) -> Result<()> {
+    guards::only_controller()?;
+    payment_tolerance.validate()?;
+    heap::mutate_state(|state| state.payment_tolerance = Some(payment_tolerance));
+    Ok(())
+}
+
 #[ic_cdk::query]
 fn refetch_user(user_id: u64, token: String) -> Result<User> {
-    let user = stable::users::get_user(&user_id)?;
+    let mut user = stable::users::get_user(&user_id)?;
     user.validate_session(&token)?;
-    Ok(user)
+    user.refresh_score(ic_cdk::api::time());
+    Ok(user.redacted())
 }
 
 #[ic_cdk::query]
 fn get_user(user_id: u64) -> Result<User> {
     guards::only_controller()?;
-    stable::users::get_user(&user_id)
+    let mut user = stable::users::get_user(&user_id)?;
+    user.refresh_score(ic_cdk::api::time());
+    Ok(user)
+}
+
+#[ic_cdk::update]
+fn add_kyc_attestor(attestor: Principal) -> Result<()> {
+    guards::only_controller()?;
+    heap::mutate_state(|state| {
+        state
+            .kyc_attestors
+            .get_or_insert_with(HashSet::new)
+            .insert(attestor);
+    });
+    Ok(())
+}
+
+#[ic_cdk::update]
+fn remove_kyc_attestor(attestor: Principal) -> Result<()> {
+    guards::only_controller()?;
+    heap::mutate_state(|state| {
+        if let Some(attestors) = state.kyc_attestors.as_mut() {
+            attestors.remove(&attestor);
+        }
+    });
+    Ok(())
+}
+
+#[ic_cdk::update]
+fn set_user_kyc_tier(user_id: u64, kyc_tier: KycTier) -> Result<()> {
+    guards::only_kyc_attestor()?;
+    user_management::set_kyc_tier(user_id, kyc_tier.clone())?;
+    audit_log::record_admin_action(user_id, AdminAction::SetKycTier(kyc_tier));
+    Ok(())
+}
+
+#[ic_cdk::update]
+fn record_user_dispute_lost(user_id: u64) -> Result<()> {
+    guards::only_controller()?;
+    user_management::record_dispute_lost(user_id)?;
+    audit_log::record_admin_action(user_id, AdminAction::RecordDisputeLost);
+    Ok(())
+}
+
+#[ic_cdk::update]
+fn freeze_user(user_id: u64, reason: String) -> Result<()> {
+    guards::only_controller()?;
+    audit::validate_reason(&reason)?;
+    user_management::freeze_user(user_id, Some(reason.clone()))?;
+    audit_log::record_admin_action(user_id, AdminAction::Freeze { reason });
+    Ok(())
+}
+
+#[ic_cdk::update]
+fn unfreeze_user(user_id: u64) -> Result<()> {
+    guards::only_controller()?;
+    user_management::freeze_user(user_id, None)?;
+    audit_log::record_admin_action(user_id, AdminAction::Unfreeze);
+    Ok(())
+}
+
+#[ic_cdk::update]
+fn adjust_user_reputation(user_id: u64, delta: i32, reason: String) -> Result<User> {
+    guards::only_controller()?;
+    audit::validate_reason(&reason)?;
+    let user = user_management::adjust_reputation(user_id, delta)?;
+    audit_log::record_admin_action(user_id, AdminAction::AdjustReputation { delta, reason });
+    Ok(user)
+}
+
+// returns the orders that failed to unlock, with their error
+#[ic_cdk::update]
+async fn force_unlock_user_orders(user_id: u64) -> Result<Vec<(u64, RampError)>> {
+    guards::only_controller()?;
+    let user = stable::users::get_user(&user_id)?;
+
+    let mut failed = vec![];
+    for (order_id, order_state) in orders::get_user_orders(&user) {
+        let OrderState::Locked(_) = order_state else {
+            continue;
+        };
+        if let Err(e) = orders::set_processing_order(&order_id) {
+            failed.push((order_id, e));
+            continue;
+        }
+        match order_management::force_unlock_order(order_id).await {
+            Ok(()) => {
+                audit_log::record_admin_action(user_id, AdminAction::ForceUnlockOrder { order_id })
+            }
+            Err(e) => {
+                orders::unset_processing_order(&order_id)?;
+                failed.push((order_id, e));
+            }
+        }
+    }
+
+    Ok(failed)
+}
+
+#[ic_cdk::query]
+fn get_user_audit_log(user_id: u64) -> Result<Vec<AuditEntry>> {
+    guards::only_controller()?;
+    Ok(audit_log::get_user_audit_log(user_id))
 }
 
 #[ic_cdk::update]
 fn remove_user(user_id: u64) -> Result<User> {
     guards::only_controller()?;
-    stable::users::remove_user(&user_id)
+    let user = user_management::delete_user(user_id)?;
+    audit_log::record_admin_action(user_id, AdminAction::RemoveUser);
+    Ok(user)
+}
+
+#[ic_cdk::query]
+fn export_user_data(user_id: u64, session_token: String) -> Result<UserDataExport> {
+    user_management::export_user_data(user_id, &session_token)
+}
+
+#[ic_cdk::update]
+fn delete_user(user_id: u64, session_token: String) -> Result<()> {
+    let user = stable::users::get_user(&user_id)?;
+    user.validate_session(&session_token)?;
+    user_management::delete_user(user_id)?;
+    Ok(())
 }
 
 #[ic_cdk::update]
@@ -617,6 +899,28 @@ async fn calculate_order_price(currency: String, crypto: Crypto) -> Result<(u64,
     order_management::calculate_price_and_fee(&currency, &crypto).await
 }
 
+// to be called before funding an order, as the deposit cannot be refused afterwards
+#[ic_cdk::update]
+async fn check_order_kyc_limits(
+    user_id: u64,
+    session_token: String,
+    currency: String,
+    blockchain: Blockchain,
+    token: Option<String>,
+    crypto_amount: u128,
+) -> Result<()> {
+    let user = stable::users::get_user(&user_id)?;
+    user.validate_session(&session_token)?;
+    order_management::check_kyc_limits(
+        user_id,
+        &currency,
+        None,
+        &Crypto::new(blockchain, token, crypto_amount, 0),
+    )
+    .await
+    .map(|_| ())
+}
+
 #[ic_cdk::query]
 async fn get_offramper_fee(price: u64) -> u64 {
     price / types::orders::fees::OFFRAMPER_FIAT_FEE_DENOM
@@ -683,15 +987,42 @@ async fn create_order(
         evm_input.clone().map(|evm| evm.estimated_gas_lock),
         evm_input.map(|evm| evm.estimated_gas_withdraw),
     )
-    .await?;
-
-    if let Some(tx_hash) = tx_hash {
-        spent_transactions::mark_tx_hash_as_processed(tx_hash);
-    };
+    .await
+    .inspect_err(|_| {
+        if let Some(tx_hash) = &tx_hash {
+            spent_transactions::release_tx_hash(tx_hash);
+        }
+    })?;
 
     Ok(order_id)
 }
 
+/// Announces an EVM order to be created automatically once its vault deposit
+/// is found, without passing the deposit transaction hash.
+#[ic_cdk::update]
+async fn register_deposit_intent(
+    session_token: String,
+    input: DepositIntentInput,
+) -> Result<String> {
+    management::deposit::register_deposit_intent(session_token, input).await
+}
+
+#[ic_cdk::query]
+fn get_deposit_intents(user_id: u64, session_token: String) -> Result<Vec<DepositIntent>> {
+    let user = stable::users::get_user(&user_id)?;
+    user.validate_session(&session_token)?;
+    Ok(stable::deposit_intents::get_user_intents(user_id))
+}
+
+#[ic_cdk::update]
+fn cancel_deposit_intent(user_id: u64, session_token: String, intent_key: String) -> Result<()> {
+    let user = stable::users::get_user(&user_id)?;
+    user.validate_session(&session_token)?;
+    stable::deposit_intents::remove_user_intent(user_id, &intent_key)
+        .map(|_| ())
+        .ok_or_else(|| SystemError::InvalidInput("Deposit intent not found".to_string()).into())
+}
+
 #[ic_cdk::update]
 async fn freeze_order(order_id: u64, user_id: u64, session_token: String) -> Result<()> {
     let order = orders::get_order(&order_id)?.created()?;
@@ -735,11 +1066,12 @@ async fn top_up_order(
     })?;
 
     order_management::topup_order(&order, amount, estimated_gas_lock, estimated_gas_withdraw)
-        .await?;
-
-    if let Some(tx_hash) = tx_hash {
-        spent_transactions::mark_tx_hash_as_processed(tx_hash);
-    };
+        .await
+        .inspect_err(|_| {
+            if let Some(tx_hash) = &tx_hash {
+                spent_transactions::release_tx_hash(tx_hash);
+            }
+        })?;
 
     orders::unset_processing_order(&order_id)
 }
@@ -789,6 +1121,15 @@ async fn retry_order_unlock(order_id: u64) -> Result<()> {
     Ok(())
 }
 
+#[ic_cdk::update]
+fn set_order_accepted_currencies(
+    order_id: u64,
+    session_token: String,
+    currencies: Vec<String>,
+) -> Result<()> {
+    order_management::set_accepted_currencies(order_id, session_token, currencies)
+}
+
 #[ic_cdk::update]
 async fn cancel_order(order_id: u64, session_token: String) -> Result<()> {
     orders::set_processing_order(&order_id)?;
@@ -801,6 +1142,32 @@ async fn cancel_order(order_id: u64, session_token: String) -> Result<()> {
     Ok(())
 }
 
+// --------------
+// PayPal Payment
+// --------------
+#[ic_cdk::update]
+async fn create_paypal_order(order_id: u64, session_token: String) -> Result<String> {
+    orders::set_processing_order(&order_id)?;
+
+    let result = process_paypal_order_creation(order_id, session_token).await;
+    orders::unset_processing_order(&order_id)?;
+    result
+}
+
+async fn process_paypal_order_creation(order_id: u64, session_token: String) -> Result<String> {
+    let order = order_management::verify_order_is_payable(order_id, Some(session_token))?;
+    if order.onramper.provider.provider_type() != PaymentProviderType::PayPal {
+        return Err(OrderError::InvalidOnramperProvider.into());
+    }
+    if let Some(paypal_order_id) = order.payment_id {
+        return Ok(paypal_order_id);
+    }
+
+    let paypal_order_id = PayPal::create_order(&order).await?;
+    order_management::set_payment_id(order_id, paypal_order_id.clone())?;
+    Ok(paypal_order_id)
+}
+
 // ---------------
 // Revolut Payment
 // ---------------
@@ -809,6 +1176,133 @@ async fn execute_revolut_payment(order_id: u64, session_token: String) -> Result
     revolut_token::wait_for_revolut_access_token(order_id, &session_token, 10, 3).await
 }
 
+// -------------
+// Bank Transfer
+// -------------
+#[ic_cdk::update]
+fn mark_bank_transfer_sent(order_id: u64, session_token: String, reference: String) -> Result<()> {
+    order_management::claim_bank_transfer(order_id, session_token, reference)
+}
+
+#[ic_cdk::update]
+async fn confirm_bank_transfer(order_id: u64, session_token: String) -> Result<()> {
+    orders::set_processing_order(&order_id)?;
+
+    if let Err(e) = process_bank_transfer_confirmation(order_id, session_token).await {
+        orders::unset_processing_order(&order_id)?;
+        return Err(e);
+    }
+
+    Ok(())
+}
+
+async fn process_bank_transfer_confirmation(order_id: u64, session_token: String) -> Result<()> {
+    let order = order_management::confirm_bank_transfer(order_id, session_token)?;
+    payment_management::handle_payment_completion(&order).await
+}
+
+#[ic_cdk::update]
+async fn resolve_bank_transfer_dispute(order_id: u64, payment_received: bool) -> Result<()> {
+    guards::only_controller()?;
+    let order = stable::orders::get_order(&order_id)?.locked()?;
+    orders::set_processing_order(&order_id)?;
+
+    if let Err(e) =
+        order_management::resolve_bank_transfer_dispute(order_id, payment_received).await
+    {
+        orders::unset_processing_order(&order_id)?;
+        return Err(e);
+    }
+
+    let action = AdminAction::ResolveDispute {
+        order_id,
+        paid: payment_received,
+    };
+    for user_id in [order.base.offramper_user_id, order.onramper.user_id] {
+        audit_log::record_admin_action(user_id, action.clone());
+    }
+    Ok(())
+}
+
+#[ic_cdk::update]
+async fn resolve_partial_payment_dispute(order_id: u64, refunded: bool) -> Result<()> {
+    guards::only_controller()?;
+    let order = stable::orders::get_order(&order_id)?.locked()?;
+    orders::set_processing_order(&order_id)?;
+
+    if let Err(e) = order_management::resolve_partial_payment_dispute(order_id, refunded).await {
+        orders::unset_processing_order(&order_id)?;
+        return Err(e);
+    }
+
+    let action = AdminAction::ResolvePartialPaymentDispute { order_id, refunded };
+    for user_id in [order.base.offramper_user_id, order.onramper.user_id] {
+        audit_log::record_admin_action(user_id, action.clone());
+    }
+    Ok(())
+}
+
+#[ic_cdk::update]
+fn resolve_unconfirmed_release(order_id: u64, released: bool) -> Result<()> {
+    guards::only_controller()?;
+    let order = stable::orders::get_order(&order_id)?.locked()?;
+    order_management::resolve_unconfirmed_release(order_id, released)?;
+
+    let action = AdminAction::ResolveUnconfirmedRelease { order_id, released };
+    for user_id in [order.base.offramper_user_id, order.onramper.user_id] {
+        audit_log::record_admin_action(user_id, action.clone());
+    }
+    Ok(())
+}
+
+// ------------
+// HTTP Gateway
+// ------------
+const PAYPAL_WEBHOOK_PATH: &str = "/webhooks/paypal";
+
+#[ic_cdk::query]
+fn http_request(request: GatewayRequest) -> GatewayResponse {
+    match (request.method.as_str(), request.path()) {
+        ("POST", PAYPAL_WEBHOOK_PATH) => GatewayResponse::upgrade(),
+        _ => GatewayResponse::new(404, "Not Found"),
+    }
+}
+
+#[ic_cdk::update]
+async fn http_request_update(request: GatewayRequest) -> GatewayResponse {
+    match (request.method.as_str(), request.path()) {
+        ("POST", PAYPAL_WEBHOOK_PATH) => webhook::handle_paypal_webhook(&request).await,
+        _ => GatewayResponse::new(404, "Not Found"),
+    }
+}
+
+// -------
+// Refunds
+// -------
+#[ic_cdk::update]
+async fn refund_order(order_id: u64) -> Result<RefundStatus> {
+    guards::only_controller()?;
+    let order = stable::orders::get_order(&order_id)?.locked()?;
+    orders::set_processing_order(&order_id)?;
+
+    let result = order_management::refund_order(order_id).await;
+    orders::unset_processing_order(&order_id)?;
+    if let Ok(RefundStatus::Refunded { .. }) = result {
+        order_management::unlock_refunded_order(order_id).await;
+    }
+
+    let action = AdminAction::RefundOrder { order_id };
+    for user_id in [order.base.offramper_user_id, order.onramper.user_id] {
+        audit_log::record_admin_action(user_id, action.clone());
+    }
+    result
+}
+
+#[ic_cdk::update]
+async fn complete_revolut_refund(order_id: u64, session_token: String) -> Result<()> {
+    order_management::complete_revolut_refund(order_id, session_token).await
+}
+
 // --------------------
 // Payment Verification
 // --------------------
@@ -818,6 +1312,18 @@ fn verify_order_is_payable(order_id: u64, session_token: String) -> Result<()> {
     Ok(())
 }
 
+#[ic_cdk::query]
+fn get_payment_id_consumer(
+    provider_type: PaymentProviderType,
+    payment_id: String,
+) -> Result<Option<u64>> {
+    guards::only_controller()?;
+    Ok(payment_ids::get_payment_id_consumer(
+        &provider_type,
+        &payment_id,
+    ))
+}
+
 #[ic_cdk::update]
 async fn retry_order_completion(order_id: u64) -> Result<()> {
     guards::only_controller()?;
@@ -826,6 +1332,14 @@ async fn retry_order_completion(order_id: u64) -> Result<()> {
     if !order.payment_done {
         return Err(OrderError::PaymentVerificationFailed)?;
     };
+    if order.refund.is_some() {
+        return Err(OrderError::RefundInProgress)?;
+    }
+    if order.release_unconfirmed.is_some() {
+        return Err(OrderError::InvalidOrderState(
+            "Release is held for review".to_string(),
+        ))?;
+    }
 
     payment_management::handle_payment_completion(&order).await
 }
@@ -852,38 +1366,62 @@ async fn verify_transaction(
     Ok(())
 }
 
+/// Settles an underpaid order for the share its payments cover instead of
+/// topping it up. The rest of the crypto is put back on sale.
+#[ic_cdk::update]
+async fn accept_partial_fill(order_id: u64, session_token: String) -> Result<()> {
+    let order = stable::orders::get_order(&order_id)?.locked()?;
+    let user = stable::users::get_user(&order.onramper.user_id)?;
+    user.validate_session(&session_token)?;
+    orders::set_processing_order(&order_id)?;
+
+    if let Err(e) = payment_management::settle_partial_fill(order_id).await {
+        orders::unset_processing_order(&order_id)?;
+        return Err(e);
+    }
+
+    Ok(())
+}
+
+#[ic_cdk::update]
+fn confirm_overpayment_refund(
+    order_id: u64,
+    session_token: String,
+    refund_reference: String,
+) -> Result<()> {
+    order_management::confirm_overpayment_refund(order_id, session_token, refund_reference)
+}
+
 async fn process_transaction(
     order_id: u64,
     session_token: Option<String>,
     transaction_id: String,
 ) -> Result<()> {
     let order = order_management::verify_order_is_payable(order_id, session_token)?;
+    payment_management::settle_payment(&order, &transaction_id).await
+}
 
-    match &order.clone().onramper.provider {
-        PaymentProvider::PayPal { id: onramper_id } => {
-            ic_cdk::println!("[verify_transaction] Handling Paypal payment verification");
-
-            payment_management::verify_paypal_payment(onramper_id, &transaction_id, &order).await?
-        }
+// --------------------
+// Mock Payment Provider
+// --------------------
+#[cfg(feature = "mock-provider")]
+use management::providers::{mock::MockOutcome, Mock};
 
-        PaymentProvider::Revolut {
-            scheme: onramper_scheme,
-            id: onramper_id,
-            name: _,
-        } => {
-            ic_cdk::println!("[verify_transaction] Handling Revolut payment verification");
-
-            payment_management::verify_revolut_payment(
-                onramper_id,
-                onramper_scheme,
-                &transaction_id,
-                &order,
-            )
-            .await?
-        }
-    }
+#[cfg(feature = "mock-provider")]
+#[ic_cdk::update]
+fn mock_simulate_payment(order_id: u64, outcome: MockOutcome) -> Result<String> {
+    guards::only_controller()?;
+    let order = stable::orders::get_order(&order_id)?.locked()?;
+    Mock::simulate_payment(&order, outcome)
+}
 
-    payment_management::handle_payment_completion(&order).await
+#[cfg(feature = "mock-provider")]
+#[ic_cdk::update]
+async fn mock_settle_payment(transaction_id: String) -> Result<()> {
+    guards::only_controller()?;
+    let order_id = Mock::settle_payment(&transaction_id)?;
+    payment_management::poll_payment(order_id).await?;
+    Ok(())
 }
 
 ic_cdk::export_candid!();
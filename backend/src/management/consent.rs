@@ -0,0 +1,58 @@
+use crate::{
+    errors::{OrderError, Result},
+    model::memory::stable::orders,
+    outcalls::revolut,
+    types::orders::{OrderState, RevolutConsentStatus},
+};
+
+pub fn set_consent_status(order_id: u64, status: RevolutConsentStatus) -> Result<()> {
+    orders::mutate_order(&order_id, |order_state| match order_state {
+        OrderState::Locked(order) => {
+            let consent = order
+                .revolut_consent
+                .as_mut()
+                .ok_or(OrderError::InvalidOnramperProvider)?;
+            consent.status = Some(status);
+            Ok(())
+        }
+        _ => Err(OrderError::InvalidOrderState(order_state.to_string()))?,
+    })?
+}
+
+/// Fetches the status of a locked order's Revolut consent and records it.
+/// Returns whether the consent can still change and should be polled again.
+pub async fn refresh_consent_status(order_id: u64) -> Result<bool> {
+    let Some(order) = orders::get_order(&order_id)
+        .ok()
+        .and_then(|order_state| order_state.locked().ok())
+    else {
+        return Ok(false);
+    };
+    let Some(consent) = &order.revolut_consent else {
+        return Ok(false);
+    };
+    if consent.status().is_final() || order.payment_done || !order.is_inside_lock_time() {
+        return Ok(false);
+    }
+
+    let status = revolut::consent::fetch_consent_status(&consent.id).await?;
+    ic_cdk::println!(
+        "[refresh_consent_status] consent of order {} is {:?}",
+        order_id,
+        status
+    );
+    let polled_again = !status.is_final();
+    set_consent_status(order_id, status)?;
+    Ok(polled_again)
+}
+
+/// Whether a locked order has a Revolut consent that can still change.
+pub fn has_open_consent(order_id: u64) -> bool {
+    orders::get_order(&order_id)
+        .and_then(|order_state| order_state.locked())
+        .is_ok_and(|order| {
+            order
+                .revolut_consent
+                .is_some_and(|consent| !consent.status().is_final())
+        })
+}
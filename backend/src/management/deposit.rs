@@ -0,0 +1,275 @@
+use evm_rpc_canister_types::BlockTag;
+use num_traits::ToPrimitive;
+
+use super::providers;
+use crate::{
+    errors::{BlockchainError, OrderError, RampError, Result, SystemError, UserError},
+    evm::{
+        event::{self, DepositEvent},
+        fees::eth_get_latest_block,
+        vault::Ic2P2ramp,
+    },
+    model::memory::{
+        self,
+        heap::read_state,
+        stable::{deposit_intents, spent_transactions},
+    },
+    types::{
+        evm::{
+            chains,
+            deposit::{DepositIntent, DepositIntentInput},
+            token,
+            transaction::{TransactionAction, TransactionVariant},
+        },
+        AddressType, Blockchain, Crypto, TransactionAddress,
+    },
+};
+
+// Blocks a deposit has to be buried under before its order is created
+const DEPOSIT_CONFIRMATION_BLOCKS: u64 = 3;
+// Largest block range requested from `eth_getLogs` in a single scan
+const MAX_SCAN_BLOCK_RANGE: u64 = 500;
+
+/// Registers the order an offramper is about to fund with a vault deposit,
+/// returning the key of the intent. The vault logs of the chain are scanned
+/// from the current block on until no deposit is awaited anymore.
+pub async fn register_deposit_intent(
+    session_token: String,
+    input: DepositIntentInput,
+) -> Result<String> {
+    let user = memory::stable::users::get_user(&input.offramper_user_id)?;
+    user.validate_session(&session_token)?;
+    user.is_banned()?;
+    user.is_offramper()?;
+
+    for (provider_type, provider) in &input.offramper_providers {
+        if !user.payment_providers.contains(provider) {
+            return Err(UserError::ProviderNotInUser(provider_type.clone()))?;
+        }
+    }
+    if !user.addresses.iter().any(|address| {
+        address.address_type == AddressType::EVM
+            && address
+                .address
+                .eq_ignore_ascii_case(&input.offramper_address)
+    }) {
+        return Err(UserError::Unauthorized.into());
+    }
+    if input.amount == 0 {
+        return Err(SystemError::InvalidInput("Deposit amount is zero".to_string()).into());
+    }
+    if let Some(provider_type) = input
+        .offramper_providers
+        .keys()
+        .find(|provider_type| !providers::supports_currency(provider_type, &input.currency))
+    {
+        return Err(OrderError::UnsupportedCurrency(
+            provider_type.clone(),
+            input.currency.clone(),
+        ))?;
+    }
+
+    chains::chain_is_supported(input.chain_id)?;
+    chains::get_vault_manager_address(input.chain_id)?;
+    if let Some(token) = &input.token {
+        token::evm_token_is_approved(input.chain_id, token)?;
+    }
+    // a deposit rejected once it sits in the vault would be stranded there
+    super::order::check_kyc_limits(
+        input.offramper_user_id,
+        &input.currency,
+        None,
+        &Crypto::new(
+            Blockchain::EVM {
+                chain_id: input.chain_id,
+            },
+            input.token.clone(),
+            input.amount,
+            0,
+        ),
+    )
+    .await?;
+
+    if chains::get_last_scanned_block(input.chain_id)?.is_none() {
+        let latest_block = latest_block_number(input.chain_id).await?;
+        chains::set_last_scanned_block(input.chain_id, Some(latest_block));
+    }
+
+    let intent = DepositIntent::new(input);
+    let key = intent.intent_key();
+    deposit_intents::insert_intent(intent)?;
+    Ok(key)
+}
+
+/// Scans the vault logs of every chain awaiting a deposit, creating the orders
+/// of the deposits that match an intent.
+pub async fn scan_deposits() {
+    deposit_intents::discard_expired_intents();
+
+    let chain_ids: Vec<u64> = read_state(|s| s.chains.keys().copied().collect());
+    for chain_id in chain_ids {
+        if let Err(e) = scan_chain(chain_id).await {
+            ic_cdk::println!("[scan_deposits] failed to scan chain {}: {:?}", chain_id, e);
+        }
+    }
+}
+
+async fn scan_chain(chain_id: u64) -> Result<()> {
+    let Some(last_scanned_block) = chains::get_last_scanned_block(chain_id)? else {
+        return Ok(());
+    };
+    if !deposit_intents::has_intents(chain_id) {
+        chains::set_last_scanned_block(chain_id, None);
+        return Ok(());
+    }
+
+    let from_block = last_scanned_block + 1;
+    let to_block = latest_block_number(chain_id)
+        .await?
+        .saturating_sub(DEPOSIT_CONFIRMATION_BLOCKS)
+        .min(from_block + MAX_SCAN_BLOCK_RANGE - 1);
+    if to_block < from_block {
+        return Ok(());
+    }
+
+    let deposits = event::get_deposit_events(chain_id, from_block, to_block).await?;
+    ic_cdk::println!(
+        "[scan_chain] chain {}: {} deposits in blocks {} to {}",
+        chain_id,
+        deposits.len(),
+        from_block,
+        to_block
+    );
+    // Deposits that failed for a transient reason are scanned again on the next
+    // round, until their intent expires
+    let mut scanned_to_block = to_block;
+    for (tx_hash, deposit) in deposits {
+        if let Err(e) = create_deposit_order(chain_id, &tx_hash, &deposit).await {
+            ic_cdk::println!(
+                "[scan_chain] failed to create the order of deposit {}: {:?}",
+                tx_hash,
+                e
+            );
+            if !is_transient(&e) {
+                continue;
+            }
+            let block = deposit
+                .block
+                .and_then(|block| u64::try_from(block).ok())
+                .unwrap_or(from_block);
+            scanned_to_block = scanned_to_block.min(block.saturating_sub(1));
+        }
+    }
+
+    chains::set_last_scanned_block(chain_id, Some(scanned_to_block));
+    Ok(())
+}
+
+/// Creates the order of a deposit matching an intent. Deposits without intent
+/// are left for the offramper to register with their transaction hash.
+///
+/// The intent is taken and the deposit reserved before any await. The deposit
+/// is released if the order cannot be created, and the intent restored only if
+/// the failure is transient: a rejected deposit is left for the offramper to
+/// register with its transaction hash.
+async fn create_deposit_order(chain_id: u64, tx_hash: &str, deposit: &DepositEvent) -> Result<()> {
+    let tx_hash = tx_hash.to_string();
+    if spent_transactions::is_tx_hash_processed(&tx_hash) {
+        return Ok(());
+    }
+    let key = DepositIntent::key(
+        chain_id,
+        &deposit.user,
+        deposit.token.as_deref(),
+        deposit.amount,
+    );
+    let Some(intent) = deposit_intents::take_intent(&key) else {
+        return Ok(());
+    };
+    spent_transactions::mark_tx_hash_as_processed(tx_hash.clone());
+
+    match create_intent_order(chain_id, intent.clone()).await {
+        Ok(order_id) => {
+            ic_cdk::println!(
+                "[create_deposit_order] deposit {} created order {}",
+                tx_hash,
+                order_id
+            );
+            Ok(())
+        }
+        Err(e) => {
+            spent_transactions::release_tx_hash(&tx_hash);
+            if !is_transient(&e) {
+                ic_cdk::println!("[create_deposit_order] intent {} dropped: {:?}", key, e);
+                return Err(e);
+            }
+            if let Err(e) = deposit_intents::insert_intent(intent) {
+                ic_cdk::println!(
+                    "[create_deposit_order] intent {} not restored: {:?}",
+                    key,
+                    e
+                );
+            }
+            Err(e)
+        }
+    }
+}
+
+/// Whether the order of a deposit failed for a reason that may not hold on the
+/// next scan, such as an unavailable RPC provider or gas price.
+fn is_transient(error: &RampError) -> bool {
+    matches!(
+        error,
+        RampError::SystemError(
+            SystemError::RpcError(_)
+                | SystemError::ICRejectionError(..)
+                | SystemError::HttpRequestError(..)
+                | SystemError::CanisterCallError(_)
+                | SystemError::ExchangeRateError(_)
+        ) | RampError::BlockchainError(
+            BlockchainError::GasLogError(_)
+                | BlockchainError::GasEstimationFailed
+                | BlockchainError::InconsistentStatus
+                | BlockchainError::NonceLockTimeout(_)
+        )
+    )
+}
+
+async fn create_intent_order(chain_id: u64, intent: DepositIntent) -> Result<u64> {
+    let transaction_variant = match intent.token {
+        Some(_) => TransactionVariant::Token,
+        None => TransactionVariant::Native,
+    };
+    let estimated_gas_lock =
+        Ic2P2ramp::get_average_gas_price(chain_id, &TransactionAction::Commit).await?;
+    let estimated_gas_withdraw = Ic2P2ramp::get_average_gas_price(
+        chain_id,
+        &TransactionAction::Release(transaction_variant),
+    )
+    .await?;
+
+    super::order::create_order(
+        &intent.currency,
+        intent.offramper_user_id,
+        TransactionAddress {
+            address_type: AddressType::EVM,
+            address: intent.offramper_address,
+        },
+        intent.offramper_providers,
+        Blockchain::EVM { chain_id },
+        intent.token,
+        intent.amount,
+        Some(estimated_gas_lock),
+        Some(estimated_gas_withdraw),
+    )
+    .await
+}
+
+async fn latest_block_number(chain_id: u64) -> Result<u64> {
+    eth_get_latest_block(chain_id, BlockTag::Latest)
+        .await?
+        .number
+        .0
+        .to_u64()
+        .ok_or_else(|| BlockchainError::EvmLogError("Invalid block number".to_string()).into())
+}
@@ -1,8 +1,13 @@
+pub mod consent;
+pub mod deposit;
 pub mod order;
 pub mod payment;
+pub mod providers;
 pub mod random;
+pub mod totp;
 pub mod user;
 pub mod vault;
+pub mod webhook;
 
 pub fn on_fail_callback(order_id: u64) -> impl Fn() + 'static {
     move || match crate::memory::stable::orders::unset_processing_order(&order_id) {
@@ -17,21 +17,27 @@ use crate::management::user as user_management;
 use crate::model::guards;
 use crate::model::{
     helpers,
-    memory::{self, stable::spent_transactions},
+    memory::{
+        self,
+        stable::{payment_ids, spent_transactions},
+    },
 };
 use crate::outcalls::xrc_rates::{get_cached_exchange_rate, Asset, AssetClass};
 use crate::types::{
     self,
     evm::{chains, logs::TransactionStatus, token, transaction::TransactionAction},
     icp::{get_icp_token, is_icp_token_supported},
+    kyc::KYC_LIMITS_CURRENCY,
     orders::{
-        fees::{get_crypto_fee, get_fiat_fee},
-        EvmOrderInput, LockInput, LockedOrder, Order, OrderFilter, OrderState, OrderStateFilter,
+        fees::{get_crypto_fee, get_fiat_fee, get_referral_fee, DEFAULT_REFERRAL_FEE_BPS},
+        generate_payment_reference, BankTransferClaim, EvmOrderInput, FiatRefund, LockInput,
+        LockedOrder, Order, OrderFilter, OrderState, OrderStateFilter, Overpayment, PartialFill,
+        PartialPayment, PaymentAmount, RefundStatus, UnlockReason, VerifiedPayment,
     },
     Blockchain, Crypto, PaymentProvider, PaymentProviderType, TransactionAddress,
 };
 
-use super::payment;
+use super::providers;
 
 pub async fn calculate_price_and_fee(currency: &str, crypto: &Crypto) -> Result<(u64, u64)> {
     let base_asset = Asset {
@@ -49,6 +55,33 @@ pub async fn calculate_price_and_fee(currency: &str, crypto: &Crypto) -> Result<
     Ok((fiat_amount, get_fiat_fee(fiat_amount)))
 }
 
+/// Values an order in the KYC limits currency and checks it against the user's tier.
+///
+/// Returns the amount to be reserved in the user's rolling volume once the order goes through.
+pub async fn check_kyc_limits(
+    user_id: u64,
+    currency: &str,
+    price: Option<u64>,
+    crypto: &Crypto,
+) -> Result<u64> {
+    let user = memory::stable::users::get_user(&user_id)?;
+    if user.kyc_tier().limits().is_none() {
+        return Ok(0);
+    }
+
+    let amount = match price {
+        Some(price) if currency == KYC_LIMITS_CURRENCY => price,
+        _ => {
+            calculate_price_and_fee(KYC_LIMITS_CURRENCY, crypto)
+                .await?
+                .0
+        }
+    };
+    user.check_kyc_limits(amount, ic_cdk::api::time())?;
+
+    Ok(amount)
+}
+
 pub async fn calculate_order_evm_fees(
     chain_id: u64,
     crypto_amount: u128,
@@ -141,24 +174,25 @@ async fn order_crypto_fee(
 }
 
 pub async fn get_valid_log_event(chain_id: &u64, tx_hash: &String) -> Result<LogEvent> {
-    if spent_transactions::is_tx_hash_processed(tx_hash) {
-        return Err(
-            BlockchainError::EvmLogError("Transaction already processed".to_string()).into(),
-        );
-    };
-
     match transaction::check_transaction_status(tx_hash, *chain_id).await {
         TransactionStatus::Confirmed(receipt) => {
-            let log_entry = receipt
-                .logs
-                .first()
-                .ok_or_else(|| BlockchainError::EvmLogError("Empty Log Entries".to_string()))?;
-            event::parse_deposit_event(log_entry)
+            // Deposits of tokens or through routers carry other logs as well
+            let vault_manager_address = chains::get_vault_manager_address(*chain_id)?;
+            event::parse_vault_events(&receipt.logs, &vault_manager_address)
+                .into_iter()
+                .find(|event| matches!(event, LogEvent::Deposit(_)))
+                .ok_or_else(|| {
+                    BlockchainError::EvmLogError("No Deposit event in the transaction".to_string())
+                        .into()
+                })
         }
         _ => Err(BlockchainError::EmptyTransactionHash.into()),
     }
 }
 
+/// Checks the deposit funding an order. The transaction hash of a valid EVM
+/// deposit is returned reserved, and has to be released with
+/// `spent_transactions::release_tx_hash` if the order is not created.
 pub async fn validate_deposit_tx(
     blockchain: &Blockchain,
     evm_input: Option<EvmOrderInput>,
@@ -177,36 +211,24 @@ pub async fn validate_deposit_tx(
                 BlockchainError::EvmLogError("EVM input data is required".to_string())
             })?;
 
-            let log_event = get_valid_log_event(chain_id, &evm_input.tx_hash).await?;
-            ic_cdk::println!("[validate_deposit_tx] log_event = {:?}", log_event);
-            match log_event {
-                LogEvent::Deposit(deposit_event) => {
-                    if deposit_event.user.to_lowercase() != order_offramper.to_lowercase() {
-                        return Err(BlockchainError::EvmLogError(
-                            "Invalid Offramper Address".to_string(),
-                        )
-                        .into());
-                    };
-                    if deposit_event.amount != order_amount {
-                        return Err(BlockchainError::EvmLogError(
-                            "Invalid Crypto Amount".to_string(),
-                        )
-                        .into());
-                    }
-                    if deposit_event.token.clone().map(|t| t.to_lowercase())
-                        != order_token.map(|t| t.to_lowercase())
-                    {
-                        return Err(
-                            BlockchainError::EvmLogError("Invalid Crypto".to_string()).into()
-                        );
-                    }
-
-                    let last_block = eth_get_latest_block(*chain_id, BlockTag::Latest)
-                        .await
-                        .map(|block| block.number)?;
-                    deposit_event.expired(last_block)?;
-                }
-            };
+            if !spent_transactions::reserve_tx_hash(&evm_input.tx_hash) {
+                return Err(BlockchainError::EvmLogError(
+                    "Transaction already processed".to_string(),
+                )
+                .into());
+            }
+            if let Err(e) = check_deposit_event(
+                chain_id,
+                &evm_input.tx_hash,
+                order_offramper,
+                order_amount,
+                order_token,
+            )
+            .await
+            {
+                spent_transactions::release_tx_hash(&evm_input.tx_hash);
+                return Err(e);
+            }
 
             Ok(Some(evm_input.tx_hash))
         }
@@ -218,6 +240,36 @@ pub async fn validate_deposit_tx(
     }
 }
 
+async fn check_deposit_event(
+    chain_id: &u64,
+    tx_hash: &String,
+    order_offramper: String,
+    order_amount: u128,
+    order_token: Option<String>,
+) -> Result<()> {
+    let log_event = get_valid_log_event(chain_id, tx_hash).await?;
+    ic_cdk::println!("[validate_deposit_tx] log_event = {:?}", log_event);
+    let LogEvent::Deposit(deposit_event) = log_event else {
+        return Err(BlockchainError::EvmLogError("Not a Deposit event".to_string()).into());
+    };
+    if deposit_event.user.to_lowercase() != order_offramper.to_lowercase() {
+        return Err(BlockchainError::EvmLogError("Invalid Offramper Address".to_string()).into());
+    };
+    if deposit_event.amount != order_amount {
+        return Err(BlockchainError::EvmLogError("Invalid Crypto Amount".to_string()).into());
+    }
+    if deposit_event.token.clone().map(|t| t.to_lowercase())
+        != order_token.map(|t| t.to_lowercase())
+    {
+        return Err(BlockchainError::EvmLogError("Invalid Crypto".to_string()).into());
+    }
+
+    let last_block = eth_get_latest_block(*chain_id, BlockTag::Latest)
+        .await
+        .map(|block| block.number)?;
+    deposit_event.expired(last_block)
+}
+
 pub async fn create_order(
     currency: &str,
     offramper_user_id: u64,
@@ -229,6 +281,24 @@ pub async fn create_order(
     estimated_gas_lock: Option<u64>,
     estimated_gas_withdraw: Option<u64>,
 ) -> Result<u64> {
+    if let Some(provider_type) = offramper_providers
+        .keys()
+        .find(|provider_type| !providers::supports_currency(provider_type, currency))
+    {
+        return Err(OrderError::UnsupportedCurrency(
+            provider_type.clone(),
+            currency.to_string(),
+        ))?;
+    }
+
+    let kyc_amount = check_kyc_limits(
+        offramper_user_id,
+        currency,
+        None,
+        &Crypto::new(blockchain.clone(), token.clone(), crypto_amount, 0),
+    )
+    .await?;
+
     let crypto_fee = order_crypto_fee(
         blockchain.clone(),
         crypto_amount,
@@ -242,7 +312,7 @@ pub async fn create_order(
         return Err(BlockchainError::FundsTooLow)?;
     }
 
-    let order = Order::new(
+    let mut order = Order::new(
         currency.to_string(),
         offramper_user_id,
         offramper_address,
@@ -252,6 +322,8 @@ pub async fn create_order(
         crypto_amount,
         crypto_fee,
     )?;
+    order.offramper_kyc_reservation =
+        user_management::reserve_kyc_volume(offramper_user_id, kyc_amount)?;
 
     memory::stable::orders::insert_order(&order);
     Ok(order.id)
@@ -360,6 +432,20 @@ pub fn get_orders(
             page,
             page_size,
         ),
+        Some(OrderFilter::ByMinOfframperScore(min_score)) => {
+            let now = ic_cdk::api::time();
+            memory::stable::orders::filter_orders(
+                |order_state| match order_state {
+                    OrderState::Created(order) => {
+                        memory::stable::users::get_user(&order.offramper_user_id)
+                            .is_ok_and(|user| user.current_score(now) >= min_score)
+                    }
+                    _ => false,
+                },
+                page,
+                page_size,
+            )
+        }
     }
 }
 
@@ -382,52 +468,65 @@ pub async fn lock_order(
     }
 
     let (price, offramper_fee) = calculate_price_and_fee(&order.currency, &order.crypto).await?;
-
-    let revolut_consent = payment::get_revolut_consent(
-        order.offramper_providers,
-        &(price as f64 / 100.).to_string(),
+    let kyc_amount = check_kyc_limits(
+        onramper_user_id,
         &order.currency,
+        Some(price),
+        &order.crypto,
+    )
+    .await?;
+
+    // The rail is prepared for everything the onramper pays, which is also what
+    // verification expects: a Revolut consent for the bare price would not cover
+    // the payment initiated against it.
+    let payment_reference = generate_payment_reference(order_id, ic_cdk::api::time());
+    let mut prepared_payment = providers::prepare_payment(
+        &order,
         &onramper_provider,
+        price + offramper_fee,
+        &payment_reference,
     )
     .await?;
+    prepared_payment.payment_reference = Some(payment_reference);
 
-    match order.crypto.blockchain {
-        Blockchain::EVM { chain_id } => {
-            let estimated_gas =
-                Ic2P2ramp::get_average_gas_price(chain_id, &TransactionAction::Commit).await?;
-            Ic2P2ramp::commit_deposit(
-                chain_id,
-                order_id,
-                order.offramper_address.address,
-                order.crypto.token,
-                order.crypto.amount,
-                Some(estimated_gas),
-                LockInput {
-                    price,
-                    offramper_fee,
-                    onramper_user_id,
-                    onramper_provider,
-                    onramper_address,
-                    revolut_consent,
-                },
-            )
-            .await?;
-            Ok(())
-        }
-        Blockchain::ICP { .. } => {
-            memory::stable::orders::lock_order(
-                order_id,
-                price,
-                offramper_fee,
-                onramper_user_id,
-                onramper_provider,
-                onramper_address,
-                revolut_consent,
-            )?;
-            Ok(())
+    let onramper_kyc_reservation =
+        user_management::reserve_kyc_volume(onramper_user_id, kyc_amount)?;
+    let lock_input = LockInput {
+        price,
+        offramper_fee,
+        onramper_user_id,
+        onramper_provider,
+        onramper_address,
+        prepared_payment,
+        onramper_kyc_reservation: onramper_kyc_reservation.clone(),
+    };
+
+    let result: Result<()> = async {
+        match order.crypto.blockchain {
+            Blockchain::EVM { chain_id } => {
+                let estimated_gas =
+                    Ic2P2ramp::get_average_gas_price(chain_id, &TransactionAction::Commit).await?;
+                Ic2P2ramp::commit_deposit(
+                    chain_id,
+                    order_id,
+                    order.offramper_address.address,
+                    order.crypto.token,
+                    order.crypto.amount,
+                    Some(estimated_gas),
+                    lock_input,
+                )
+                .await
+            }
+            Blockchain::ICP { .. } => memory::stable::orders::lock_order(order_id, lock_input),
+            _ => Err(BlockchainError::UnsupportedBlockchain.into()),
         }
-        _ => Err(BlockchainError::UnsupportedBlockchain)?,
     }
+    .await;
+
+    if result.is_err() {
+        user_management::release_kyc_volume(onramper_user_id, &onramper_kyc_reservation);
+    }
+    result
 }
 
 /// Unlocks an order, handling both ICP and EVM blockchain orders.
@@ -464,19 +563,65 @@ pub async fn lock_order(
 /// ```
 pub async fn unlock_order(order_id: u64) -> Result<()> {
     let order = memory::stable::orders::get_order(&order_id)?.locked()?;
-    if order.payment_done {
+    if order.is_inside_lock_time() {
+        return Err(OrderError::OrderInLockTime)?;
+    }
+    if order.bank_transfer.is_some() {
+        return Err(OrderError::OfframperConfirmationRequired)?;
+    }
+    // The onramper paid part of the order: settle that part instead of unlocking,
+    // or have the refund of the partial payment settled if it cannot be filled
+    if order.partial_payment.is_some() && !order.payment_done {
+        if let Err(e) = partial_fill_split(&order) {
+            ic_cdk::println!(
+                "[unlock_order] order {} cannot be filled: {:?}",
+                order_id,
+                e
+            );
+            return dispute_partial_payment(order_id);
+        }
+        return super::payment::settle_partial_fill(order_id).await;
+    }
+
+    release_locked_order(order, UnlockReason::Timeout).await
+}
+
+/// Unlocks an order before its lock time is over, for admin interventions.
+/// Orders the onramper claims to have paid, in full or in part, are settled
+/// through their dispute endpoints instead.
+pub async fn force_unlock_order(order_id: u64) -> Result<()> {
+    let order = memory::stable::orders::get_order(&order_id)?.locked()?;
+    if order.bank_transfer.is_some() {
+        return Err(OrderError::OfframperConfirmationRequired)?;
+    }
+    if order.partial_payment.is_some() && !order.payment_done {
+        return Err(OrderError::PaymentUnderpaid(order.outstanding_amount()))?;
+    }
+    release_locked_order(order, UnlockReason::Admin).await
+}
+
+async fn release_locked_order(order: LockedOrder, reason: UnlockReason) -> Result<()> {
+    // Refunded orders go back to the offramper as if they were never paid
+    if order.payment_done && !order.is_refunded() {
         return Err(OrderError::PaymentDone)?;
     }
     if order.uncommited {
         return Err(OrderError::OrderUncommitted)?;
     }
-    if order.is_inside_lock_time() {
-        return Err(OrderError::OrderInLockTime)?;
-    }
 
+    let order_id = order.base.id;
     let user = memory::stable::users::get_user(&order.onramper.user_id)?;
     user.validate_onramper()?;
 
+    // EVM orders are unlocked once the uncommit goes through, so the reason is kept on the order
+    memory::stable::orders::mutate_order(&order_id, |order_state| match order_state {
+        OrderState::Locked(order) => {
+            order.unlock_reason = Some(reason);
+            Ok(())
+        }
+        _ => Err(OrderError::InvalidOrderState(order_state.to_string())),
+    })??;
+
     match order.base.crypto.blockchain {
         Blockchain::EVM { chain_id } => {
             let estimated_gas =
@@ -558,6 +703,7 @@ pub fn mark_order_as_paid(order_id: u64) -> Result<()> {
                     order.onramper.user_id,
                     order.price,
                     &order.base.currency,
+                    ic_cdk::api::time().saturating_sub(order.locked_at) / 1_000_000_000,
                 )?;
                 user_management::update_offramper_payment(
                     order.base.offramper_user_id,
@@ -577,6 +723,11 @@ pub fn mark_order_as_paid(order_id: u64) -> Result<()> {
 pub fn set_payment_id(order_id: u64, payment_id: String) -> Result<()> {
     memory::stable::orders::mutate_order(&order_id, |order_state| match order_state {
         OrderState::Locked(order) => {
+            payment_ids::consume_payment_id(
+                &order.onramper.provider.provider_type(),
+                &payment_id,
+                order_id,
+            )?;
             order.payment_id = Some(payment_id);
             Ok(())
         }
@@ -584,20 +735,103 @@ pub fn set_payment_id(order_id: u64, payment_id: String) -> Result<()> {
     })?
 }
 
-pub fn set_order_completed(order_id: u64) -> Result<()> {
+/// Holds a paid order whose release was mined without the expected vault event.
+/// The crypto may have left the vault, so the order stays processing and is not
+/// refundable until an admin resolves it.
+pub fn record_unconfirmed_release(order_id: u64, reason: String) {
+    ic_cdk::println!("[release] order {} awaits review: {}", order_id, reason);
+    let result = memory::stable::orders::mutate_order(&order_id, |order_state| match order_state {
+        OrderState::Locked(order) => {
+            order.release_unconfirmed = Some(reason);
+            Ok(())
+        }
+        _ => Err(OrderError::InvalidOrderState(order_state.to_string())),
+    });
+    if let Err(e) = result {
+        ic_cdk::println!(
+            "[release] could not hold order {} for review: {:?}",
+            order_id,
+            e
+        );
+    }
+}
+
+/// Settles a release held for review. If the crypto reached the onramper the
+/// order is completed, otherwise the release is recorded as failed, making the
+/// fiat payment refundable.
+pub fn resolve_unconfirmed_release(order_id: u64, released: bool) -> Result<()> {
+    let order = memory::stable::orders::get_order(&order_id)?.locked()?;
+    let Some(reason) = order.release_unconfirmed else {
+        return Err(
+            OrderError::InvalidOrderState("Release is not held for review".to_string()).into(),
+        );
+    };
+
+    if released {
+        return set_order_completed(order_id);
+    }
     memory::stable::orders::mutate_order(&order_id, |order_state| match order_state {
         OrderState::Locked(order) => {
-            *order_state = OrderState::Completed(order.clone().complete());
+            order.release_unconfirmed = None;
+            order.release_failure = Some(reason);
             Ok(())
         }
-        _ => Err(OrderError::InvalidOrderState(order_state.to_string()))?,
-    })?
+        _ => Err(OrderError::InvalidOrderState(order_state.to_string())),
+    })??;
+    memory::stable::orders::unset_processing_order(&order_id)
 }
 
-pub fn verify_order_is_payable(
-    order_id: u64,
-    session_token: Option<String>,
-) -> Result<LockedOrder> {
+/// Records that the crypto of a paid order could not be released, which makes
+/// its fiat payment refundable.
+pub fn record_release_failure(order_id: u64, reason: String) {
+    ic_cdk::println!("[release] order {} failed for good: {}", order_id, reason);
+    let result = memory::stable::orders::mutate_order(&order_id, |order_state| match order_state {
+        OrderState::Locked(order) => {
+            order.release_failure = Some(reason);
+            Ok(())
+        }
+        _ => Err(OrderError::InvalidOrderState(order_state.to_string())),
+    });
+    if let Err(e) = result {
+        ic_cdk::println!(
+            "[release] could not record the failure of order {}: {:?}",
+            order_id,
+            e
+        );
+    }
+}
+
+pub fn set_order_completed(order_id: u64) -> Result<()> {
+    let order =
+        memory::stable::orders::mutate_order(&order_id, |order_state| match order_state {
+            OrderState::Locked(order) => {
+                let locked_order = order.clone();
+                *order_state = OrderState::Completed(order.clone().complete());
+                Ok(locked_order)
+            }
+            _ => Err(OrderError::InvalidOrderState(order_state.to_string())),
+        })??;
+
+    let referral_fee_bps =
+        memory::heap::read_state(|s| s.referral_fee_bps).unwrap_or(DEFAULT_REFERRAL_FEE_BPS);
+    let referral_fee = get_referral_fee(order.base.crypto.amount, referral_fee_bps);
+    for referee_id in [order.base.offramper_user_id, order.onramper.user_id] {
+        if let Err(e) =
+            user_management::reward_referrer(referee_id, &order.base.crypto, referral_fee)
+        {
+            ic_cdk::println!(
+                "[set_order_completed] failed to reward referrer of user {}: {:?}",
+                referee_id,
+                e
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Locked order that can still receive its payment, whoever reports it.
+pub fn get_payable_order(order_id: u64) -> Result<LockedOrder> {
     let order = memory::stable::orders::get_order(&order_id)?.locked()?;
     if !order.is_inside_lock_time() {
         Err(OrderError::OrderUncommitted)?;
@@ -614,6 +848,15 @@ pub fn verify_order_is_payable(
         .get(&order.onramper.provider.provider_type())
         .ok_or_else(|| UserError::ProviderNotInUser(order.onramper.provider.provider_type()))?;
 
+    Ok(order)
+}
+
+pub fn verify_order_is_payable(
+    order_id: u64,
+    session_token: Option<String>,
+) -> Result<LockedOrder> {
+    let order = get_payable_order(order_id)?;
+
     let user = memory::stable::users::get_user(&order.onramper.user_id)?;
     if let Some(session_token) = session_token {
         user.validate_session(&session_token)?;
@@ -626,3 +869,491 @@ pub fn verify_order_is_payable(
 
     Ok(order)
 }
+
+/// Records the onramper's claim of having sent a manual bank transfer.
+///
+/// The lock timer is replaced by the offramper's confirmation deadline, after
+/// which the order escalates to a dispute instead of being unlocked.
+pub fn claim_bank_transfer(order_id: u64, session_token: String, reference: String) -> Result<()> {
+    let order = verify_order_is_payable(order_id, Some(session_token))?;
+    if order.onramper.provider.provider_type() != PaymentProviderType::BankTransfer {
+        return Err(OrderError::InvalidOnramperProvider.into());
+    }
+    if order.bank_transfer.is_some() {
+        return Err(OrderError::BankTransferAlreadyClaimed.into());
+    }
+    let reference = reference.trim().to_string();
+    if reference.is_empty() {
+        return Err(SystemError::InvalidInput("Reference is empty".to_string()).into());
+    }
+
+    let claimed_at = ic_cdk::api::time();
+    let confirm_by = claimed_at + memory::heap::BANK_TRANSFER_CONFIRMATION_SECONDS * 1_000_000_000;
+    memory::stable::orders::mutate_order(&order_id, |order_state| match order_state {
+        OrderState::Locked(order) => {
+            order.payment_id = Some(reference.clone());
+            order.bank_transfer = Some(BankTransferClaim {
+                reference,
+                claimed_at,
+                confirm_by,
+                disputed: false,
+                dispute_loser: None,
+            });
+            Ok(())
+        }
+        _ => Err(OrderError::InvalidOrderState(order_state.to_string())),
+    })??;
+
+    memory::heap::set_bank_transfer_timer(order_id, confirm_by);
+    Ok(())
+}
+
+/// Marks a claimed bank transfer as received by the offramper, returning the
+/// order so that its funds can be released.
+pub fn confirm_bank_transfer(order_id: u64, session_token: String) -> Result<LockedOrder> {
+    let order = memory::stable::orders::get_order(&order_id)?.locked()?;
+    if order.bank_transfer.is_none() {
+        return Err(OrderError::BankTransferNotClaimed.into());
+    }
+    if order.payment_done {
+        return Err(OrderError::PaymentDone.into());
+    }
+
+    let offramper = memory::stable::users::get_user(&order.base.offramper_user_id)?;
+    offramper.validate_session(&session_token)?;
+    offramper.is_offramper()?;
+
+    mark_order_as_paid(order_id)?;
+    Ok(order)
+}
+
+/// Flags an unconfirmed bank transfer as disputed once its deadline has passed.
+pub fn escalate_bank_transfer(order_id: u64) -> Result<()> {
+    memory::stable::orders::mutate_order(&order_id, |order_state| match order_state {
+        OrderState::Locked(order) if !order.payment_done => {
+            let claim = order
+                .bank_transfer
+                .as_mut()
+                .ok_or(OrderError::BankTransferNotClaimed)?;
+            claim.disputed = true;
+            ic_cdk::println!("[escalate_bank_transfer] order {} is disputed", order_id);
+            Ok(())
+        }
+        _ => Err(OrderError::InvalidOrderState(order_state.to_string()))?,
+    })?
+}
+
+/// Confirmation deadline of a locked order with a claimed bank transfer.
+pub fn bank_transfer_deadline(order_id: u64) -> Option<u64> {
+    let order = memory::stable::orders::get_order(&order_id)
+        .ok()?
+        .locked()
+        .ok()?;
+    order.bank_transfer.map(|claim| claim.confirm_by)
+}
+
+/// Settles a disputed bank transfer. If the payment is deemed received, the
+/// funds are released to the onramper; otherwise the order is unlocked. Once
+/// the release or unlock goes through, the losing side gets a lost dispute on
+/// its reputation. Failed settlements can be retried without counting the
+/// payment or the lost dispute twice.
+pub async fn resolve_bank_transfer_dispute(order_id: u64, payment_received: bool) -> Result<()> {
+    let order = memory::stable::orders::get_order(&order_id)?.locked()?;
+    if !order
+        .bank_transfer
+        .as_ref()
+        .is_some_and(|claim| claim.disputed)
+    {
+        return Err(OrderError::OrderNotDisputed.into());
+    }
+
+    let loser = if payment_received {
+        if !order.payment_done {
+            mark_order_as_paid(order_id)?;
+        }
+        super::payment::handle_payment_completion(&order).await?;
+        order.base.offramper_user_id
+    } else {
+        let onramper_user_id = order.onramper.user_id;
+        release_locked_order(order, UnlockReason::DisputeLost).await?;
+        onramper_user_id
+    };
+    if let Err(e) = record_bank_transfer_dispute_lost(order_id, loser) {
+        ic_cdk::println!(
+            "[resolve_bank_transfer_dispute] lost dispute of user {} not recorded: {:?}",
+            loser,
+            e
+        );
+    }
+    Ok(())
+}
+
+/// Records the lost dispute of a bank transfer once, however many times its
+/// settlement is retried. Orders already settled by the time this runs are
+/// not disputed again.
+fn record_bank_transfer_dispute_lost(order_id: u64, user_id: u64) -> Result<()> {
+    let recorded =
+        memory::stable::orders::mutate_order(&order_id, |order_state| match order_state {
+            OrderState::Locked(order) => order
+                .bank_transfer
+                .as_mut()
+                .is_some_and(|claim| claim.dispute_loser.replace(user_id).is_some()),
+            _ => false,
+        })?;
+    if recorded {
+        return Ok(());
+    }
+    user_management::record_dispute_lost(user_id)
+}
+
+fn set_refund_status(order_id: u64, status: RefundStatus) -> Result<()> {
+    memory::stable::orders::mutate_order(&order_id, |order_state| match order_state {
+        OrderState::Locked(order) => {
+            let requested_at = order
+                .refund
+                .as_ref()
+                .map_or_else(ic_cdk::api::time, |refund| refund.requested_at);
+            order.refund = Some(FiatRefund {
+                requested_at,
+                status,
+            });
+            Ok(())
+        }
+        _ => Err(OrderError::InvalidOrderState(order_state.to_string()))?,
+    })?
+}
+
+/// Refunds the onramper's fiat payment of an order whose crypto release failed
+/// for good. The order is marked as `RefundPending` until the provider takes
+/// the refund, and the outcome is recorded in the order. Failed refunds can be
+/// requested again.
+pub async fn refund_order(order_id: u64) -> Result<RefundStatus> {
+    let order = memory::stable::orders::get_order(&order_id)?.locked()?;
+    if !order.payment_done {
+        return Err(OrderError::InvalidOrderState("Order is not paid".to_string()).into());
+    }
+    if order.release_failure.is_none() || order.release_unconfirmed.is_some() {
+        return Err(
+            OrderError::InvalidOrderState("Crypto release has not failed".to_string()).into(),
+        );
+    }
+    if order
+        .refund
+        .as_ref()
+        .is_some_and(|refund| !matches!(refund.status, RefundStatus::Failed(_)))
+    {
+        return Err(OrderError::RefundInProgress.into());
+    }
+    set_refund_status(order_id, RefundStatus::RefundPending)?;
+
+    match providers::refund_payment(&order).await {
+        Ok(status) => {
+            set_refund_status(order_id, status.clone())?;
+            Ok(status)
+        }
+        Err(e) => {
+            set_refund_status(order_id, RefundStatus::Failed(e.to_string()))?;
+            Err(e)
+        }
+    }
+}
+
+/// Executes the Revolut refund payment the offramper has authorized.
+pub async fn complete_revolut_refund(order_id: u64, session_token: String) -> Result<()> {
+    let order = memory::stable::orders::get_order(&order_id)?.locked()?;
+    let Some(RefundStatus::AwaitingOfframper(consent)) =
+        order.refund.as_ref().map(|refund| &refund.status)
+    else {
+        return Err(OrderError::RefundNotAwaitingOfframper.into());
+    };
+
+    let offramper = memory::stable::users::get_user(&order.base.offramper_user_id)?;
+    offramper.validate_session(&session_token)?;
+    offramper.is_offramper()?;
+
+    memory::stable::orders::set_processing_order(&order_id)?;
+    let result = providers::Revolut::execute_refund(&order, &consent.id).await;
+    memory::stable::orders::unset_processing_order(&order_id)?;
+
+    set_refund_status(order_id, RefundStatus::Refunded { refund_id: result? })?;
+    unlock_refunded_order(order_id).await;
+    Ok(())
+}
+
+/// Gives the crypto of a refunded order back to its offramper. The onramper got
+/// their payment back, so the unlock is not held against them.
+pub async fn unlock_refunded_order(order_id: u64) {
+    let result: Result<()> = async {
+        let order = memory::stable::orders::get_order(&order_id)?.locked()?;
+        memory::stable::orders::set_processing_order(&order_id)?;
+        let result = release_locked_order(order, UnlockReason::Refunded).await;
+        if result.is_err() {
+            memory::stable::orders::unset_processing_order(&order_id)?;
+        }
+        result
+    }
+    .await;
+
+    if let Err(e) = result {
+        ic_cdk::println!("[refund] could not unlock order {}: {:?}", order_id, e);
+    }
+}
+
+/// Credits a verified payment to a locked order and compares the total received
+/// with the order's, within the configured tolerance bands.
+///
+/// An underpaid order keeps its payments as a partial payment, awaiting a top-up
+/// or a partial fill. An overpayment is recorded for the offramper to refund.
+pub fn credit_payment(
+    order_id: u64,
+    payment_id: String,
+    payment: VerifiedPayment,
+) -> Result<PaymentAmount> {
+    let tolerance = memory::heap::read_state(|s| s.payment_tolerance.clone()).unwrap_or_default();
+    memory::stable::orders::mutate_order(&order_id, |order_state| match order_state {
+        OrderState::Locked(order) => {
+            payment_ids::consume_payment_id(
+                &order.onramper.provider.provider_type(),
+                &payment_id,
+                order_id,
+            )?;
+            if payment.fx_payment.is_some() {
+                order.fx_payment = payment.fx_payment;
+            }
+
+            let mut partial_payment = order.partial_payment.take().unwrap_or(PartialPayment {
+                received: 0,
+                payment_ids: vec![],
+                disputed_at: None,
+            });
+            if !partial_payment.payment_ids.contains(&payment_id) {
+                partial_payment.received += payment.amount;
+                partial_payment.payment_ids.push(payment_id.clone());
+            }
+
+            let amount = tolerance.classify(order.total_amount(), partial_payment.received);
+            ic_cdk::println!(
+                "[credit_payment] order {} received {} of {}: {:?}",
+                order_id,
+                partial_payment.received,
+                order.total_amount(),
+                amount
+            );
+            match amount {
+                PaymentAmount::Underpaid { .. } => {
+                    order.payment_id = None;
+                    order.partial_payment = Some(partial_payment);
+                }
+                PaymentAmount::Exact | PaymentAmount::Overpaid { .. } => {
+                    if let PaymentAmount::Overpaid { excess } = amount {
+                        order.overpayment = Some(Overpayment {
+                            excess,
+                            offramper_user_id: order.base.offramper_user_id,
+                            refund_reference: None,
+                        });
+                    }
+                    order.payment_id = Some(payment_id);
+                    order.received_amount = Some(partial_payment.received);
+                    // Only kept as the record of a topped-up order
+                    order.partial_payment =
+                        (partial_payment.payment_ids.len() > 1).then_some(partial_payment);
+                }
+            }
+            Ok(amount)
+        }
+        _ => Err(OrderError::InvalidOrderState(order_state.to_string()))?,
+    })?
+}
+
+/// Settles an underpaid order for the share of its crypto the partial payments
+/// cover, putting the rest back on sale as a new order of the offramper.
+///
+/// Only ICP orders can be split: the crypto of EVM orders is committed as a
+/// whole in the vault.
+pub fn partial_fill_order(order_id: u64) -> Result<LockedOrder> {
+    let order = memory::stable::orders::get_order(&order_id)?.locked()?;
+    let (paid, filled_crypto, remainder_crypto) = partial_fill_split(&order)?;
+    let total = order.total_amount();
+
+    // the remainder takes over its share of the offramper's volume, while the
+    // onramper's volume for the unpaid share is given back
+    let mut offramper_kyc_reservation = order.base.offramper_kyc_reservation.clone();
+    let mut onramper_kyc_reservation = order.onramper_kyc_reservation.clone();
+    let unpaid_kyc_reservation = onramper_kyc_reservation
+        .as_mut()
+        .map(|reservation| reservation.split_off(total - paid, total));
+
+    let mut remainder = order.base.clone();
+    remainder.id = memory::heap::generate_order_id();
+    remainder.created_at = ic_cdk::api::time();
+    remainder.crypto = remainder_crypto;
+    remainder.offramper_kyc_reservation = offramper_kyc_reservation
+        .as_mut()
+        .map(|reservation| reservation.split_off(total - paid, total));
+    remainder.unset_processing();
+    memory::stable::orders::insert_order(&remainder);
+
+    let price = order.price * paid / total;
+    memory::stable::orders::mutate_order(&order_id, |order_state| match order_state {
+        OrderState::Locked(order) => {
+            order.base.crypto = filled_crypto;
+            order.base.offramper_kyc_reservation = offramper_kyc_reservation;
+            order.onramper_kyc_reservation = onramper_kyc_reservation;
+            order.price = price;
+            order.offramper_fee = paid - price;
+            order.partial_fill = Some(PartialFill {
+                paid,
+                remainder_order_id: remainder.id,
+            });
+            Ok(())
+        }
+        _ => Err(OrderError::InvalidOrderState(order_state.to_string())),
+    })??;
+    user_management::release_kyc_volume(order.onramper.user_id, &unpaid_kyc_reservation);
+    ic_cdk::println!(
+        "[partial_fill_order] order {} filled for {} of {}, remainder in order {}",
+        order_id,
+        paid,
+        total,
+        remainder.id
+    );
+
+    mark_order_as_paid(order_id)?;
+    memory::stable::orders::get_order(&order_id)?.locked()
+}
+
+/// Paid share of an underpaid order and its crypto split into the filled part
+/// and the remainder, each carrying its share of the fee.
+fn partial_fill_split(order: &LockedOrder) -> Result<(u64, Crypto, Crypto)> {
+    if order.payment_done {
+        return Err(OrderError::PaymentDone.into());
+    }
+    let Some(partial_payment) = &order.partial_payment else {
+        return Err(OrderError::NoPartialPayment.into());
+    };
+    if !matches!(order.base.crypto.blockchain, Blockchain::ICP { .. }) {
+        return Err(BlockchainError::UnsupportedBlockchain.into());
+    }
+
+    let tolerance = memory::heap::read_state(|s| s.payment_tolerance.clone()).unwrap_or_default();
+    let (paid, total) = (partial_payment.received, order.total_amount());
+    if !tolerance.allows_partial_fill(total, paid) {
+        return Err(OrderError::PartialFillNotAllowed.into());
+    }
+
+    let mut filled = order.base.crypto.clone();
+    let remainder = filled.split_off(total - paid, total);
+    if filled.amount <= filled.fee || remainder.amount <= 2 * remainder.fee {
+        return Err(BlockchainError::FundsTooLow.into());
+    }
+    Ok((paid, filled, remainder))
+}
+
+/// Flags an underpaid order that cannot be partially filled as disputed once
+/// its lock time is over. It stays locked until an admin settles the refund of
+/// the partial payment.
+fn dispute_partial_payment(order_id: u64) -> Result<()> {
+    memory::stable::orders::mutate_order(&order_id, |order_state| -> Result<()> {
+        match order_state {
+            OrderState::Locked(order) if !order.payment_done => {
+                let partial_payment = order
+                    .partial_payment
+                    .as_mut()
+                    .ok_or(OrderError::NoPartialPayment)?;
+                partial_payment
+                    .disputed_at
+                    .get_or_insert_with(ic_cdk::api::time);
+                ic_cdk::println!("[dispute_partial_payment] order {} is disputed", order_id);
+                Ok(())
+            }
+            _ => Err(OrderError::InvalidOrderState(order_state.to_string()))?,
+        }
+    })??;
+
+    let _ = memory::heap::clear_order_timer(order_id);
+    Ok(())
+}
+
+/// Settles a disputed partial payment. If the offramper refunded it, the order
+/// is unlocked as timed out, the onramper having not paid in full. Otherwise the
+/// offramper gets a lost dispute and the order is unlocked without holding it
+/// against the onramper.
+pub async fn resolve_partial_payment_dispute(order_id: u64, refunded: bool) -> Result<()> {
+    let order = memory::stable::orders::get_order(&order_id)?.locked()?;
+    if order
+        .partial_payment
+        .as_ref()
+        .is_none_or(|partial_payment| partial_payment.disputed_at.is_none())
+    {
+        return Err(OrderError::OrderNotDisputed.into());
+    }
+
+    if refunded {
+        release_locked_order(order, UnlockReason::Timeout).await
+    } else {
+        user_management::record_dispute_lost(order.base.offramper_user_id)?;
+        release_locked_order(order, UnlockReason::DisputeLost).await
+    }
+}
+
+/// Records the offramper's refund of the amount an onramper paid in excess.
+pub fn confirm_overpayment_refund(
+    order_id: u64,
+    session_token: String,
+    refund_reference: String,
+) -> Result<()> {
+    let overpayment = match memory::stable::orders::get_order(&order_id)? {
+        OrderState::Locked(order) => order.overpayment,
+        OrderState::Completed(order) => order.overpayment,
+        _ => None,
+    }
+    .ok_or(OrderError::NoOverpayment)?;
+    let user = memory::stable::users::get_user(&overpayment.offramper_user_id)?;
+    user.validate_session(&session_token)?;
+    if refund_reference.trim().is_empty() {
+        return Err(SystemError::InvalidInput("Refund reference is empty".to_string()).into());
+    }
+
+    memory::stable::orders::mutate_order(&order_id, |order_state| {
+        let overpayment = match order_state {
+            OrderState::Locked(order) => order.overpayment.as_mut(),
+            OrderState::Completed(order) => order.overpayment.as_mut(),
+            _ => None,
+        }
+        .ok_or(OrderError::NoOverpayment)?;
+        overpayment.refund_reference = Some(refund_reference.trim().to_string());
+        Ok(())
+    })?
+}
+
+/// Sets the other currencies the offramper accepts payment in for an order
+/// that is not locked yet. An empty list only accepts the order's currency.
+pub fn set_accepted_currencies(
+    order_id: u64,
+    session_token: String,
+    currencies: Vec<String>,
+) -> Result<()> {
+    let order = memory::stable::orders::get_order(&order_id)?.created()?;
+    let user = memory::stable::users::get_user(&order.offramper_user_id)?;
+    user.validate_session(&session_token)?;
+    user.is_offramper()?;
+
+    let currencies: Vec<String> = currencies
+        .iter()
+        .map(|currency| currency.trim().to_uppercase())
+        .collect();
+    if let Some(invalid) = currencies
+        .iter()
+        .find(|currency| currency.len() != 3 || !currency.chars().all(|c| c.is_ascii_alphabetic()))
+    {
+        return Err(SystemError::InvalidInput(format!("Invalid currency: {}", invalid)).into());
+    }
+
+    memory::stable::orders::mutate_order(&order_id, |order_state| match order_state {
+        OrderState::Created(order) => {
+            order.accepted_currencies = (!currencies.is_empty()).then_some(currencies);
+            Ok(())
+        }
+        _ => Err(OrderError::InvalidOrderState(order_state.to_string()))?,
+    })?
+}
@@ -1,126 +1,135 @@
-use std::collections::HashMap;
-
 use candid::Principal;
 use icrc_ledger_types::icrc1::{account::Account, transfer::NumTokens};
 
+use super::providers;
 use crate::{
-    errors::{BlockchainError, OrderError, Result, UserError},
+    errors::{BlockchainError, OrderError, RampError, Result},
     evm::vault::Ic2P2ramp,
     icp::vault::Ic2P2ramp as ICPRamp,
     management,
-    outcalls::{paypal, revolut},
+    model::memory::{
+        heap,
+        stable::{orders, payment_ids},
+    },
     types::{
         icp::get_icp_token,
-        orders::{LockedOrder, RevolutConsent},
-        Blockchain, PaymentProvider, PaymentProviderType,
+        orders::{LockedOrder, PaymentAmount},
+        Blockchain,
     },
 };
 
-pub async fn verify_paypal_payment(
-    onramper_id: &str,
-    transaction_id: &str,
-    order: &LockedOrder,
-) -> Result<()> {
-    let access_token = paypal::auth::get_paypal_access_token().await?;
-    ic_cdk::println!("[verify_transaction] Obtained PayPal access token");
-    let capture_details = paypal::order::fetch_paypal_order(&access_token, transaction_id).await?;
-
-    let received_amount: f64 = capture_details
-        .purchase_units
-        .iter()
-        .flat_map(|unit| &unit.payments.captures)
-        .map(|capture| capture.amount.value.parse::<f64>().unwrap())
-        .sum();
-
-    let amount_matches = order.payment_amount_matches(&received_amount.to_string());
-    let currency_matches =
-        capture_details.purchase_units[0].amount.currency_code == order.base.currency;
-
-    let offramper_provider = order
-        .base
-        .offramper_providers
-        .iter()
-        .find(|(provider_type, _)| *provider_type == &PaymentProviderType::PayPal)
-        .ok_or(OrderError::InvalidOfframperProvider)?;
-
-    let PaymentProvider::PayPal { id: offramper_id } = offramper_provider.1 else {
-        return Err(OrderError::InvalidOfframperProvider)?;
-    };
+/// Verifies the payment through the onramper's rail and credits it to the order,
+/// which is marked as paid once its total is covered. Otherwise the onramper is
+/// asked for a top-up of the missing amount.
+pub async fn verify_payment(order: &LockedOrder, transaction_id: &str) -> Result<()> {
+    let payment = providers::verify_payment(order, transaction_id).await?;
 
-    let offramper_matches = capture_details.purchase_units[0].payee.email_address == *offramper_id;
-    let onramper_matches = capture_details.payer.email_address == *onramper_id;
+    match management::order::credit_payment(order.base.id, transaction_id.to_string(), payment)? {
+        PaymentAmount::Underpaid { missing } => Err(OrderError::PaymentUnderpaid(missing).into()),
+        PaymentAmount::Exact | PaymentAmount::Overpaid { .. } => {
+            management::order::mark_order_as_paid(order.base.id)
+        }
+    }
+}
 
-    if capture_details.status == "COMPLETED"
-        && amount_matches
-        && currency_matches
-        && offramper_matches
-        && onramper_matches
+/// Verifies a payment id not used by any other order and releases the funds.
+/// Orders with a recorded payment id only accept that payment.
+///
+/// A payment the rail reports as pending is recorded on the order and polled in
+/// the background until it settles or the lock expires.
+pub async fn settle_payment(order: &LockedOrder, transaction_id: &str) -> Result<()> {
+    let provider_type = order.onramper.provider.provider_type();
+    payment_ids::check_payment_id_unused(&provider_type, transaction_id, order.base.id)?;
+    // Payments the canister initiated or created can only be settled by themselves
+    if order
+        .payment_id
+        .as_deref()
+        .is_some_and(|payment_id| payment_id != transaction_id)
+    {
+        return Err(OrderError::PaymentVerificationFailed.into());
+    }
+    // A top-up has to be a new payment
+    if order
+        .partial_payment
+        .as_ref()
+        .is_some_and(|partial_payment| {
+            partial_payment
+                .payment_ids
+                .iter()
+                .any(|id| id == transaction_id)
+        })
     {
-        ic_cdk::println!("[verify_transaction] Verification succeded.");
-        management::order::set_payment_id(order.base.id, transaction_id.to_string())?;
-        management::order::mark_order_as_paid(order.base.id)?;
-    } else {
-        return Err(OrderError::PaymentVerificationFailed)?;
+        return Err(OrderError::PaymentIdAlreadyUsed.into());
     }
 
-    Ok(())
+    ic_cdk::println!(
+        "[settle_payment] Handling {:?} payment verification",
+        provider_type
+    );
+    match verify_payment(order, transaction_id).await {
+        Err(RampError::OrderError(OrderError::PaymentPending))
+            if providers::polls_payment_status(&provider_type) =>
+        {
+            management::order::set_payment_id(order.base.id, transaction_id.to_string())?;
+            heap::set_payment_poll_timer(order.base.id, heap::PAYMENT_POLL_INITIAL_SECONDS);
+            return Err(OrderError::PaymentPending.into());
+        }
+        result => result?,
+    }
+
+    handle_payment_completion(order).await
 }
 
-pub async fn verify_revolut_payment(
-    onramper_id: &str,
-    onramper_scheme: &str,
-    transaction_id: &str,
-    order: &LockedOrder,
-) -> Result<()> {
-    let payment_details =
-        revolut::transaction::fetch_revolut_payment_details(transaction_id).await?;
-
-    // Verify the captured payment details (amounts are in cents)
-    let amount_matches =
-        order.payment_amount_matches(&payment_details.data.initiation.instructed_amount.amount);
-    let currency_matches =
-        payment_details.data.initiation.instructed_amount.currency == order.base.currency;
-
-    let onramper_account = match payment_details.data.initiation.debtor_account {
-        Some(details) => details,
-        None => return Err(OrderError::MissingDebtorAccount)?,
+/// Checks again the pending payment of a locked order, releasing the funds
+/// once it settles. Returns whether the payment is still pending and should be
+/// polled again. Failed checks are retried as long as the order awaits
+/// settlement.
+pub async fn poll_payment(order_id: u64) -> Result<bool> {
+    let Some(order) = awaiting_settlement(order_id) else {
+        return Ok(false);
     };
-    let debtor_matches = onramper_account.scheme_name == *onramper_scheme
-        && onramper_account.identification == *onramper_id;
-
-    let offramper_account = payment_details.data.initiation.creditor_account;
-
-    let offramper_provider = order
-        .base
-        .offramper_providers
-        .iter()
-        .find(|(provider_type, _)| *provider_type == &PaymentProviderType::Revolut)
-        .ok_or(OrderError::InvalidOfframperProvider)?;
-
-    let PaymentProvider::Revolut {
-        scheme: offramper_scheme,
-        id: offramper_id,
-        name: offramper_name,
-    } = offramper_provider.1
-    else {
-        return Err(OrderError::InvalidOfframperProvider)?;
+    let Some(payment_id) = order.payment_id.clone() else {
+        return Ok(false);
     };
 
-    let creditor_matches = offramper_account.scheme_name == *offramper_scheme
-        && offramper_account.identification == *offramper_id
-        && offramper_account.name == *offramper_name;
-
-    if payment_details.data.status == "AcceptedSettlementCompleted"
-        && amount_matches
-        && currency_matches
-        && debtor_matches
-        && creditor_matches
-    {
-        ic_cdk::println!("[verify_transaction] verified is true!!");
-        management::order::mark_order_as_paid(order.base.id)
-    } else {
-        Err(OrderError::PaymentVerificationFailed)?
+    if orders::set_processing_order(&order_id).is_err() {
+        // Being verified by another call, check again later
+        return Ok(true);
     }
+    match poll_locked_payment(&order, &payment_id).await {
+        Ok(()) => Ok(false),
+        Err(e) => {
+            orders::unset_processing_order(&order_id)?;
+            match e {
+                RampError::OrderError(OrderError::PaymentPending) => Ok(true),
+                // Settled short of the total, now awaiting a top-up
+                RampError::OrderError(OrderError::PaymentUnderpaid(_)) => Ok(false),
+                e => Err(e),
+            }
+        }
+    }
+}
+
+async fn poll_locked_payment(order: &LockedOrder, payment_id: &str) -> Result<()> {
+    verify_payment(order, payment_id).await?;
+    handle_payment_completion(order).await
+}
+
+/// Locked order, still inside its lock time, whose recorded payment has not
+/// settled yet on a rail that is polled.
+pub fn awaiting_settlement(order_id: u64) -> Option<LockedOrder> {
+    let order = orders::get_order(&order_id).ok()?.locked().ok()?;
+    let polled = providers::polls_payment_status(&order.onramper.provider.provider_type());
+
+    (polled && order.payment_id.is_some() && !order.payment_done && order.is_inside_lock_time())
+        .then_some(order)
+}
+
+/// Partially fills an underpaid order and releases the share of the crypto
+/// its payments cover.
+pub async fn settle_partial_fill(order_id: u64) -> Result<()> {
+    let order = management::order::partial_fill_order(order_id)?;
+    handle_payment_completion(&order).await
 }
 
 pub async fn handle_payment_completion(order: &LockedOrder) -> Result<()> {
@@ -146,60 +155,19 @@ async fn handle_icp_payment_completion(
         owner: onramper_principal,
         subaccount: None,
     };
-    ICPRamp::transfer(
+    if let Err(e) = ICPRamp::transfer(
         *ledger_principal,
         to_account,
         amount - order.base.crypto.fee,
         Some(fee),
     )
-    .await?;
+    .await
+    {
+        super::order::record_release_failure(order.base.id, e.to_string());
+        return Err(e);
+    }
 
     super::order::set_order_completed(order.base.id)?;
 
     Ok(())
 }
-
-pub async fn get_revolut_consent(
-    offramper_providers: HashMap<PaymentProviderType, PaymentProvider>,
-    fiat_amount: &str,
-    currency_symbol: &str,
-    onramper_provider: &PaymentProvider,
-) -> Result<Option<RevolutConsent>> {
-    match &onramper_provider {
-        PaymentProvider::Revolut {
-            scheme: onramper_scheme,
-            id: onramper_id,
-            ..
-        } => {
-            let offramper_provider = offramper_providers
-                .get(&PaymentProviderType::Revolut)
-                .ok_or(UserError::ProviderNotInUser(PaymentProviderType::Revolut))?;
-
-            if let PaymentProvider::Revolut {
-                scheme: offramper_scheme,
-                id: offramper_id,
-                name: offramper_name,
-            } = offramper_provider
-            {
-                let consent_id = revolut::consent::create_account_access_consent(
-                    fiat_amount,
-                    currency_symbol,
-                    onramper_scheme,
-                    onramper_id,
-                    offramper_scheme,
-                    offramper_id,
-                    &offramper_name
-                        .clone()
-                        .ok_or(OrderError::InvalidOfframperProvider)?,
-                )
-                .await?;
-
-                let auth_url = revolut::authorize::get_authorization_url(&consent_id).await?;
-                Ok(Some(RevolutConsent::new(consent_id, auth_url)))
-            } else {
-                Err(OrderError::InvalidOrderState("Expected Revolut provider".to_string()).into())
-            }
-        }
-        _ => Ok(None),
-    }
-}
@@ -0,0 +1,48 @@
+use super::PaymentRail;
+use crate::{
+    errors::{OrderError, Result, SystemError},
+    types::{
+        orders::{LockedOrder, VerifiedPayment},
+        PaymentProvider, PaymentProviderType,
+    },
+};
+
+/// Manual bank transfer, confirmed by the offramper instead of a bank API.
+///
+/// See `management::order::claim_bank_transfer` and `confirm_bank_transfer`.
+pub struct BankTransfer;
+
+impl PaymentRail for BankTransfer {
+    const PROVIDER_TYPE: PaymentProviderType = PaymentProviderType::BankTransfer;
+
+    const SUPPORTED_CURRENCIES: &'static [&'static str] = &[];
+
+    fn validate_account(provider: &PaymentProvider) -> Result<()> {
+        let PaymentProvider::BankTransfer {
+            account_holder,
+            account_number,
+            ..
+        } = provider
+        else {
+            return Err(OrderError::InvalidOnramperProvider.into());
+        };
+        if account_holder.is_empty() || account_number.is_empty() {
+            return Err(
+                SystemError::InvalidInput("Bank account details are empty".to_string()).into(),
+            );
+        }
+        Ok(())
+    }
+
+    async fn verify_payment(
+        _order: &LockedOrder,
+        _transaction_id: &str,
+    ) -> Result<VerifiedPayment> {
+        Err(OrderError::OfframperConfirmationRequired)?
+    }
+
+    // Any currency a bank account can hold
+    fn supports_currency(currency: &str) -> bool {
+        currency.len() == 3 && currency.chars().all(|c| c.is_ascii_alphabetic())
+    }
+}
@@ -0,0 +1,94 @@
+use super::PaymentRail;
+use crate::{
+    errors::{OrderError, Result, SystemError},
+    model::types::payment::mock::{self, MockPayment},
+    types::{
+        orders::{LockedOrder, VerifiedPayment},
+        PaymentProvider, PaymentProviderType,
+    },
+};
+
+pub use crate::model::types::payment::mock::MockOutcome;
+
+/// Simulated payment rail for local and integration testing.
+///
+/// Payments are created by a controller with [`Mock::simulate_payment`] and then
+/// go through the regular `verify_transaction` path, so the whole order flow can
+/// be exercised without a sandbox account at a real provider.
+pub struct Mock;
+
+impl Mock {
+    /// Records a payment for the locked order `order` shaped after `outcome` and
+    /// returns the transaction id to verify it with.
+    pub fn simulate_payment(order: &LockedOrder, outcome: MockOutcome) -> Result<String> {
+        let PaymentProvider::Mock { id: payer } = &order.onramper.provider else {
+            return Err(OrderError::InvalidOnramperProvider)?;
+        };
+        let PaymentProvider::Mock { id: payee } = Self::offramper_provider(&order.base)? else {
+            return Err(OrderError::InvalidOfframperProvider)?;
+        };
+
+        let payment = MockPayment::new(order, payer, payee, outcome);
+        let transaction_id = format!("mock-{}-{}", order.base.id, ic_cdk::api::time());
+        mock::insert_payment(transaction_id.clone(), payment);
+        Ok(transaction_id)
+    }
+
+    /// Settles a pending simulated payment, which the payment poller then picks up.
+    /// Returns the id of the order it pays.
+    pub fn settle_payment(transaction_id: &str) -> Result<u64> {
+        mock::settle_payment(transaction_id).ok_or_else(|| {
+            SystemError::InvalidInput(format!("Unknown mock payment {}", transaction_id)).into()
+        })
+    }
+}
+
+impl PaymentRail for Mock {
+    const PROVIDER_TYPE: PaymentProviderType = PaymentProviderType::Mock;
+
+    const SUPPORTED_CURRENCIES: &'static [&'static str] = &[];
+
+    const POLLS_PAYMENT_STATUS: bool = true;
+
+    fn validate_account(provider: &PaymentProvider) -> Result<()> {
+        let PaymentProvider::Mock { id } = provider else {
+            return Err(OrderError::InvalidOnramperProvider.into());
+        };
+        if id.is_empty() {
+            return Err(SystemError::InvalidInput("Mock account id is empty".to_string()).into());
+        }
+        Ok(())
+    }
+
+    async fn verify_payment(order: &LockedOrder, transaction_id: &str) -> Result<VerifiedPayment> {
+        let PaymentProvider::Mock { id: onramper_id } = &order.onramper.provider else {
+            return Err(OrderError::InvalidOnramperProvider)?;
+        };
+        let PaymentProvider::Mock { id: offramper_id } = Self::offramper_provider(&order.base)?
+        else {
+            return Err(OrderError::InvalidOfframperProvider)?;
+        };
+
+        let payment =
+            mock::get_payment(transaction_id).ok_or(OrderError::PaymentVerificationFailed)?;
+
+        let verified_payment =
+            super::check_payment_amount(order, &payment.currency, payment.amount).await?;
+        if !payment.pays(order, onramper_id, offramper_id) {
+            return Err(OrderError::PaymentVerificationFailed.into());
+        }
+        if payment.pending {
+            return Err(OrderError::PaymentPending)?;
+        }
+
+        ic_cdk::println!(
+            "[verify_transaction] Mock payment {} verified",
+            transaction_id
+        );
+        Ok(verified_payment)
+    }
+
+    fn supports_currency(_currency: &str) -> bool {
+        true
+    }
+}
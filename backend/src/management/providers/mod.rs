@@ -0,0 +1,233 @@
+mod bank_transfer;
+#[cfg(feature = "mock-provider")]
+pub mod mock;
+mod paypal;
+mod pix;
+mod revolut;
+mod upi;
+mod wise;
+
+pub use bank_transfer::BankTransfer;
+#[cfg(feature = "mock-provider")]
+pub use mock::Mock;
+pub use paypal::PayPal;
+pub use pix::Pix;
+pub use revolut::Revolut;
+pub use upi::Upi;
+pub use wise::Wise;
+
+use crate::{
+    errors::{OrderError, Result},
+    model::memory::heap::read_state,
+    outcalls::xrc_rates::{get_cached_exchange_rate, Asset, AssetClass},
+    types::{
+        orders::{
+            FxPayment, LockedOrder, Order, PreparedPayment, RefundStatus, VerifiedPayment,
+            DEFAULT_FX_TOLERANCE_BPS,
+        },
+        PaymentProvider, PaymentProviderType,
+    },
+};
+
+/// Operations a payment rail implements to take part in the order flow.
+///
+/// Rails are selected by [`PaymentProviderType`] in the dispatch functions of this
+/// module, which is the only place to register a new one.
+pub(crate) trait PaymentRail {
+    const PROVIDER_TYPE: PaymentProviderType;
+
+    /// ISO 4217 codes of the fiat currencies the rail can settle.
+    const SUPPORTED_CURRENCIES: &'static [&'static str];
+
+    /// Checks the account details a user registers for this rail.
+    fn validate_account(provider: &PaymentProvider) -> Result<()>;
+
+    /// Runs before an order is locked, e.g. to request the onramper's payment consent
+    /// or to generate the instructions the onramper pays with. `amount` is the total
+    /// the onramper pays, in cents, and `reference` the order's payment reference.
+    async fn prepare_payment(
+        _order: &Order,
+        _onramper_provider: &PaymentProvider,
+        _amount: u64,
+        _reference: &str,
+    ) -> Result<PreparedPayment> {
+        Ok(PreparedPayment::default())
+    }
+
+    /// Whether the rail reports in-flight payments as
+    /// [`OrderError::PaymentPending`], so that they are polled until settled.
+    const POLLS_PAYMENT_STATUS: bool = false;
+
+    /// Checks that `transaction_id` is a settled payment matching the locked order,
+    /// returning the amount it credits. Whether that amount covers the order is
+    /// decided by the caller, see `management::order::credit_payment`.
+    async fn verify_payment(order: &LockedOrder, transaction_id: &str) -> Result<VerifiedPayment>;
+
+    /// Refunds the onramper's fiat payment of a paid order, returning where the
+    /// refund stands once the provider accepted it.
+    async fn refund_payment(_order: &LockedOrder) -> Result<RefundStatus> {
+        Err(OrderError::RefundNotSupported(Self::PROVIDER_TYPE).into())
+    }
+
+    fn supports_currency(currency: &str) -> bool {
+        Self::SUPPORTED_CURRENCIES
+            .iter()
+            .any(|supported| supported.eq_ignore_ascii_case(currency))
+    }
+
+    fn offramper_provider(order: &Order) -> Result<&PaymentProvider> {
+        order
+            .offramper_providers
+            .get(&Self::PROVIDER_TYPE)
+            .ok_or(OrderError::InvalidOfframperProvider.into())
+    }
+}
+
+/// Converts the `amount` (cents) of a payment received in `currency` to the
+/// cents it credits the order with. Payments in another currency the order accepts are
+/// converted at the cached XRC rate, and credited as the outstanding amount when
+/// within the configured FX tolerance of it.
+pub(crate) async fn check_payment_amount(
+    order: &LockedOrder,
+    currency: &str,
+    amount: u64,
+) -> Result<VerifiedPayment> {
+    if order.base.currency.eq_ignore_ascii_case(currency) {
+        return Ok(VerifiedPayment::new(amount));
+    }
+    if !order.base.accepts_currency(currency) {
+        return Err(OrderError::PaymentVerificationFailed.into());
+    }
+
+    let rate = get_cached_exchange_rate(
+        Asset {
+            symbol: currency.to_uppercase(),
+            class: AssetClass::FiatCurrency,
+        },
+        Asset {
+            symbol: order.base.currency.to_uppercase(),
+            class: AssetClass::FiatCurrency,
+        },
+    )
+    .await?;
+    let fx_payment = FxPayment::new(currency.to_uppercase(), amount as f64 / 100., rate);
+
+    let tolerance_bps = read_state(|s| s.fx_tolerance_bps).unwrap_or(DEFAULT_FX_TOLERANCE_BPS);
+    let expected_amount = order.outstanding_amount() as f64 / 100.;
+    let amount = if fx_payment.is_within_tolerance(expected_amount, tolerance_bps) {
+        order.outstanding_amount()
+    } else {
+        ic_cdk::println!(
+            "[check_payment_amount] {} {} converts to {} {}, expected {}",
+            fx_payment.received_amount,
+            currency,
+            fx_payment.converted_amount,
+            order.base.currency,
+            expected_amount
+        );
+        (fx_payment.converted_amount * 100.).round() as u64
+    };
+    Ok(VerifiedPayment {
+        amount,
+        fx_payment: Some(fx_payment),
+    })
+}
+
+pub fn validate_account(provider: &PaymentProvider) -> Result<()> {
+    match provider.provider_type() {
+        PaymentProviderType::PayPal => PayPal::validate_account(provider),
+        PaymentProviderType::Revolut => Revolut::validate_account(provider),
+        PaymentProviderType::Wise => Wise::validate_account(provider),
+        PaymentProviderType::Pix => Pix::validate_account(provider),
+        PaymentProviderType::Upi => Upi::validate_account(provider),
+        PaymentProviderType::BankTransfer => BankTransfer::validate_account(provider),
+        #[cfg(feature = "mock-provider")]
+        PaymentProviderType::Mock => Mock::validate_account(provider),
+    }
+}
+
+pub fn supports_currency(provider_type: &PaymentProviderType, currency: &str) -> bool {
+    match provider_type {
+        PaymentProviderType::PayPal => PayPal::supports_currency(currency),
+        PaymentProviderType::Revolut => Revolut::supports_currency(currency),
+        PaymentProviderType::Wise => Wise::supports_currency(currency),
+        PaymentProviderType::Pix => Pix::supports_currency(currency),
+        PaymentProviderType::Upi => Upi::supports_currency(currency),
+        PaymentProviderType::BankTransfer => BankTransfer::supports_currency(currency),
+        #[cfg(feature = "mock-provider")]
+        PaymentProviderType::Mock => Mock::supports_currency(currency),
+    }
+}
+
+pub fn polls_payment_status(provider_type: &PaymentProviderType) -> bool {
+    match provider_type {
+        PaymentProviderType::PayPal => PayPal::POLLS_PAYMENT_STATUS,
+        PaymentProviderType::Revolut => Revolut::POLLS_PAYMENT_STATUS,
+        PaymentProviderType::Wise => Wise::POLLS_PAYMENT_STATUS,
+        PaymentProviderType::Pix => Pix::POLLS_PAYMENT_STATUS,
+        PaymentProviderType::Upi => Upi::POLLS_PAYMENT_STATUS,
+        PaymentProviderType::BankTransfer => BankTransfer::POLLS_PAYMENT_STATUS,
+        #[cfg(feature = "mock-provider")]
+        PaymentProviderType::Mock => Mock::POLLS_PAYMENT_STATUS,
+    }
+}
+
+pub async fn prepare_payment(
+    order: &Order,
+    onramper_provider: &PaymentProvider,
+    amount: u64,
+    reference: &str,
+) -> Result<PreparedPayment> {
+    match onramper_provider.provider_type() {
+        PaymentProviderType::PayPal => {
+            PayPal::prepare_payment(order, onramper_provider, amount, reference).await
+        }
+        PaymentProviderType::Revolut => {
+            Revolut::prepare_payment(order, onramper_provider, amount, reference).await
+        }
+        PaymentProviderType::Wise => {
+            Wise::prepare_payment(order, onramper_provider, amount, reference).await
+        }
+        PaymentProviderType::Pix => {
+            Pix::prepare_payment(order, onramper_provider, amount, reference).await
+        }
+        PaymentProviderType::Upi => {
+            Upi::prepare_payment(order, onramper_provider, amount, reference).await
+        }
+        PaymentProviderType::BankTransfer => {
+            BankTransfer::prepare_payment(order, onramper_provider, amount, reference).await
+        }
+        #[cfg(feature = "mock-provider")]
+        PaymentProviderType::Mock => {
+            Mock::prepare_payment(order, onramper_provider, amount, reference).await
+        }
+    }
+}
+
+pub async fn verify_payment(order: &LockedOrder, transaction_id: &str) -> Result<VerifiedPayment> {
+    match order.onramper.provider.provider_type() {
+        PaymentProviderType::PayPal => PayPal::verify_payment(order, transaction_id).await,
+        PaymentProviderType::Revolut => Revolut::verify_payment(order, transaction_id).await,
+        PaymentProviderType::Wise => Wise::verify_payment(order, transaction_id).await,
+        PaymentProviderType::Pix => Pix::verify_payment(order, transaction_id).await,
+        PaymentProviderType::Upi => Upi::verify_payment(order, transaction_id).await,
+        PaymentProviderType::BankTransfer => {
+            BankTransfer::verify_payment(order, transaction_id).await
+        }
+        #[cfg(feature = "mock-provider")]
+        PaymentProviderType::Mock => Mock::verify_payment(order, transaction_id).await,
+    }
+}
+
+pub async fn refund_payment(order: &LockedOrder) -> Result<RefundStatus> {
+    match order.onramper.provider.provider_type() {
+        PaymentProviderType::PayPal => PayPal::refund_payment(order).await,
+        PaymentProviderType::Revolut => Revolut::refund_payment(order).await,
+        PaymentProviderType::Wise => Wise::refund_payment(order).await,
+        PaymentProviderType::Pix => Pix::refund_payment(order).await,
+        PaymentProviderType::Upi => Upi::refund_payment(order).await,
+        PaymentProviderType::BankTransfer => BankTransfer::refund_payment(order).await,
+        #[cfg(feature = "mock-provider")]
+        PaymentProviderType::Mock => Mock::refund_payment(order).await,
+    }
+}
@@ -0,0 +1,212 @@
+use super::PaymentRail;
+use crate::{
+    errors::{OrderError, Result, SystemError},
+    outcalls::paypal::{
+        self,
+        order::{Amount, CreateOrderRequest, Payee, PurchaseUnitRequest},
+    },
+    types::{
+        orders::{parse_fiat_amount, LockedOrder, RefundStatus, VerifiedPayment},
+        PaymentProvider, PaymentProviderType,
+    },
+};
+
+/// Currencies PayPal only takes whole amounts of.
+const ZERO_DECIMAL_CURRENCIES: &[&str] = &["HUF", "JPY", "TWD"];
+
+pub struct PayPal;
+
+impl PayPal {
+    /// Formats `amount` cents as a PayPal amount value of `currency`. Amounts of
+    /// currencies without decimals are rounded up so that they cover the order.
+    pub fn format_amount(amount: u64, currency: &str) -> String {
+        if ZERO_DECIMAL_CURRENCIES
+            .iter()
+            .any(|zero_decimal| zero_decimal.eq_ignore_ascii_case(currency))
+        {
+            amount.div_ceil(100).to_string()
+        } else {
+            format!("{}.{:02}", amount / 100, amount % 100)
+        }
+    }
+
+    /// The PayPal order the onramper approves: paid to the offramper's account,
+    /// for the amount still outstanding and tagged with its payment reference.
+    pub fn order_request(order: &LockedOrder) -> Result<CreateOrderRequest> {
+        let PaymentProvider::PayPal { id: offramper_id } = Self::offramper_provider(&order.base)?
+        else {
+            return Err(OrderError::InvalidOfframperProvider)?;
+        };
+        let amount = order.outstanding_amount();
+
+        Ok(CreateOrderRequest {
+            intent: "CAPTURE".to_string(),
+            purchase_units: vec![PurchaseUnitRequest {
+                custom_id: order.payment_reference.clone(),
+                invoice_id: order.payment_reference.clone(),
+                amount: Amount {
+                    currency_code: order.base.currency.clone(),
+                    value: Self::format_amount(amount, &order.base.currency),
+                },
+                payee: Payee {
+                    email_address: offramper_id.clone(),
+                },
+            }],
+        })
+    }
+
+    /// Creates the PayPal order of a locked order, returning its id.
+    pub async fn create_order(order: &LockedOrder) -> Result<String> {
+        let order_request = Self::order_request(order)?;
+        // Top-ups of an underpaid order are new PayPal orders
+        let idempotency_key = format!(
+            "create-order-{}-{}-{}",
+            order.base.id,
+            order.locked_at,
+            order.outstanding_amount()
+        );
+
+        let access_token = paypal::auth::get_paypal_access_token().await?;
+        paypal::order::create_paypal_order(&access_token, &order_request, &idempotency_key).await
+    }
+}
+
+impl PaymentRail for PayPal {
+    const PROVIDER_TYPE: PaymentProviderType = PaymentProviderType::PayPal;
+
+    const SUPPORTED_CURRENCIES: &'static [&'static str] = &[
+        "AUD", "BRL", "CAD", "CHF", "CNY", "CZK", "DKK", "EUR", "GBP", "HKD", "HUF", "ILS", "JPY",
+        "MXN", "MYR", "NOK", "NZD", "PHP", "PLN", "SEK", "SGD", "THB", "TWD", "USD",
+    ];
+
+    const POLLS_PAYMENT_STATUS: bool = true;
+
+    fn validate_account(provider: &PaymentProvider) -> Result<()> {
+        let PaymentProvider::PayPal { id } = provider else {
+            return Err(OrderError::InvalidOnramperProvider.into());
+        };
+        if id.is_empty() {
+            return Err(SystemError::InvalidInput("Paypal ID is empty".to_string()).into());
+        }
+        Ok(())
+    }
+
+    async fn verify_payment(order: &LockedOrder, transaction_id: &str) -> Result<VerifiedPayment> {
+        let PaymentProvider::PayPal { id: onramper_id } = &order.onramper.provider else {
+            return Err(OrderError::InvalidOnramperProvider)?;
+        };
+        let PaymentProvider::PayPal { id: offramper_id } = Self::offramper_provider(&order.base)?
+        else {
+            return Err(OrderError::InvalidOfframperProvider)?;
+        };
+
+        let access_token = paypal::auth::get_paypal_access_token().await?;
+        ic_cdk::println!("[verify_transaction] Obtained PayPal access token");
+        let capture_details =
+            paypal::order::fetch_paypal_order(&access_token, transaction_id).await?;
+
+        // Orders approved but not captured yet carry no captures
+        let captures: Vec<_> = capture_details
+            .purchase_units
+            .iter()
+            .flat_map(|unit| &unit.payments.captures)
+            .collect();
+        let received_amount: u64 = if captures.is_empty() {
+            capture_details
+                .purchase_units
+                .iter()
+                .filter_map(|unit| parse_fiat_amount(&unit.amount.value))
+                .sum()
+        } else {
+            captures
+                .iter()
+                .filter_map(|capture| parse_fiat_amount(&capture.amount.value))
+                .sum()
+        };
+
+        let verified_payment = super::check_payment_amount(
+            order,
+            &capture_details.purchase_units[0].amount.currency_code,
+            received_amount,
+        )
+        .await?;
+        let offramper_matches =
+            capture_details.purchase_units[0].payee.email_address == *offramper_id;
+        let onramper_matches = capture_details.payer.email_address == *onramper_id;
+        // The canister creates every PayPal order with the payment reference;
+        // orders locked before references existed accept any
+        let reference_matches = capture_details
+            .purchase_units
+            .iter()
+            .flat_map(|unit| [unit.custom_id.as_deref(), unit.invoice_id.as_deref()])
+            .any(|reference| order.payment_reference_matches(reference));
+
+        if !(offramper_matches && onramper_matches && reference_matches) {
+            return Err(OrderError::PaymentVerificationFailed.into());
+        }
+
+        let capture_pending = captures.iter().any(|capture| capture.status == "PENDING");
+        match capture_details.status.as_str() {
+            "COMPLETED" if !capture_pending => {
+                ic_cdk::println!("[verify_transaction] Verification succeded.");
+                Ok(verified_payment)
+            }
+            "COMPLETED" | "APPROVED" => Err(OrderError::PaymentPending)?,
+            _ => Err(OrderError::PaymentVerificationFailed)?,
+        }
+    }
+
+    async fn refund_payment(order: &LockedOrder) -> Result<RefundStatus> {
+        let PaymentProvider::PayPal { id: offramper_id } = Self::offramper_provider(&order.base)?
+        else {
+            return Err(OrderError::InvalidOfframperProvider)?;
+        };
+        let paypal_order_ids = order.payment_ids();
+        if paypal_order_ids.is_empty() {
+            return Err(
+                OrderError::InvalidOrderState("Order has no PayPal order id".to_string()).into(),
+            );
+        }
+
+        let access_token = paypal::auth::get_paypal_access_token().await?;
+        // Refund requests are idempotent per capture, so retries return the same refunds
+        let mut refund_ids = Vec::new();
+        for paypal_order_id in &paypal_order_ids {
+            let order_details =
+                paypal::order::fetch_paypal_order(&access_token, paypal_order_id).await?;
+            for capture in order_details
+                .purchase_units
+                .iter()
+                .flat_map(|unit| &unit.payments.captures)
+                .filter(|capture| {
+                    matches!(
+                        capture.status.as_str(),
+                        "COMPLETED" | "PARTIALLY_REFUNDED" | "REFUNDED"
+                    )
+                })
+            {
+                let refund =
+                    paypal::refund::refund_capture(&access_token, &capture.id, offramper_id)
+                        .await?;
+                ic_cdk::println!(
+                    "[refund_payment] capture {} of {} refund {} is {}",
+                    capture.id,
+                    paypal_order_id,
+                    refund.id,
+                    refund.status
+                );
+                refund_ids.push(refund.id);
+            }
+        }
+
+        if refund_ids.is_empty() {
+            return Err(OrderError::InvalidOrderState(
+                "PayPal order has no capture to refund".to_string(),
+            )
+            .into());
+        }
+        Ok(RefundStatus::Refunded {
+            refund_id: refund_ids.join(","),
+        })
+    }
+}
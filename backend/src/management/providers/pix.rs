@@ -0,0 +1,155 @@
+use super::PaymentRail;
+use crate::{
+    errors::{OrderError, Result},
+    model::memory::heap::LOCK_DURATION_TIME_SECONDS,
+    outcalls::{
+        client_credentials,
+        pix::{
+            self,
+            charge::{PixChargeCalendar, PixChargeRequest, PixChargeValue, PixDebtor},
+        },
+    },
+    types::{
+        orders::{
+            parse_fiat_amount, LockedOrder, Order, PaymentInstructions, PreparedPayment,
+            VerifiedPayment,
+        },
+        payment::{
+            client_credentials::ClientCredentialsRail,
+            pix::{brcode_payload, payer_matches, PixKeyType},
+        },
+        PaymentProvider, PaymentProviderType,
+    },
+};
+
+pub struct Pix;
+
+impl PaymentRail for Pix {
+    const PROVIDER_TYPE: PaymentProviderType = PaymentProviderType::Pix;
+
+    const SUPPORTED_CURRENCIES: &'static [&'static str] = &["BRL"];
+
+    fn validate_account(provider: &PaymentProvider) -> Result<()> {
+        let PaymentProvider::Pix { key_type, key, .. } = provider else {
+            return Err(OrderError::InvalidOnramperProvider.into());
+        };
+        key_type.validate_key(key)
+    }
+
+    /// Creates the PSP charge the onramper pays, open for the lock time and
+    /// showing the order's payment reference, and the dynamic BR Code pointing
+    /// to it. The txid assigned by the PSP ties the PIX to the order.
+    async fn prepare_payment(
+        order: &Order,
+        onramper_provider: &PaymentProvider,
+        amount: u64,
+        reference: &str,
+    ) -> Result<PreparedPayment> {
+        let PaymentProvider::Pix {
+            key, name, city, ..
+        } = Self::offramper_provider(order)?
+        else {
+            return Err(OrderError::InvalidOfframperProvider)?;
+        };
+        let PaymentProvider::Pix {
+            key_type: onramper_key_type,
+            key: onramper_key,
+            name: onramper_name,
+            ..
+        } = onramper_provider
+        else {
+            return Err(OrderError::InvalidOnramperProvider)?;
+        };
+
+        // Only keys that are tax ids identify the payer to the PSP
+        let devedor = match onramper_key_type {
+            PixKeyType::Cpf | PixKeyType::Cnpj => Some(PixDebtor {
+                cpf: (*onramper_key_type == PixKeyType::Cpf).then(|| onramper_key.clone()),
+                cnpj: (*onramper_key_type == PixKeyType::Cnpj).then(|| onramper_key.clone()),
+                nome: onramper_name.clone(),
+            }),
+            _ => None,
+        };
+        let charge_request = PixChargeRequest {
+            calendario: PixChargeCalendar {
+                expiracao: LOCK_DURATION_TIME_SECONDS,
+            },
+            devedor,
+            valor: PixChargeValue {
+                original: format!("{}.{:02}", amount / 100, amount % 100),
+            },
+            chave: key.clone(),
+            solicitacao_pagador: reference.to_string(),
+        };
+
+        let access_token = client_credentials::get_access_token(ClientCredentialsRail::Pix).await?;
+        let charge =
+            pix::charge::create_pix_charge(&access_token, &charge_request, reference).await?;
+        ic_cdk::println!(
+            "[prepare_payment] PIX charge {} is {}",
+            charge.txid,
+            charge.status
+        );
+
+        let payload = charge
+            .pix_copia_e_cola
+            .unwrap_or_else(|| brcode_payload(&charge.location, name, city));
+        Ok(PreparedPayment {
+            instructions: Some(PaymentInstructions::Pix {
+                txid: charge.txid,
+                payload,
+            }),
+            ..Default::default()
+        })
+    }
+
+    /// `transaction_id` is the end-to-end id of the PIX, looked up on the PSP.
+    async fn verify_payment(order: &LockedOrder, transaction_id: &str) -> Result<VerifiedPayment> {
+        let PaymentProvider::Pix {
+            key_type: onramper_key_type,
+            key: onramper_key,
+            name: onramper_name,
+            ..
+        } = &order.onramper.provider
+        else {
+            return Err(OrderError::InvalidOnramperProvider)?;
+        };
+        let PaymentProvider::Pix { key, .. } = Self::offramper_provider(&order.base)? else {
+            return Err(OrderError::InvalidOfframperProvider)?;
+        };
+
+        let access_token = client_credentials::get_access_token(ClientCredentialsRail::Pix).await?;
+        let payment = pix::payment::fetch_pix_payment(&access_token, transaction_id).await?;
+
+        // PIX only settles BRL, the order's own currency
+        let amount = parse_fiat_amount(&payment.valor);
+        // Orders locked before charges were created carry their reference as txid
+        let txid_matches = match &order.payment_instructions {
+            Some(PaymentInstructions::Pix { txid, .. }) => payment.txid.as_ref() == Some(txid),
+            _ => order.payment_reference_matches(payment.txid.as_deref()),
+        };
+        let key_matches = payment.chave.eq_ignore_ascii_case(key);
+        let payer_matches = payment.pagador.as_ref().is_some_and(|payer| {
+            payer_matches(
+                onramper_key_type,
+                onramper_key,
+                onramper_name,
+                payer.document(),
+                payer.nome.as_deref(),
+            )
+        });
+
+        match amount {
+            Some(amount)
+                if payment.end_to_end_id == transaction_id
+                    && txid_matches
+                    && key_matches
+                    && payer_matches =>
+            {
+                ic_cdk::println!("[verify_transaction] PIX payment verified.");
+                Ok(VerifiedPayment::new(amount))
+            }
+            _ => Err(OrderError::PaymentVerificationFailed)?,
+        }
+    }
+}
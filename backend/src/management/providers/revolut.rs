@@ -0,0 +1,230 @@
+use super::PaymentRail;
+use crate::{
+    errors::{OrderError, Result, SystemError},
+    outcalls::revolut::{self, initiation::PaymentInitiation},
+    types::{
+        orders::{
+            parse_fiat_amount, LockedOrder, Order, PreparedPayment, RefundStatus, RevolutConsent,
+            VerifiedPayment,
+        },
+        PaymentProvider, PaymentProviderType,
+    },
+};
+
+pub struct Revolut;
+
+impl Revolut {
+    /// The initiation shared by the consent requested at lock time and the
+    /// payment executed once the onramper authorized it.
+    pub fn payment_initiation(
+        order: &Order,
+        onramper_provider: &PaymentProvider,
+        amount: u64,
+        reference: Option<&str>,
+    ) -> Result<PaymentInitiation> {
+        let PaymentProvider::Revolut {
+            scheme: onramper_scheme,
+            id: onramper_id,
+            ..
+        } = onramper_provider
+        else {
+            return Err(OrderError::InvalidOnramperProvider)?;
+        };
+        let PaymentProvider::Revolut {
+            scheme: offramper_scheme,
+            id: offramper_id,
+            name: offramper_name,
+        } = Self::offramper_provider(order)?
+        else {
+            return Err(OrderError::InvalidOrderState(
+                "Expected Revolut provider".to_string(),
+            ))?;
+        };
+
+        Ok(PaymentInitiation {
+            amount: (amount as f64 / 100.).to_string(),
+            currency: order.currency.clone(),
+            debtor_scheme: onramper_scheme.clone(),
+            debtor_id: onramper_id.clone(),
+            creditor_scheme: offramper_scheme.clone(),
+            creditor_id: offramper_id.clone(),
+            creditor_name: offramper_name
+                .clone()
+                .ok_or(OrderError::InvalidOfframperProvider)?,
+            reference: reference.map(str::to_string),
+        })
+    }
+}
+
+impl Revolut {
+    /// The initiation of the onramper's payment of a locked order, the one its
+    /// consent was created for.
+    pub fn locked_payment_initiation(order: &LockedOrder) -> Result<PaymentInitiation> {
+        Self::payment_initiation(
+            &order.base,
+            &order.onramper.provider,
+            order.total_amount(),
+            order.payment_reference.as_deref(),
+        )
+    }
+
+    /// Formats `amount` cents as a Revolut amount value.
+    pub fn format_amount(amount: u64) -> String {
+        format!("{}.{:02}", amount / 100, amount % 100)
+    }
+
+    /// The initiation of a refund, paying what the onramper actually paid back
+    /// from the offramper's account to the onramper's.
+    pub fn refund_initiation(order: &LockedOrder) -> Result<PaymentInitiation> {
+        let PaymentProvider::Revolut {
+            scheme: onramper_scheme,
+            id: onramper_id,
+            name: onramper_name,
+        } = &order.onramper.provider
+        else {
+            return Err(OrderError::InvalidOnramperProvider)?;
+        };
+        let PaymentProvider::Revolut {
+            scheme: offramper_scheme,
+            id: offramper_id,
+            ..
+        } = Self::offramper_provider(&order.base)?
+        else {
+            return Err(OrderError::InvalidOfframperProvider)?;
+        };
+
+        let (amount, currency) = order.received_payment();
+        Ok(PaymentInitiation {
+            amount: Self::format_amount(amount),
+            currency,
+            debtor_scheme: offramper_scheme.clone(),
+            debtor_id: offramper_id.clone(),
+            creditor_scheme: onramper_scheme.clone(),
+            creditor_id: onramper_id.clone(),
+            creditor_name: onramper_name
+                .clone()
+                .ok_or(OrderError::InvalidOnramperProvider)?,
+            reference: order
+                .payment_reference
+                .as_ref()
+                .map(|reference| format!("REFUND {}", reference)),
+        })
+    }
+
+    /// Executes the refund payment once the offramper authorized its consent,
+    /// returning the payment id.
+    pub async fn execute_refund(order: &LockedOrder, consent_id: &str) -> Result<String> {
+        let initiation = Self::refund_initiation(order)?;
+        let access_token = revolut::token::get_revolut_access_token(consent_id.to_string()).await?;
+        revolut::pay::initiate_domestic_payment(consent_id, &access_token, &initiation).await
+    }
+}
+
+impl PaymentRail for Revolut {
+    const PROVIDER_TYPE: PaymentProviderType = PaymentProviderType::Revolut;
+
+    const SUPPORTED_CURRENCIES: &'static [&'static str] = &["EUR", "GBP", "USD"];
+
+    const POLLS_PAYMENT_STATUS: bool = true;
+
+    fn validate_account(provider: &PaymentProvider) -> Result<()> {
+        let PaymentProvider::Revolut { scheme, id, .. } = provider else {
+            return Err(OrderError::InvalidOnramperProvider.into());
+        };
+        if scheme.is_empty() || id.is_empty() {
+            return Err(SystemError::InvalidInput("Revolut details are empty".to_string()).into());
+        }
+        Ok(())
+    }
+
+    async fn prepare_payment(
+        order: &Order,
+        onramper_provider: &PaymentProvider,
+        amount: u64,
+        reference: &str,
+    ) -> Result<PreparedPayment> {
+        let initiation =
+            Self::payment_initiation(order, onramper_provider, amount, Some(reference))?;
+        let consent_id = revolut::consent::create_account_access_consent(&initiation).await?;
+
+        let auth_url = revolut::authorize::get_authorization_url(&consent_id).await?;
+        Ok(PreparedPayment {
+            revolut_consent: Some(RevolutConsent::new(consent_id, auth_url)),
+            ..Default::default()
+        })
+    }
+
+    async fn verify_payment(order: &LockedOrder, transaction_id: &str) -> Result<VerifiedPayment> {
+        let PaymentProvider::Revolut {
+            scheme: onramper_scheme,
+            id: onramper_id,
+            ..
+        } = &order.onramper.provider
+        else {
+            return Err(OrderError::InvalidOnramperProvider)?;
+        };
+        let PaymentProvider::Revolut {
+            scheme: offramper_scheme,
+            id: offramper_id,
+            name: offramper_name,
+        } = Self::offramper_provider(&order.base)?
+        else {
+            return Err(OrderError::InvalidOfframperProvider)?;
+        };
+
+        let payment_details =
+            revolut::transaction::fetch_revolut_payment_details(transaction_id).await?;
+
+        // Verify the captured payment details (amounts are in cents)
+        let amount = parse_fiat_amount(&payment_details.data.initiation.instructed_amount.amount)
+            .ok_or(OrderError::PaymentVerificationFailed)?;
+        let currency_matches =
+            payment_details.data.initiation.instructed_amount.currency == order.base.currency;
+
+        let onramper_account = match payment_details.data.initiation.debtor_account {
+            Some(details) => details,
+            None => return Err(OrderError::MissingDebtorAccount)?,
+        };
+        let debtor_matches = onramper_account.scheme_name == *onramper_scheme
+            && onramper_account.identification == *onramper_id;
+
+        let offramper_account = payment_details.data.initiation.creditor_account;
+        let creditor_matches = offramper_account.scheme_name == *offramper_scheme
+            && offramper_account.identification == *offramper_id
+            && offramper_account.name == *offramper_name;
+
+        let reference_matches = order.payment_reference_matches(
+            payment_details
+                .data
+                .initiation
+                .remittance_information
+                .and_then(|info| info.reference)
+                .as_deref(),
+        );
+
+        if !(currency_matches && reference_matches && debtor_matches && creditor_matches) {
+            return Err(OrderError::PaymentVerificationFailed.into());
+        }
+
+        match payment_details.data.status.as_str() {
+            "AcceptedSettlementCompleted" => {
+                ic_cdk::println!("[verify_transaction] verified is true!!");
+                Ok(VerifiedPayment::new(amount))
+            }
+            "Pending" | "AcceptedSettlementInProcess" => Err(OrderError::PaymentPending)?,
+            _ => Err(OrderError::PaymentVerificationFailed)?,
+        }
+    }
+
+    /// Revolut cannot reverse a payment: the offramper has to authorize a
+    /// payment back to the onramper.
+    async fn refund_payment(order: &LockedOrder) -> Result<RefundStatus> {
+        let initiation = Self::refund_initiation(order)?;
+        let consent_id = revolut::consent::create_account_access_consent(&initiation).await?;
+
+        let auth_url = revolut::authorize::get_authorization_url(&consent_id).await?;
+        Ok(RefundStatus::AwaitingOfframper(RevolutConsent::new(
+            consent_id, auth_url,
+        )))
+    }
+}
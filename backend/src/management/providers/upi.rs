@@ -0,0 +1,98 @@
+use super::PaymentRail;
+use crate::{
+    errors::{OrderError, Result},
+    outcalls::{
+        client_credentials,
+        upi::{self, transaction::UPI_TRANSACTION_SUCCESS},
+    },
+    types::{
+        orders::{
+            normalize_reference, parse_fiat_amount, LockedOrder, Order, PaymentInstructions,
+            PreparedPayment, VerifiedPayment,
+        },
+        payment::{
+            client_credentials::ClientCredentialsRail,
+            upi::{intent_uri, validate_vpa},
+        },
+        PaymentProvider, PaymentProviderType,
+    },
+};
+
+pub struct Upi;
+
+impl PaymentRail for Upi {
+    const PROVIDER_TYPE: PaymentProviderType = PaymentProviderType::Upi;
+
+    const SUPPORTED_CURRENCIES: &'static [&'static str] = &["INR"];
+
+    fn validate_account(provider: &PaymentProvider) -> Result<()> {
+        let PaymentProvider::Upi { vpa, .. } = provider else {
+            return Err(OrderError::InvalidOnramperProvider.into());
+        };
+        validate_vpa(vpa)
+    }
+
+    /// Generates the intent the onramper's UPI app pays the offramper's VPA with.
+    async fn prepare_payment(
+        order: &Order,
+        _onramper_provider: &PaymentProvider,
+        amount: u64,
+        reference: &str,
+    ) -> Result<PreparedPayment> {
+        let PaymentProvider::Upi { vpa, name } = Self::offramper_provider(order)? else {
+            return Err(OrderError::InvalidOfframperProvider)?;
+        };
+
+        let reference = normalize_reference(reference);
+        // Fall back to the VPA's handle for accounts registered without a name
+        let payee_name = name
+            .as_deref()
+            .unwrap_or_else(|| vpa.split('@').next().unwrap_or(vpa));
+        let intent_uri = intent_uri(vpa, payee_name, amount, &reference);
+        Ok(PreparedPayment {
+            instructions: Some(PaymentInstructions::Upi {
+                reference,
+                intent_uri,
+            }),
+            ..Default::default()
+        })
+    }
+
+    /// `transaction_id` is the UTR of the UPI transaction, looked up on the gateway.
+    async fn verify_payment(order: &LockedOrder, transaction_id: &str) -> Result<VerifiedPayment> {
+        let PaymentProvider::Upi {
+            vpa: onramper_vpa, ..
+        } = &order.onramper.provider
+        else {
+            return Err(OrderError::InvalidOnramperProvider)?;
+        };
+        let PaymentProvider::Upi {
+            vpa: offramper_vpa, ..
+        } = Self::offramper_provider(&order.base)?
+        else {
+            return Err(OrderError::InvalidOfframperProvider)?;
+        };
+
+        let access_token = client_credentials::get_access_token(ClientCredentialsRail::Upi).await?;
+        let transaction =
+            upi::transaction::fetch_upi_transaction(&access_token, transaction_id).await?;
+
+        let amount = parse_fiat_amount(&transaction.amount);
+        let reference_matches = order.payment_reference_matches(transaction.reference.as_deref());
+        let payer_matches = transaction.payer_vpa.eq_ignore_ascii_case(onramper_vpa);
+        let payee_matches = transaction.payee_vpa.eq_ignore_ascii_case(offramper_vpa);
+
+        match amount {
+            Some(amount)
+                if transaction.status == UPI_TRANSACTION_SUCCESS
+                    && reference_matches
+                    && payer_matches
+                    && payee_matches =>
+            {
+                ic_cdk::println!("[verify_transaction] UPI payment verified.");
+                super::check_payment_amount(order, &transaction.currency, amount).await
+            }
+            _ => Err(OrderError::PaymentVerificationFailed)?,
+        }
+    }
+}
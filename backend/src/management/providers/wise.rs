@@ -0,0 +1,92 @@
+use super::PaymentRail;
+use crate::{
+    errors::{OrderError, Result, SystemError},
+    outcalls::{
+        client_credentials,
+        wise::{self, transfer::WISE_TRANSFER_SENT},
+    },
+    types::{
+        orders::{parse_fiat_amount, LockedOrder, VerifiedPayment},
+        payment::client_credentials::ClientCredentialsRail,
+        PaymentProvider, PaymentProviderType,
+    },
+};
+
+pub struct Wise;
+
+impl PaymentRail for Wise {
+    const PROVIDER_TYPE: PaymentProviderType = PaymentProviderType::Wise;
+
+    const SUPPORTED_CURRENCIES: &'static [&'static str] = &[
+        "AUD", "BGN", "CAD", "CHF", "CZK", "DKK", "EUR", "GBP", "HKD", "HUF", "JPY", "NOK", "NZD",
+        "PLN", "RON", "SEK", "SGD", "USD",
+    ];
+
+    fn validate_account(provider: &PaymentProvider) -> Result<()> {
+        let PaymentProvider::Wise {
+            profile_id,
+            account_id,
+            ..
+        } = provider
+        else {
+            return Err(OrderError::InvalidOnramperProvider.into());
+        };
+        if profile_id.is_empty() || account_id.is_empty() {
+            return Err(SystemError::InvalidInput("Wise details are empty".to_string()).into());
+        }
+        if profile_id.parse::<u64>().is_err() {
+            return Err(
+                SystemError::InvalidInput("Wise profile ID must be numeric".to_string()).into(),
+            );
+        }
+        Ok(())
+    }
+
+    /// The onramper sends a Wise transfer with the order's payment reference; the
+    /// recipient entry it targets must belong to the onramper's profile and
+    /// point at the offramper's account.
+    async fn verify_payment(order: &LockedOrder, transaction_id: &str) -> Result<VerifiedPayment> {
+        let PaymentProvider::Wise {
+            profile_id: onramper_profile,
+            ..
+        } = &order.onramper.provider
+        else {
+            return Err(OrderError::InvalidOnramperProvider)?;
+        };
+        let PaymentProvider::Wise {
+            account_id: offramper_account,
+            name: offramper_name,
+            ..
+        } = Self::offramper_provider(&order.base)?
+        else {
+            return Err(OrderError::InvalidOfframperProvider)?;
+        };
+
+        let access_token =
+            client_credentials::get_access_token(ClientCredentialsRail::Wise).await?;
+        let transfer = wise::transfer::fetch_wise_transfer(&access_token, transaction_id).await?;
+        let recipient =
+            wise::transfer::fetch_wise_recipient(&access_token, transfer.target_account).await?;
+
+        let amount = parse_fiat_amount(&transfer.target_value.to_string());
+        let reference_matches = order.payment_reference_matches(Some(&transfer.reference));
+        let sender_matches = recipient.profile.to_string() == *onramper_profile;
+        let recipient_matches = recipient.matches_account(offramper_account)
+            && offramper_name
+                .as_ref()
+                .is_none_or(|name| recipient.account_holder_name.eq_ignore_ascii_case(name));
+
+        match amount {
+            Some(amount)
+                if transfer.status == WISE_TRANSFER_SENT
+                    && reference_matches
+                    && sender_matches
+                    && recipient_matches =>
+            {
+                ic_cdk::println!("[verify_transaction] Wise transfer verified.");
+                super::check_payment_amount(order, &transfer.target_currency, amount).await
+            }
+            _ => Err(OrderError::PaymentVerificationFailed)?,
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+use super::random;
+use crate::model::errors::{Result, SystemError};
+
+// RFC 6238 defaults, as expected by common authenticator apps
+pub const TOTP_STEP_SECS: u64 = 30;
+pub const TOTP_DIGITS: u32 = 6;
+// accepted clock drift, in steps, on each side of the current one
+const TOTP_SKEW_STEPS: u64 = 1;
+const TOTP_SECRET_BYTES: usize = 20;
+
+pub const RECOVERY_CODES: usize = 10;
+const RECOVERY_CODE_BYTES: usize = 6;
+
+const BASE32: base32::Alphabet = base32::Alphabet::RFC4648 { padding: false };
+
+pub async fn generate_secret() -> Result<String> {
+    let random_bytes = random::get_random_bytes().await?;
+    Ok(base32::encode(BASE32, &random_bytes[..TOTP_SECRET_BYTES]))
+}
+
+/// Generates single-use recovery codes, returned in plain text to be shown once to the user.
+pub async fn generate_recovery_codes() -> Result<Vec<String>> {
+    let mut random_bytes = random::get_random_bytes().await?.to_vec();
+    random_bytes.extend(random::get_random_bytes().await?);
+
+    Ok(random_bytes
+        .chunks_exact(RECOVERY_CODE_BYTES)
+        .take(RECOVERY_CODES)
+        .map(hex::encode)
+        .collect())
+}
+
+pub fn hash_recovery_code(code: &str) -> String {
+    hex::encode(Sha256::digest(code.trim().to_lowercase().as_bytes()))
+}
+
+pub fn otpauth_uri(secret: &str, account: &str) -> String {
+    format!(
+        "otpauth://totp/ic2P2ramp:{}?secret={}&issuer=ic2P2ramp&digits={}&period={}",
+        account, secret, TOTP_DIGITS, TOTP_STEP_SECS
+    )
+}
+
+pub fn generate_code(secret: &[u8], step: u64) -> String {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC accepts keys of any size");
+    mac.update(&step.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+
+    // dynamic truncation (RFC 4226 section 5.3)
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]);
+
+    format!(
+        "{:0width$}",
+        binary % 10u32.pow(TOTP_DIGITS),
+        width = TOTP_DIGITS as usize
+    )
+}
+
+/// Checks the code against the steps around `now` (in seconds).
+///
+/// Returns the matching time step, so that callers can reject its reuse.
+pub fn verify_code(secret: &str, code: &str, now: u64) -> Result<Option<u64>> {
+    let secret = base32::decode(BASE32, secret)
+        .ok_or_else(|| SystemError::InternalError("Invalid TOTP secret".to_string()))?;
+
+    let current_step = now / TOTP_STEP_SECS;
+    let code = code.trim();
+    Ok(
+        (current_step.saturating_sub(TOTP_SKEW_STEPS)..=current_step + TOTP_SKEW_STEPS)
+            .find(|step| generate_code(&secret, *step) == code),
+    )
+}
@@ -1,15 +1,29 @@
 use std::collections::HashSet;
 
-use super::random;
+use candid::Principal;
+use icrc_ledger_types::icrc1::{account::Account, transfer::NumTokens};
+
+use super::{random, totp};
 use crate::{
+    evm::{signer, vault::Ic2P2ramp},
+    icp::vault::Ic2P2ramp as ICPRamp,
     model::{
-        errors::{Result, SystemError, UserError},
-        memory::stable::users,
+        errors::{BlockchainError, Result, SystemError, UserError},
+        memory::{
+            heap::{self, logs, read_state},
+            stable::{login_attempts, orders, payment_ids, users},
+        },
     },
     types::{
+        evm::{chains, token as evm_token},
+        icp::get_icp_token,
+        kyc::{KycReservation, KycTier},
+        orders::{OrderId, OrderState},
+        referral::{self, ReferralClaim},
         session::Session,
-        user::{User, UserType},
-        LoginAddress, PaymentProvider, TransactionAddress,
+        user::{PendingEmailLink, TotpConfig, User, UserDataExport, UserType},
+        AddressType, AuthenticationData, Blockchain, Crypto, LoginAddress, PaymentProvider,
+        TransactionAddress,
     },
 };
 
@@ -18,8 +32,15 @@ pub async fn register_user(
     payment_providers: HashSet<PaymentProvider>,
     login_address: LoginAddress,
     password: Option<String>,
+    referrer_id: Option<u64>,
 ) -> Result<User> {
     login_address.validate()?;
+    if users::find_user_by_login_address(&login_address).is_ok() {
+        return Err(UserError::LoginAddressInUse)?;
+    }
+    if let Some(referrer_id) = referrer_id {
+        users::get_user(&referrer_id)?;
+    }
 
     let hashed_password: Result<Option<String>> = match login_address.clone() {
         LoginAddress::Email { .. } => {
@@ -48,9 +69,10 @@ pub async fn register_user(
 
     let mut user = User::new(user_type, login_address, hashed_password?)?;
     user.payment_providers = payment_providers;
+    user.referrer_id = referrer_id;
 
     users::insert_user(&user);
-    Ok(user)
+    Ok(user.redacted())
 }
 
 pub async fn reset_password_user(
@@ -118,6 +140,271 @@ pub fn remove_payment_provider(
     })?
 }
 
+pub async fn build_evm_auth_message(login_address: &LoginAddress) -> Result<String> {
+    let LoginAddress::EVM { address } = login_address else {
+        return Err(SystemError::InvalidInput(
+            "Login address is not of type EVM".to_string(),
+        ))?;
+    };
+
+    Ok(format!(
+        "Please sign this message to authenticate: {}\nNonce: {}",
+        address,
+        random::generate_token().await?
+    ))
+}
+
+pub async fn link_login_address(
+    user_id: u64,
+    token: &str,
+    login_address: LoginAddress,
+    auth_data: Option<AuthenticationData>,
+) -> Result<User> {
+    login_address.validate()?;
+    let user = users::get_user(&user_id)?;
+    user.validate_session(token)?;
+
+    if users::find_user_by_login_address(&login_address).is_ok() {
+        return Err(UserError::LoginAddressInUse)?;
+    }
+
+    // prove ownership of the new login address before attaching it
+    match &login_address {
+        LoginAddress::Email { .. } => {
+            if user
+                .logins()
+                .iter()
+                .any(|login| login.login_type() == login_address.login_type())
+            {
+                return Err(UserError::LoginTypeAlreadyLinked.into());
+            }
+            let password = auth_data
+                .and_then(|auth| auth.password)
+                .ok_or(UserError::PasswordRequired)?;
+            let pending_link = PendingEmailLink {
+                login_address,
+                hashed_password: random::hash_password(&password).await?,
+                code: random::generate_token().await?,
+                expires_at: ic_cdk::api::time() + PendingEmailLink::EXPIRATION_SECS * 1_000_000_000,
+            };
+            // linked by `confirm_email_link` once the code sent to the address is confirmed
+            return users::mutate_user(user_id, |user| {
+                user.pending_email_link = Some(pending_link);
+                user.to_owned().redacted()
+            });
+        }
+        LoginAddress::EVM { address } => {
+            let signature = auth_data
+                .and_then(|auth| auth.signature)
+                .ok_or(UserError::SignatureRequired)?;
+            let message = user.evm_auth_message.as_ref().ok_or_else(|| {
+                SystemError::InternalError("evm auth message not in user".to_string())
+            })?;
+            signer::verify_signature(address, message, &signature)?;
+        }
+        LoginAddress::ICP { principal_id } => {
+            if ic_cdk::caller().to_string() != *principal_id {
+                return Err(UserError::UnauthorizedPrincipal)?;
+            }
+        }
+        LoginAddress::Solana { .. } => return Err(UserError::UnauthorizedPrincipal)?,
+    };
+
+    users::mutate_user(user_id, |user| {
+        user.link_login(login_address)?;
+        Ok(user.to_owned().redacted())
+    })?
+}
+
+/// Links the email login awaiting verification, given the code sent to it.
+pub fn confirm_email_link(user_id: u64, token: &str, code: &str) -> Result<User> {
+    let user = users::get_user(&user_id)?;
+    user.validate_session(token)?;
+    let pending_link = user
+        .pending_email_link
+        .ok_or(UserError::EmailLinkNotPending)?;
+    if ic_cdk::api::time() > pending_link.expires_at {
+        return Err(UserError::TokenExpired.into());
+    }
+    if pending_link.code != code {
+        return Err(UserError::InvalidEmailCode.into());
+    }
+    if users::find_user_by_login_address(&pending_link.login_address).is_ok() {
+        return Err(UserError::LoginAddressInUse.into());
+    }
+
+    users::mutate_user(user_id, |user| {
+        user.link_login(pending_link.login_address)?;
+        user.hashed_password = Some(pending_link.hashed_password);
+        user.pending_email_link = None;
+        Ok(user.to_owned().redacted())
+    })?
+}
+
+pub fn unlink_login_address(
+    user_id: u64,
+    token: &str,
+    login_address: &LoginAddress,
+) -> Result<User> {
+    users::mutate_user(user_id, |user| {
+        user.validate_session(token)?;
+        user.unlink_login(login_address)?;
+        Ok(user.to_owned().redacted())
+    })?
+}
+
+/// Starts TOTP enrollment, returning the new secret and its otpauth URI.
+///
+/// The secret stays pending until it is confirmed through [`enable_totp`].
+pub async fn setup_totp(user_id: u64, token: &str) -> Result<(String, String)> {
+    let user = users::get_user(&user_id)?;
+    user.validate_session(token)?;
+    if user.is_totp_enabled() {
+        return Err(UserError::TotpAlreadyEnabled)?;
+    }
+    let email = user
+        .logins()
+        .into_iter()
+        .find_map(|login| match login {
+            LoginAddress::Email { email } => Some(email),
+            _ => None,
+        })
+        .ok_or(UserError::TotpRequiresEmailLogin)?;
+
+    let secret = totp::generate_secret().await?;
+    let uri = totp::otpauth_uri(&secret, &email);
+
+    users::mutate_user(user_id, |user| {
+        user.totp = Some(TotpConfig {
+            secret: secret.clone(),
+            enabled: false,
+            last_used_step: None,
+            recovery_codes: vec![],
+        });
+    })?;
+
+    Ok((secret, uri))
+}
+
+pub async fn enable_totp(user_id: u64, token: &str, code: &str) -> Result<Vec<String>> {
+    let recovery_codes = totp::generate_recovery_codes().await?;
+
+    users::mutate_user(user_id, |user| {
+        user.validate_session(token)?;
+        if user.is_totp_enabled() {
+            return Err(UserError::TotpAlreadyEnabled)?;
+        }
+        user.verify_totp(code, ic_cdk::api::time() / 1_000_000_000)?;
+
+        if let Some(totp) = user.totp.as_mut() {
+            totp.enabled = true;
+            totp.recovery_codes = recovery_codes
+                .iter()
+                .map(|code| totp::hash_recovery_code(code))
+                .collect();
+        }
+        Ok(recovery_codes)
+    })?
+}
+
+pub fn disable_totp(user_id: u64, token: &str, code: &str) -> Result<()> {
+    users::mutate_user(user_id, |user| {
+        user.validate_session(token)?;
+        if !user.is_totp_enabled() {
+            return Err(UserError::TotpNotEnabled)?;
+        }
+        user.verify_totp(code, ic_cdk::api::time() / 1_000_000_000)?;
+
+        user.totp = None;
+        Ok(())
+    })?
+}
+
+pub async fn regenerate_recovery_codes(
+    user_id: u64,
+    token: &str,
+    code: &str,
+) -> Result<Vec<String>> {
+    let recovery_codes = totp::generate_recovery_codes().await?;
+
+    users::mutate_user(user_id, |user| {
+        user.validate_session(token)?;
+        if !user.is_totp_enabled() {
+            return Err(UserError::TotpNotEnabled)?;
+        }
+        user.verify_totp(code, ic_cdk::api::time() / 1_000_000_000)?;
+
+        if let Some(totp) = user.totp.as_mut() {
+            totp.recovery_codes = recovery_codes
+                .iter()
+                .map(|code| totp::hash_recovery_code(code))
+                .collect();
+        }
+        Ok(recovery_codes)
+    })?
+}
+
+/// Requires the TOTP code (or a recovery code) for email logins with TOTP enabled.
+pub fn verify_second_factor(
+    user_id: u64,
+    login_address: &LoginAddress,
+    auth_data: Option<AuthenticationData>,
+) -> Result<()> {
+    let LoginAddress::Email { .. } = login_address else {
+        return Ok(());
+    };
+
+    users::mutate_user(user_id, |user| {
+        if !user.is_totp_enabled() {
+            return Ok(());
+        }
+        let code = auth_data
+            .and_then(|auth| auth.totp_code)
+            .ok_or(UserError::TotpRequired)?;
+        user.verify_totp(&code, ic_cdk::api::time() / 1_000_000_000)
+    })?
+}
+
+pub fn export_user_data(user_id: u64, token: &str) -> Result<UserDataExport> {
+    let user = users::get_user(&user_id)?;
+    user.validate_session(token)?;
+
+    let orders = orders::get_user_orders(&user);
+    let order_ids: HashSet<OrderId> = orders.iter().map(|(order_id, _)| *order_id).collect();
+    Ok(UserDataExport {
+        payment_receipts: payment_ids::get_order_payments(&order_ids),
+        transaction_logs: order_ids
+            .iter()
+            .filter_map(|order_id| logs::get_transaction_log(*order_id))
+            .collect(),
+        orders,
+        user: user.redacted(),
+    })
+}
+
+/// Removes the user record, anonymizing the completed orders that reference it.
+///
+/// Users with created or locked orders must cancel or settle them first, so that
+/// no funds are left attached to a deleted account.
+pub fn delete_user(user_id: u64) -> Result<User> {
+    let user = users::get_user(&user_id)?;
+
+    if orders::get_user_orders(&user)
+        .iter()
+        .any(|(_, order_state)| {
+            matches!(order_state, OrderState::Created(_) | OrderState::Locked(_))
+        })
+    {
+        return Err(UserError::UserHasActiveOrders.into());
+    }
+
+    orders::anonymize_user_orders(&user);
+    for login_address in user.logins() {
+        login_attempts::clear_login_attempts(&login_address);
+    }
+    users::remove_user(&user_id)
+}
+
 pub fn update_user_auth_message(user_id: u64, auth_message: &str) -> Result<()> {
     users::mutate_user(user_id, |user| {
         user.evm_auth_message = Some(auth_message.to_string());
@@ -127,19 +414,235 @@ pub fn update_user_auth_message(user_id: u64, auth_message: &str) -> Result<()>
 pub fn set_session(user_id: u64, session: &Session) -> Result<User> {
     users::mutate_user(user_id, |user| {
         user.session = Some(session.clone());
-        Ok(user.to_owned())
+        Ok(User {
+            session: user.session.clone(),
+            ..user.to_owned().redacted()
+        })
     })?
 }
 
-pub fn update_onramper_payment(user_id: u64, fiat_amount: u64, currency: &str) -> Result<()> {
+pub fn update_onramper_payment(
+    user_id: u64,
+    fiat_amount: u64,
+    currency: &str,
+    payment_secs: u64,
+) -> Result<()> {
     users::mutate_user(user_id, |user| {
         user.update_fiat_amount(fiat_amount, currency);
-        user.increase_score();
+        user.record_onramper_trade(payment_secs, ic_cdk::api::time());
     })
 }
 
 pub fn update_offramper_payment(user_id: u64, fiat_amount: u64, currency: &str) -> Result<()> {
     users::mutate_user(user_id, |user| {
-        user.update_fiat_amount(fiat_amount, currency)
+        user.update_fiat_amount(fiat_amount, currency);
+        user.record_offramper_trade(ic_cdk::api::time());
+    })
+}
+
+pub fn set_kyc_tier(user_id: u64, kyc_tier: KycTier) -> Result<()> {
+    users::mutate_user(user_id, |user| {
+        user.kyc_tier = Some(kyc_tier);
+    })
+}
+
+pub fn reserve_kyc_volume(user_id: u64, amount: u64) -> Result<Option<KycReservation>> {
+    users::mutate_user(user_id, |user| {
+        user.reserve_kyc_volume(amount, ic_cdk::api::time())
+    })?
+}
+
+pub fn release_kyc_volume(user_id: u64, reservation: &Option<KycReservation>) {
+    let Some(reservation) = reservation else {
+        return;
+    };
+    if let Err(e) = users::mutate_user(user_id, |user| user.release_kyc_volume(reservation)) {
+        ic_cdk::println!(
+            "[release_kyc_volume] could not release the volume of user {}: {:?}",
+            user_id,
+            e
+        );
+    }
+}
+
+pub fn freeze_user(user_id: u64, reason: Option<String>) -> Result<()> {
+    users::mutate_user(user_id, |user| {
+        user.frozen = reason;
+    })
+}
+
+pub fn adjust_reputation(user_id: u64, delta: i32) -> Result<User> {
+    users::mutate_user(user_id, |user| {
+        user.adjust_reputation(delta, ic_cdk::api::time());
+        user.to_owned()
+    })
+}
+
+pub fn reward_referrer(referee_id: u64, crypto: &Crypto, amount: u128) -> Result<()> {
+    let Some(referrer_id) = users::get_user(&referee_id)?.referrer_id else {
+        return Ok(());
+    };
+    if amount == 0 {
+        return Ok(());
+    }
+
+    users::mutate_user(referrer_id, |referrer| {
+        referrer.accrue_referral_reward(&crypto.blockchain, &crypto.token, amount)
+    })?;
+    if let Blockchain::EVM { chain_id } = crypto.blockchain {
+        chains::reserve_fees(chain_id, &crypto.token, amount);
+    }
+    Ok(())
+}
+
+/// Pays out the accrued referral rewards of one asset to the user's address on that blockchain.
+///
+/// EVM rewards are withdrawn from the vault fees to the canister address, as in
+/// `withdraw_evm_fees`, and then transferred to the user. Their balance is only debited
+/// once the transfer is confirmed. ICP rewards are transferred from the canister account,
+/// their balance being restored if the transfer fails.
+pub async fn claim_referral_rewards(
+    user_id: u64,
+    token: &str,
+    blockchain: Blockchain,
+    token_address: Option<String>,
+) -> Result<u128> {
+    let user = users::get_user(&user_id)?;
+    user.validate_session(token)?;
+
+    let address_type = match blockchain {
+        Blockchain::EVM { .. } => AddressType::EVM,
+        Blockchain::ICP { .. } => AddressType::ICP,
+        Blockchain::Solana => AddressType::Solana,
+    };
+    let address = user
+        .addresses
+        .iter()
+        .find(|address| address.address_type == address_type)
+        .ok_or(UserError::MissingTransactionAddress(address_type))?
+        .address
+        .clone();
+
+    match &blockchain {
+        Blockchain::EVM { chain_id } => {
+            if let Some(token_address) = &token_address {
+                evm_token::evm_token_is_approved(*chain_id, token_address)?;
+            }
+            if referral::has_pending_claim(user_id, *chain_id, &token_address) {
+                return Err(UserError::ReferralClaimPending.into());
+            }
+            let amount = user.referral_reward(&blockchain, &token_address)?;
+
+            // claims share the id space of orders, which keys the transaction logs
+            let claim_id = heap::generate_order_id();
+            referral::insert_claim(
+                claim_id,
+                ReferralClaim {
+                    user_id,
+                    chain_id: *chain_id,
+                    token: token_address.clone(),
+                    amount,
+                    address,
+                },
+            );
+
+            let canister_address =
+                read_state(|s| s.evm_address.clone()).expect("evm address should be initialized");
+            if let Err(e) = Ic2P2ramp::withdraw_deposit(
+                *chain_id,
+                claim_id,
+                canister_address,
+                token_address,
+                amount,
+                0,
+            )
+            .await
+            {
+                referral::remove_claim(claim_id);
+                return Err(e);
+            }
+            Ok(amount)
+        }
+        Blockchain::ICP { ledger_principal } => {
+            let fee = get_icp_token(ledger_principal)?.fee;
+            let to_account = Account {
+                owner: Principal::from_text(&address)
+                    .map_err(|_| BlockchainError::InvalidAddress)?,
+                subaccount: None,
+            };
+
+            let amount = users::mutate_user(user_id, |user| {
+                user.take_referral_reward(&blockchain, &token_address)
+            })??;
+            let result = if fee >= amount {
+                Err(BlockchainError::FundsBelowFees.into())
+            } else {
+                ICPRamp::transfer(
+                    *ledger_principal,
+                    to_account,
+                    NumTokens::from(amount) - fee.clone(),
+                    Some(fee),
+                )
+                .await
+                .map(|_| ())
+            };
+
+            if let Err(e) = result {
+                users::mutate_user(user_id, |user| {
+                    user.accrue_referral_reward(&blockchain, &token_address, amount)
+                })?;
+                return Err(e);
+            }
+            Ok(amount)
+        }
+        Blockchain::Solana => Err(BlockchainError::UnsupportedBlockchain.into()),
+    }
+}
+
+/// Sends the fees withdrawn for a referral claim from the canister address to the referrer.
+pub async fn transfer_referral_claim(claim_id: u64) -> Result<()> {
+    let claim = referral::get_claim(claim_id)
+        .ok_or_else(|| SystemError::InvalidInput("Referral claim not found".to_string()))?;
+    Ic2P2ramp::transfer(
+        claim.chain_id,
+        claim_id,
+        &claim.address,
+        claim.amount,
+        claim.token,
+        None,
+    )
+    .await
+}
+
+pub fn complete_referral_claim(claim_id: u64) {
+    let Some(claim) = referral::remove_claim(claim_id) else {
+        return;
+    };
+    chains::release_reserved_fees(claim.chain_id, &claim.token, claim.amount);
+    if let Err(e) = users::mutate_user(claim.user_id, |user| {
+        user.debit_referral_reward(&claim.blockchain(), &claim.token, claim.amount)
+    }) {
+        ic_cdk::println!(
+            "[complete_referral_claim] could not debit user {}: {:?}",
+            claim.user_id,
+            e
+        );
+    }
+}
+
+/// Drops a claim that did not go through. Its rewards were never debited.
+pub fn fail_referral_claim(claim_id: u64) {
+    if let Some(claim) = referral::remove_claim(claim_id) {
+        ic_cdk::println!(
+            "[fail_referral_claim] claim {} of user {} failed",
+            claim_id,
+            claim.user_id
+        );
+    }
+}
+
+pub fn record_dispute_lost(user_id: u64) -> Result<()> {
+    users::mutate_user(user_id, |user| {
+        user.record_dispute_lost(ic_cdk::api::time());
     })
 }
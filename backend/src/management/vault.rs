@@ -2,19 +2,25 @@ use evm_rpc_canister_types::TransactionReceipt;
 use num_traits::ToPrimitive;
 
 use crate::{
-    evm::transaction,
-    model::memory,
+    errors::{OrderError, Result, SystemError},
+    evm::{event::VaultTransfer, transaction},
+    model::memory::{
+        self,
+        heap::{logs, read_state},
+    },
     types::{
         evm::{
             gas,
+            logs::TransactionStatus,
             request::SignRequest,
             transaction::{TransactionAction, TransactionVariant},
         },
-        orders::LockInput,
+        orders::{LockInput, OrderState},
+        referral,
     },
 };
 
-use super::on_fail_callback;
+use super::{on_fail_callback, user};
 
 fn register_gas_usage(
     chain_id: u64,
@@ -49,6 +55,57 @@ fn register_gas_usage(
     }
 }
 
+/// Funds the vault transaction of an order moves: its whole deposit.
+fn order_transfer(order_id: u64) -> Result<VaultTransfer> {
+    let order = match memory::stable::orders::get_order(&order_id)? {
+        OrderState::Created(order) => order,
+        OrderState::Locked(order) => order.base,
+        order_state => return Err(OrderError::InvalidOrderState(order_state.to_string()).into()),
+    };
+    Ok(VaultTransfer {
+        offramper: order.offramper_address.address,
+        token: order.crypto.token,
+        amount: order.crypto.amount,
+    })
+}
+
+/// Funds the withdrawal of a referral claim moves: the claimed fees, withdrawn
+/// to the canister address.
+fn claim_transfer(claim_id: u64) -> Result<VaultTransfer> {
+    let claim = referral::get_claim(claim_id)
+        .ok_or_else(|| SystemError::InvalidInput("Referral claim not found".to_string()))?;
+    let canister_address = read_state(|s| s.evm_address.clone())
+        .ok_or_else(|| SystemError::InternalError("EVM address is not set".to_string()))?;
+    Ok(VaultTransfer {
+        offramper: canister_address,
+        token: claim.token,
+        amount: claim.amount,
+    })
+}
+
+/// Fails the order's transaction when the vault did not emit the event of
+/// `action` for the order's funds. Callers decide what happens to the order.
+fn vault_event_emitted(
+    order_id: u64,
+    chain_id: u64,
+    receipt: &TransactionReceipt,
+    action: &TransactionAction,
+) -> bool {
+    let event = order_transfer(order_id)
+        .and_then(|transfer| transaction::check_vault_event(chain_id, receipt, action, &transfer));
+    match event {
+        Ok(event) => {
+            ic_cdk::println!("[vault] order {} emitted {:?}", order_id, event);
+            true
+        }
+        Err(e) => {
+            ic_cdk::println!("[vault] order {}: {:?}", order_id, e);
+            logs::update_transaction_log(order_id, TransactionStatus::Failed(e.to_string()));
+            false
+        }
+    }
+}
+
 pub fn spawn_commit_listener(
     order_id: u64,
     chain_id: u64,
@@ -56,6 +113,8 @@ pub fn spawn_commit_listener(
     sign_request: SignRequest,
     lock_input: LockInput,
 ) {
+    let onramper_user_id = lock_input.onramper_user_id;
+    let onramper_kyc_reservation = lock_input.onramper_kyc_reservation.clone();
     transaction::spawn_transaction_checker(
         0,
         tx_hash.to_string(),
@@ -66,22 +125,17 @@ pub fn spawn_commit_listener(
             register_gas_usage(chain_id, &receipt, &TransactionAction::Commit);
 
             // Lock the order in the storage once the transaction succeeds
-            match memory::stable::orders::lock_order(
-                order_id,
-                lock_input.price,
-                lock_input.offramper_fee,
-                lock_input.onramper_user_id,
-                lock_input.onramper_provider.clone(),
-                lock_input.onramper_address.clone(),
-                lock_input.revolut_consent.clone(),
-            ) {
+            match memory::stable::orders::lock_order(order_id, lock_input.clone()) {
                 Ok(()) => ic_cdk::println!("[commit] order {} is locked.", order_id),
                 Err(err) => {
                     ic_cdk::println!("[commit] order {} failed to be locked: {:?}", order_id, err)
                 }
             };
         },
-        on_fail_callback(order_id),
+        move |_| {
+            user::release_kyc_volume(onramper_user_id, &onramper_kyc_reservation);
+            on_fail_callback(order_id)();
+        },
     );
 }
 
@@ -110,7 +164,7 @@ pub fn spawn_uncommit_listener(
                 ),
             };
         },
-        on_fail_callback(order_id),
+        move |_| on_fail_callback(order_id)(),
     );
 }
 
@@ -128,11 +182,12 @@ pub fn spawn_cancel_listener(
         order_id,
         sign_request,
         move |receipt| {
-            register_gas_usage(
-                chain_id,
-                &receipt,
-                &TransactionAction::Cancel(cancel_variant.clone()),
-            );
+            let action = TransactionAction::Cancel(cancel_variant.clone());
+            register_gas_usage(chain_id, &receipt, &action);
+            if !vault_event_emitted(order_id, chain_id, &receipt, &action) {
+                on_fail_callback(order_id)();
+                return;
+            }
 
             // Cancel the order in the backend once the transaction succeeds
             match memory::stable::orders::cancel_order(order_id) {
@@ -144,7 +199,7 @@ pub fn spawn_cancel_listener(
                 ),
             }
         },
-        on_fail_callback(order_id),
+        move |_| on_fail_callback(order_id)(),
     );
 }
 
@@ -162,11 +217,16 @@ pub fn spawn_release_listener(
         order_id,
         sign_request,
         move |receipt| {
-            register_gas_usage(
-                chain_id,
-                &receipt,
-                &TransactionAction::Release(release_variant.clone()),
-            );
+            let action = TransactionAction::Release(release_variant.clone());
+            register_gas_usage(chain_id, &receipt, &action);
+            if !vault_event_emitted(order_id, chain_id, &receipt, &action) {
+                // The release went through, so the order must not become refundable
+                super::order::record_unconfirmed_release(
+                    order_id,
+                    "Release emitted no vault event".to_string(),
+                );
+                return;
+            }
 
             // Update order state to completed
             match super::order::set_order_completed(order_id) {
@@ -178,6 +238,68 @@ pub fn spawn_release_listener(
                 ),
             }
         },
-        super::on_fail_callback(order_id),
+        move |reverted| {
+            if reverted {
+                super::order::record_release_failure(
+                    order_id,
+                    "Release transaction reverted".to_string(),
+                );
+            }
+            on_fail_callback(order_id)();
+        },
+    );
+}
+
+/// Listens for the withdrawal of a referral claim from the vault fees, then
+/// transfers the withdrawn fees to the referrer.
+pub fn spawn_claim_withdraw_listener(
+    claim_id: u64,
+    chain_id: u64,
+    cancel_variant: TransactionVariant,
+    tx_hash: &str,
+    sign_request: SignRequest,
+) {
+    transaction::spawn_transaction_checker(
+        0,
+        tx_hash.to_string(),
+        chain_id,
+        claim_id,
+        sign_request,
+        move |receipt| {
+            let action = TransactionAction::Cancel(cancel_variant.clone());
+            register_gas_usage(chain_id, &receipt, &action);
+            if let Err(e) = claim_transfer(claim_id).and_then(|transfer| {
+                transaction::check_vault_event(chain_id, &receipt, &action, &transfer)
+            }) {
+                logs::update_transaction_log(claim_id, TransactionStatus::Failed(e.to_string()));
+                user::fail_referral_claim(claim_id);
+                return;
+            }
+
+            ic_cdk::spawn(async move {
+                if let Err(e) = user::transfer_referral_claim(claim_id).await {
+                    ic_cdk::println!("[claim] transfer of claim {} failed: {:?}", claim_id, e);
+                    user::fail_referral_claim(claim_id);
+                }
+            });
+        },
+        move |_| user::fail_referral_claim(claim_id),
+    );
+}
+
+pub fn spawn_claim_transfer_listener(
+    claim_id: u64,
+    chain_id: u64,
+    tx_hash: &str,
+    sign_request: SignRequest,
+) {
+    transaction::spawn_transaction_checker(
+        0,
+        tx_hash.to_string(),
+        chain_id,
+        claim_id,
+        sign_request,
+        move |_| user::complete_referral_claim(claim_id),
+        move |_| user::fail_referral_claim(claim_id),
     );
 }
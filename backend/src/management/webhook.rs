@@ -0,0 +1,135 @@
+use crate::{
+    errors::{OrderError, RampError, Result, SystemError},
+    management::{order as order_management, payment as payment_management},
+    model::memory::{
+        heap::{read_state, try_start_webhook_verification},
+        stable::orders,
+    },
+    outcalls::paypal::{
+        auth::get_paypal_access_token,
+        webhook::{self, TransmissionHeaders, WebhookEvent, PAYMENT_CAPTURE_COMPLETED},
+    },
+    types::{
+        gateway::{GatewayRequest, GatewayResponse},
+        PaymentProviderType,
+    },
+};
+
+/// Handles a PayPal webhook delivery.
+///
+/// Completed captures are matched to the locked order carrying their payment
+/// reference and settled through the regular verification path. Any other
+/// event, or a capture not related to an order, is acknowledged and ignored.
+/// Failures that may be transient answer with a 5xx so that PayPal retries.
+///
+/// The endpoint is public, so every check that needs no outcall runs first and
+/// the signature of deliveries for a same order is verified at most once per
+/// interval.
+pub async fn handle_paypal_webhook(request: &GatewayRequest) -> GatewayResponse {
+    let Ok(raw_event) = std::str::from_utf8(&request.body) else {
+        return GatewayResponse::new(400, "Body is not UTF-8 encoded");
+    };
+    let event: WebhookEvent = match serde_json::from_str(raw_event) {
+        Ok(event) => event,
+        Err(e) => return GatewayResponse::new(400, &format!("Invalid event: {}", e)),
+    };
+    ic_cdk::println!(
+        "[handle_paypal_webhook] event {} of type {}",
+        event.id,
+        event.event_type
+    );
+
+    if event.event_type != PAYMENT_CAPTURE_COMPLETED {
+        return GatewayResponse::new(200, "Event ignored");
+    }
+    let Some(order_id) = event
+        .resource
+        .reference()
+        .and_then(orders::find_locked_order_by_reference)
+    else {
+        return GatewayResponse::new(200, "No locked order matches the capture");
+    };
+    let Some(transaction_id) = event.resource.order_id() else {
+        return GatewayResponse::new(400, "Capture is missing its PayPal order id");
+    };
+    let Some(headers) = transmission_headers(request) else {
+        return GatewayResponse::new(400, "Missing PayPal transmission headers");
+    };
+    match order_management::get_payable_order(order_id) {
+        Ok(order) if order.onramper.provider.provider_type() == PaymentProviderType::PayPal => {}
+        Ok(_) => return GatewayResponse::new(200, "Order is not paid with PayPal"),
+        Err(RampError::OrderError(OrderError::PaymentDone)) => {
+            return GatewayResponse::new(200, "Payment already processed")
+        }
+        Err(_) => return GatewayResponse::new(200, "Order is not awaiting a payment"),
+    }
+    if !try_start_webhook_verification(order_id, ic_cdk::api::time()) {
+        return GatewayResponse::new(429, "Order was notified too recently");
+    }
+
+    match verify_delivery(&headers, raw_event).await {
+        Ok(true) => {}
+        Ok(false) => return GatewayResponse::new(401, "Invalid webhook signature"),
+        Err(e) => return GatewayResponse::new(500, &e.to_string()),
+    }
+
+    match settle_order(order_id, transaction_id).await {
+        Ok(()) => GatewayResponse::new(200, "Payment processed"),
+        Err(RampError::OrderError(OrderError::PaymentDone)) => {
+            GatewayResponse::new(200, "Payment already processed")
+        }
+        Err(RampError::OrderError(OrderError::PaymentUnderpaid(_))) => {
+            GatewayResponse::new(200, "Payment recorded, order awaits a top-up")
+        }
+        Err(e) => {
+            ic_cdk::println!(
+                "[handle_paypal_webhook] capture {} for order {} failed: {}",
+                event.resource.id,
+                order_id,
+                e
+            );
+            GatewayResponse::new(500, &e.to_string())
+        }
+    }
+}
+
+fn transmission_headers(request: &GatewayRequest) -> Option<TransmissionHeaders> {
+    let header = |name: &str| request.header(name).map(str::to_string);
+    Some(TransmissionHeaders {
+        auth_algo: header("paypal-auth-algo")?,
+        cert_url: header("paypal-cert-url")?,
+        transmission_id: header("paypal-transmission-id")?,
+        transmission_sig: header("paypal-transmission-sig")?,
+        transmission_time: header("paypal-transmission-time")?,
+    })
+}
+
+async fn verify_delivery(headers: &TransmissionHeaders, raw_event: &str) -> Result<bool> {
+    let webhook_id = read_state(|s| s.paypal.webhook_id.clone()).ok_or_else(|| {
+        SystemError::InternalError("PayPal webhook id is not configured".to_string())
+    })?;
+
+    let access_token = get_paypal_access_token().await?;
+    webhook::verify_webhook_signature(&access_token, &webhook_id, headers, raw_event).await
+}
+
+async fn settle_order(order_id: u64, transaction_id: &str) -> Result<()> {
+    orders::set_processing_order(&order_id)?;
+
+    if let Err(e) = process_capture(order_id, transaction_id).await {
+        orders::unset_processing_order(&order_id)?;
+        return Err(e);
+    }
+
+    Ok(())
+}
+
+async fn process_capture(order_id: u64, transaction_id: &str) -> Result<()> {
+    // The verified signature authenticates the payment report in place of a session
+    let order = order_management::get_payable_order(order_id)?;
+    if order.onramper.provider.provider_type() != PaymentProviderType::PayPal {
+        return Err(OrderError::InvalidOnramperProvider.into());
+    }
+
+    payment_management::settle_payment(&order, transaction_id).await
+}
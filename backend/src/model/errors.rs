@@ -4,7 +4,10 @@ use candid::CandidType;
 use ic_cdk::api::call::RejectionCode;
 use thiserror::Error;
 
-use crate::{outcalls::xrc_rates::ExchangeRateError, types::PaymentProviderType};
+use crate::{
+    outcalls::xrc_rates::ExchangeRateError,
+    types::{AddressType, PaymentProviderType},
+};
 
 pub type Result<T> = std::result::Result<T, RampError>;
 
@@ -67,8 +70,68 @@ pub enum UserError {
     #[error("User score below zero")]
     UserBanned,
 
+    #[error("User is frozen: {0}")]
+    UserFrozen(String),
+
     #[error("Provider is Not Defined for User {:?}", .0)]
     ProviderNotInUser(PaymentProviderType),
+
+    #[error("Login address is already in use")]
+    LoginAddressInUse,
+
+    #[error("Login address is not linked to the user")]
+    LoginAddressNotLinked,
+
+    #[error("A login address of this type is already linked")]
+    LoginTypeAlreadyLinked,
+
+    #[error("Primary login address cannot be unlinked")]
+    CannotUnlinkPrimaryLogin,
+
+    #[error("No email login is awaiting verification")]
+    EmailLinkNotPending,
+
+    #[error("Email verification code is Invalid")]
+    InvalidEmailCode,
+
+    #[error("Too many failed login attempts, retry in {0} seconds")]
+    LoginLocked(u64),
+
+    #[error("TOTP code is Required")]
+    TotpRequired,
+
+    #[error("TOTP code is Invalid")]
+    InvalidTotpCode,
+
+    #[error("TOTP is not enabled")]
+    TotpNotEnabled,
+
+    #[error("TOTP is already enabled")]
+    TotpAlreadyEnabled,
+
+    #[error("TOTP is only available for email accounts")]
+    TotpRequiresEmailLogin,
+
+    #[error("No referral rewards to claim")]
+    NoReferralRewards,
+
+    #[error("A claim of these referral rewards is already in progress")]
+    ReferralClaimPending,
+
+    #[error("User has no {0:?} address to receive funds")]
+    MissingTransactionAddress(AddressType),
+
+    #[error("User has orders still in progress")]
+    UserHasActiveOrders,
+
+    #[error("Only controller or KYC attestor is allowed")]
+    OnlyKycAttestor,
+
+    #[error("Order exceeds the KYC tier limit of {0} cents")]
+    KycOrderLimitExceeded(u64),
+
+    #[error("Order exceeds the KYC tier volume limit of {0} cents for the last 30 days")]
+    KycVolumeLimitExceeded(u64),
 }
 
 #[derive(Error, Debug, CandidType, Clone)]
@@ -111,6 +174,51 @@ pub enum OrderError {
 
     #[error("Payment Verification Failed")]
     PaymentVerificationFailed,
+
+    #[error("Provider {0:?} does not support currency {1}")]
+    UnsupportedCurrency(PaymentProviderType, String),
+
+    #[error("Payment must be confirmed by the offramper")]
+    OfframperConfirmationRequired,
+
+    #[error("Bank transfer was already marked as sent")]
+    BankTransferAlreadyClaimed,
+
+    #[error("Bank transfer was not marked as sent")]
+    BankTransferNotClaimed,
+
+    #[error("Order is not in dispute")]
+    OrderNotDisputed,
+
+    #[error("Payment ID was already used for another order")]
+    PaymentIdAlreadyUsed,
+
+    #[error("Payment is pending settlement")]
+    PaymentPending,
+
+    #[error("Provider {0:?} does not support refunds")]
+    RefundNotSupported(PaymentProviderType),
+
+    #[error("Order has a refund in progress")]
+    RefundInProgress,
+
+    #[error("Order has no refund awaiting the offramper")]
+    RefundNotAwaitingOfframper,
+
+    #[error("Revolut consent can no longer authorise a payment")]
+    RevolutConsentUnusable,
+
+    #[error("Payment is {0} cents short of the order total, a top-up is required")]
+    PaymentUnderpaid(u64),
+
+    #[error("Order has not received a partial payment")]
+    NoPartialPayment,
+
+    #[error("Payments received do not cover enough of the order to partially fill it")]
+    PartialFillNotAllowed,
+
+    #[error("Order has no overpayment to refund")]
+    NoOverpayment,
 }
 
 #[derive(Error, Debug, CandidType, Clone)]
@@ -160,6 +268,9 @@ pub enum BlockchainError {
     #[error("Funds are too low")]
     FundsTooLow,
 
+    #[error("Only {0} of the fees can be withdrawn, the rest is owed to referrers")]
+    FeesReserved(u128),
+
     #[error("Ledger principal {0} not supported")]
     LedgerPrincipalNotSupported(String),
 
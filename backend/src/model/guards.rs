@@ -1,4 +1,5 @@
 use super::errors::{Result, UserError};
+use super::memory::heap::read_state;
 
 pub fn only_controller() -> Result<()> {
     if ic_cdk::api::is_controller(&ic_cdk::caller()) {
@@ -7,3 +8,18 @@ pub fn only_controller() -> Result<()> {
         Err(UserError::OnlyController.into())
     }
 }
+
+pub fn only_kyc_attestor() -> Result<()> {
+    let caller = ic_cdk::caller();
+    if ic_cdk::api::is_controller(&caller)
+        || read_state(|s| {
+            s.kyc_attestors
+                .as_ref()
+                .is_some_and(|attestors| attestors.contains(&caller))
+        })
+    {
+        Ok(())
+    } else {
+        Err(UserError::OnlyKycAttestor.into())
+    }
+}
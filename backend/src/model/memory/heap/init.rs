@@ -8,7 +8,9 @@ use ic_cdk::api::management_canister::ecdsa::EcdsaKeyId;
 use super::state::{InvalidStateError, State};
 use crate::model::types::{
     evm::chains::ChainState,
-    payment::{paypal::PayPalState, revolut::RevolutState},
+    payment::{
+        client_credentials::ClientCredentialsState, paypal::PayPalState, revolut::RevolutState,
+    },
 };
 
 #[derive(CandidType, Deserialize, Debug, Clone)]
@@ -24,6 +26,46 @@ pub struct PaypalConfig {
     pub client_id: String,
     pub client_secret: String,
     pub api_url: String,
+    pub webhook_id: Option<String>,
+}
+
+#[derive(CandidType, Deserialize, Debug, Clone)]
+pub struct WiseConfig {
+    pub client_id: String,
+    pub client_secret: String,
+    pub api_url: String,
+}
+
+impl From<WiseConfig> for ClientCredentialsState {
+    fn from(config: WiseConfig) -> Self {
+        ClientCredentialsState::new(config.client_id, config.client_secret, config.api_url)
+    }
+}
+
+#[derive(CandidType, Deserialize, Debug, Clone)]
+pub struct PixConfig {
+    pub client_id: String,
+    pub client_secret: String,
+    pub api_url: String, // PSP implementing the BCB PIX API
+}
+
+impl From<PixConfig> for ClientCredentialsState {
+    fn from(config: PixConfig) -> Self {
+        ClientCredentialsState::new(config.client_id, config.client_secret, config.api_url)
+    }
+}
+
+#[derive(CandidType, Deserialize, Debug, Clone)]
+pub struct UpiConfig {
+    pub client_id: String,
+    pub client_secret: String,
+    pub api_url: String, // UPI payment gateway
+}
+
+impl From<UpiConfig> for ClientCredentialsState {
+    fn from(config: UpiConfig) -> Self {
+        ClientCredentialsState::new(config.client_id, config.client_secret, config.api_url)
+    }
 }
 
 #[derive(CandidType, Deserialize, Clone)]
@@ -55,6 +97,9 @@ pub struct InitArg {
     pub ecdsa_key_id: EcdsaKeyId,
     pub paypal: PaypalConfig,
     pub revolut: RevolutConfig,
+    pub wise: Option<WiseConfig>,
+    pub pix: Option<PixConfig>,
+    pub upi: Option<UpiConfig>,
     pub proxy_url: String,
 }
 
@@ -67,6 +112,9 @@ impl TryFrom<InitArg> for State {
             ecdsa_key_id,
             paypal,
             revolut,
+            wise,
+            pix,
+            upi,
             proxy_url,
         }: InitArg,
     ) -> Result<Self, Self::Error> {
@@ -97,6 +145,7 @@ impl TryFrom<InitArg> for State {
                 client_id: paypal.client_id,
                 client_secret: paypal.client_secret,
                 api_url: paypal.api_url,
+                webhook_id: paypal.webhook_id,
             },
             revolut: RevolutState {
                 access_token: None,
@@ -108,8 +157,18 @@ impl TryFrom<InitArg> for State {
                 kid: revolut.kid,
                 tan: revolut.tan,
             },
+            wise: wise.map(ClientCredentialsState::from),
+            pix: pix.map(ClientCredentialsState::from),
+            upi: upi.map(ClientCredentialsState::from),
             proxy_url,
             icp_tokens: HashMap::new(),
+            kyc_attestors: None,
+            referral_fee_bps: None,
+            fx_tolerance_bps: None,
+            payment_tolerance: None,
+            referral_claims: None,
+            #[cfg(feature = "mock-provider")]
+            mock_payments: None,
         };
         Ok(state)
     }
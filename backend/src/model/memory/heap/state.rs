@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use candid::{CandidType, Deserialize, Principal};
 use ic_cdk::api::management_canister::ecdsa::EcdsaKeyId;
@@ -6,9 +6,16 @@ use ic_cdk::api::management_canister::ecdsa::EcdsaKeyId;
 use crate::model::types::{
     evm::chains::ChainState,
     icp::IcpToken,
-    payment::{paypal::PayPalState, revolut::RevolutState},
+    orders::PaymentTolerance,
+    payment::{
+        client_credentials::ClientCredentialsState, paypal::PayPalState, revolut::RevolutState,
+    },
+    referral::ReferralClaim,
 };
 
+#[cfg(feature = "mock-provider")]
+use crate::model::types::payment::mock::MockPayment;
+
 use super::storage::STATE;
 
 #[derive(Clone, Debug, CandidType, Deserialize)]
@@ -19,8 +26,18 @@ pub struct State {
     pub evm_address: Option<String>,
     pub paypal: PayPalState,
     pub revolut: RevolutState,
+    pub wise: Option<ClientCredentialsState>,
+    pub pix: Option<ClientCredentialsState>,
+    pub upi: Option<ClientCredentialsState>,
     pub proxy_url: String,
     pub icp_tokens: HashMap<Principal, IcpToken>,
+    pub kyc_attestors: Option<HashSet<Principal>>,
+    pub referral_fee_bps: Option<u16>, // share of the admin crypto fee
+    pub fx_tolerance_bps: Option<u16>, // accepted deviation of converted payments
+    pub payment_tolerance: Option<PaymentTolerance>,
+    pub referral_claims: Option<HashMap<u64, ReferralClaim>>, // by the id of their transactions
+    #[cfg(feature = "mock-provider")]
+    pub mock_payments: Option<HashMap<String, MockPayment>>, // by their transaction id
 }
 
 #[derive(Debug, Eq, PartialEq)]
@@ -1,4 +1,4 @@
-use ic_cdk_timers::{clear_timer, set_timer, TimerId};
+use ic_cdk_timers::{clear_timer, set_timer, set_timer_interval, TimerId};
 use std::{cell::RefCell, collections::HashMap, time::Duration};
 
 use super::State;
@@ -9,6 +9,13 @@ use crate::{
 };
 
 pub(crate) const LOCK_DURATION_TIME_SECONDS: u64 = 1800; // 30 min
+pub(crate) const BANK_TRANSFER_CONFIRMATION_SECONDS: u64 = 2 * 24 * 3600; // 48 hours
+pub(crate) const PAYMENT_POLL_INITIAL_SECONDS: u64 = 30;
+pub(crate) const PAYMENT_POLL_MAX_SECONDS: u64 = 300; // 5 min
+pub(crate) const REVOLUT_CONSENT_POLL_SECONDS: u64 = 60;
+pub(crate) const DEPOSIT_SCAN_INTERVAL_SECONDS: u64 = 60;
+pub(crate) const WEBHOOK_VERIFICATION_INTERVAL_SECONDS: u64 = 60;
+const DEPOSIT_SCAN_TIMEOUT_SECONDS: u64 = 600; // 10 min
 
 thread_local! {
     pub(crate) static STATE: RefCell<Option<State>> = RefCell::default();
@@ -16,6 +23,10 @@ thread_local! {
     static USER_ID_COUNTER: RefCell<u64> = const { RefCell::new(0) };
     static ORDER_ID_COUNTER: RefCell<u64> = const { RefCell::new(0) };
     static LOCKED_ORDER_TIMERS: RefCell<HashMap<u64, TimerId>> = RefCell::default();
+    static PAYMENT_POLL_TIMERS: RefCell<HashMap<u64, TimerId>> = RefCell::default();
+    static CONSENT_POLL_TIMERS: RefCell<HashMap<u64, TimerId>> = RefCell::default();
+    static DEPOSIT_SCAN_STARTED_AT: RefCell<Option<u64>> = const { RefCell::new(None) };
+    static WEBHOOK_VERIFICATIONS: RefCell<HashMap<u64, u64>> = RefCell::default();
 
     pub(super) static EVM_TRANSACTION_LOGS: RefCell<HashMap<u64, EvmTransactionLog>> = RefCell::new(HashMap::new());
     pub(super) static TRANSACTION_LOG_TIMERS: RefCell<HashMap<u64, TimerId>> = RefCell::new(HashMap::new());
@@ -56,6 +67,118 @@ pub fn set_order_timer(order_id: u64) {
     });
 }
 
+/// Replaces the lock timer of an order whose bank transfer was claimed: if the
+/// offramper has not confirmed it by `confirm_by` (ns), it escalates to a dispute.
+pub fn set_bank_transfer_timer(order_id: u64, confirm_by: u64) {
+    let delay = confirm_by.saturating_sub(ic_cdk::api::time());
+    let timer_id = set_timer(Duration::from_nanos(delay), move || {
+        if let Err(e) = management::order::escalate_bank_transfer(order_id) {
+            ic_cdk::println!("Failed to escalate order {}: {:?}", order_id, e);
+        }
+    });
+
+    LOCKED_ORDER_TIMERS.with_borrow_mut(|timer| {
+        if let Some(previous) = timer.insert(order_id, timer_id) {
+            clear_timer(previous);
+        }
+    });
+}
+
+/// Schedules the next status check of an order's pending payment, replacing
+/// any check already scheduled. `interval` (s) doubles on every check that
+/// finds the payment still pending or fails to check it.
+pub fn set_payment_poll_timer(order_id: u64, interval: u64) {
+    let timer_id = set_timer(Duration::from_secs(interval), move || {
+        PAYMENT_POLL_TIMERS.with_borrow_mut(|timers| timers.remove(&order_id));
+        ic_cdk::spawn(async move {
+            let next_interval = (interval * 2).min(PAYMENT_POLL_MAX_SECONDS);
+            match management::payment::poll_payment(order_id).await {
+                Ok(true) => set_payment_poll_timer(order_id, next_interval),
+                Ok(false) => {}
+                Err(e) => {
+                    ic_cdk::println!("Failed to poll payment of order {}: {:?}", order_id, e);
+                    // Polling stops once the order no longer awaits settlement
+                    set_payment_poll_timer(order_id, next_interval);
+                }
+            }
+        });
+    });
+
+    PAYMENT_POLL_TIMERS.with_borrow_mut(|timers| {
+        if let Some(previous) = timers.insert(order_id, timer_id) {
+            clear_timer(previous);
+        }
+    });
+}
+
+/// Schedules the next status check of a locked order's Revolut consent,
+/// rescheduled for as long as the consent can still change.
+pub fn set_consent_poll_timer(order_id: u64) {
+    let timer_id = set_timer(
+        Duration::from_secs(REVOLUT_CONSENT_POLL_SECONDS),
+        move || {
+            CONSENT_POLL_TIMERS.with_borrow_mut(|timers| timers.remove(&order_id));
+            ic_cdk::spawn(async move {
+                match management::consent::refresh_consent_status(order_id).await {
+                    Ok(true) => set_consent_poll_timer(order_id),
+                    Ok(false) => {}
+                    Err(e) => {
+                        ic_cdk::println!("Failed to poll consent of order {}: {:?}", order_id, e);
+                        set_consent_poll_timer(order_id);
+                    }
+                }
+            });
+        },
+    );
+
+    CONSENT_POLL_TIMERS.with_borrow_mut(|timers| {
+        if let Some(previous) = timers.insert(order_id, timer_id) {
+            clear_timer(previous);
+        }
+    });
+}
+
+/// Scans the vault logs for awaited deposits every interval, skipping the
+/// interval while the previous scan is still running.
+pub fn set_deposit_scan_timer() {
+    set_timer_interval(Duration::from_secs(DEPOSIT_SCAN_INTERVAL_SECONDS), || {
+        let now = ic_cdk::api::time();
+        // A scan that trapped midway never clears its start time
+        let scanning = DEPOSIT_SCAN_STARTED_AT.with_borrow(|started_at| {
+            started_at.is_some_and(|started_at| {
+                now.saturating_sub(started_at) < DEPOSIT_SCAN_TIMEOUT_SECONDS * 1_000_000_000
+            })
+        });
+        if scanning {
+            return;
+        }
+
+        DEPOSIT_SCAN_STARTED_AT.set(Some(now));
+        ic_cdk::spawn(async {
+            management::deposit::scan_deposits().await;
+            DEPOSIT_SCAN_STARTED_AT.set(None);
+        });
+    });
+}
+
+/// Records a webhook signature verification for an order at `now` (ns), unless
+/// one already ran within the last interval. Bounds the outcalls a delivery
+/// naming a guessed reference can trigger.
+pub fn try_start_webhook_verification(order_id: u64, now: u64) -> bool {
+    WEBHOOK_VERIFICATIONS.with_borrow_mut(|verifications| {
+        let interval = WEBHOOK_VERIFICATION_INTERVAL_SECONDS * 1_000_000_000;
+        if verifications
+            .get(&order_id)
+            .is_some_and(|last| now.saturating_sub(*last) < interval)
+        {
+            return false;
+        }
+        verifications.retain(|_, last| now.saturating_sub(*last) < interval);
+        verifications.insert(order_id, now);
+        true
+    })
+}
+
 pub fn clear_order_timer(order_id: u64) -> Result<()> {
     LOCKED_ORDER_TIMERS.with_borrow_mut(|timer| match timer.remove(&order_id) {
         Some(timer_id) => {
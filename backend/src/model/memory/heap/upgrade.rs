@@ -20,9 +20,10 @@ use crate::{
 use super::{
     clear_order_timer, get_exchange_rate_cache, get_locked_order_timers, get_order_id_counter,
     get_state, get_user_id_counter,
-    init::{ChainConfig, PaypalConfig, RevolutConfig},
-    initialize_state, set_exchange_rate_cache, set_order_id_counter, set_order_timer,
-    set_user_id_counter, State, LOCK_DURATION_TIME_SECONDS,
+    init::{ChainConfig, PaypalConfig, PixConfig, RevolutConfig, UpiConfig, WiseConfig},
+    initialize_state, set_bank_transfer_timer, set_consent_poll_timer, set_deposit_scan_timer,
+    set_exchange_rate_cache, set_order_id_counter, set_order_timer, set_payment_poll_timer,
+    set_user_id_counter, State, LOCK_DURATION_TIME_SECONDS, PAYMENT_POLL_INITIAL_SECONDS,
 };
 
 const MAX_HEAP_SIZE: u32 = 128 * 1024; // 128KB
@@ -33,6 +34,9 @@ pub struct UpdateArg {
     pub ecdsa_key_id: Option<EcdsaKeyId>, // Optional ECDSA key update
     pub paypal: Option<PaypalConfig>,     // Optional PayPal configuration update
     pub revolut: Option<RevolutConfig>,   // Optional Revolut configuration update
+    pub wise: Option<WiseConfig>,         // Optional Wise configuration update
+    pub pix: Option<PixConfig>,           // Optional PIX PSP configuration update
+    pub upi: Option<UpiConfig>,           // Optional UPI gateway configuration update
     pub proxy_url: Option<String>,        // Optional proxy URL update
 }
 
@@ -87,6 +91,16 @@ impl SerializableHeap {
 
     pub fn set_locked_order_timers(self) {
         for (order_id, unlock_timestamp) in self.locked_order_timers {
+            if let Some(confirm_by) = management::order::bank_transfer_deadline(order_id) {
+                set_bank_transfer_timer(order_id, confirm_by);
+                continue;
+            }
+            if management::consent::has_open_consent(order_id) {
+                set_consent_poll_timer(order_id);
+            }
+            if management::payment::awaiting_settlement(order_id).is_some() {
+                set_payment_poll_timer(order_id, PAYMENT_POLL_INITIAL_SECONDS);
+            }
             if ic_cdk::api::time() < unlock_timestamp {
                 set_order_timer(order_id);
             } else {
@@ -122,6 +136,7 @@ pub fn post_upgrade(update_arg: Option<UpdateArg>) {
             set_user_id_counter(serializable_heap.user_id_counter);
             set_order_id_counter(serializable_heap.order_id_counter);
             serializable_heap.clone().set_locked_order_timers();
+            set_deposit_scan_timer();
             set_exchange_rate_cache(serializable_heap.exchange_rate_cache);
 
             let mut state: State = serializable_heap.state.clone();
@@ -169,6 +184,7 @@ fn update_state(update_arg: UpdateArg, state: &mut State) {
             client_id: paypal_config.client_id,
             client_secret: paypal_config.client_secret,
             api_url: paypal_config.api_url,
+            webhook_id: paypal_config.webhook_id,
         };
     }
 
@@ -185,6 +201,18 @@ fn update_state(update_arg: UpdateArg, state: &mut State) {
         };
     }
 
+    if let Some(wise_config) = update_arg.wise {
+        state.wise = Some(wise_config.into()); // Resets the access token
+    }
+
+    if let Some(pix_config) = update_arg.pix {
+        state.pix = Some(pix_config.into());
+    }
+
+    if let Some(upi_config) = update_arg.upi {
+        state.upi = Some(upi_config.into());
+    }
+
     if let Some(proxy_url) = update_arg.proxy_url {
         state.proxy_url = proxy_url;
     }
@@ -0,0 +1,24 @@
+use crate::types::audit::{AdminAction, AuditEntry};
+
+use super::storage::AUDIT_LOG;
+
+pub fn record_admin_action(user_id: u64, action: AdminAction) {
+    let entry = AuditEntry::new(user_id, action);
+    ic_cdk::println!("[audit_log] {:?}", entry);
+
+    AUDIT_LOG.with_borrow_mut(|log| {
+        let next_index = log
+            .range((user_id, 0)..=(user_id, u64::MAX))
+            .last()
+            .map_or(0, |((_, index), _)| index + 1);
+        log.insert((user_id, next_index), entry);
+    });
+}
+
+pub fn get_user_audit_log(user_id: u64) -> Vec<AuditEntry> {
+    AUDIT_LOG.with_borrow(|log| {
+        log.range((user_id, 0)..=(user_id, u64::MAX))
+            .map(|(_, entry)| entry)
+            .collect()
+    })
+}
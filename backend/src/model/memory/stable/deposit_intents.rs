@@ -0,0 +1,72 @@
+use super::storage::DEPOSIT_INTENTS;
+use crate::{
+    errors::{BlockchainError, Result},
+    types::evm::deposit::DepositIntent,
+};
+
+/// Registers an intent, failing if an identical deposit is already awaited.
+pub fn insert_intent(intent: DepositIntent) -> Result<()> {
+    let key = intent.intent_key();
+    DEPOSIT_INTENTS.with_borrow_mut(|intents| {
+        if intents
+            .get(&key)
+            .is_some_and(|existing| !existing.is_expired(ic_cdk::api::time()))
+        {
+            return Err(BlockchainError::EvmLogError(
+                "An identical deposit is already awaited".to_string(),
+            )
+            .into());
+        }
+        intents.insert(key, intent);
+        Ok(())
+    })
+}
+
+/// Removes and returns the unexpired intent matching a deposit.
+pub fn take_intent(key: &str) -> Option<DepositIntent> {
+    DEPOSIT_INTENTS
+        .with_borrow_mut(|intents| intents.remove(&key.to_string()))
+        .filter(|intent| !intent.is_expired(ic_cdk::api::time()))
+}
+
+pub fn get_user_intents(user_id: u64) -> Vec<DepositIntent> {
+    DEPOSIT_INTENTS.with_borrow(|intents| {
+        intents
+            .iter()
+            .filter(|(_, intent)| intent.offramper_user_id == user_id)
+            .map(|(_, intent)| intent)
+            .collect()
+    })
+}
+
+pub fn remove_user_intent(user_id: u64, key: &str) -> Option<DepositIntent> {
+    DEPOSIT_INTENTS.with_borrow_mut(|intents| {
+        let key = key.to_string();
+        match intents.get(&key) {
+            Some(intent) if intent.offramper_user_id == user_id => intents.remove(&key),
+            _ => None,
+        }
+    })
+}
+
+pub fn has_intents(chain_id: u64) -> bool {
+    DEPOSIT_INTENTS.with_borrow(|intents| {
+        intents
+            .iter()
+            .any(|(_, intent)| intent.chain_id == chain_id)
+    })
+}
+
+pub fn discard_expired_intents() {
+    let now = ic_cdk::api::time();
+    DEPOSIT_INTENTS.with_borrow_mut(|intents| {
+        let expired: Vec<String> = intents
+            .iter()
+            .filter(|(_, intent)| intent.is_expired(now))
+            .map(|(key, _)| key)
+            .collect();
+        for key in expired {
+            intents.remove(&key);
+        }
+    });
+}
@@ -0,0 +1,26 @@
+use crate::errors::Result;
+use crate::types::LoginAddress;
+
+use super::storage::LOGIN_ATTEMPTS;
+
+pub fn check_login_allowed(login_address: &LoginAddress) -> Result<()> {
+    LOGIN_ATTEMPTS.with_borrow(|attempts| match attempts.get(&login_address.to_key()) {
+        Some(login_attempts) => login_attempts.check_locked(ic_cdk::api::time()),
+        None => Ok(()),
+    })
+}
+
+pub fn record_failed_login(login_address: &LoginAddress) {
+    let key = login_address.to_key();
+    LOGIN_ATTEMPTS.with_borrow_mut(|attempts| {
+        let mut login_attempts = attempts.get(&key).unwrap_or_default();
+        login_attempts.record_failure(ic_cdk::api::time());
+        attempts.insert(key, login_attempts);
+    });
+}
+
+pub fn clear_login_attempts(login_address: &LoginAddress) {
+    LOGIN_ATTEMPTS.with_borrow_mut(|attempts| {
+        attempts.remove(&login_address.to_key());
+    });
+}
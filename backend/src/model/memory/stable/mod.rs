@@ -1,4 +1,8 @@
+pub mod audit_log;
+pub mod deposit_intents;
+pub mod login_attempts;
 pub mod orders;
+pub mod payment_ids;
 pub mod spent_transactions;
 pub mod storage;
 pub mod users;
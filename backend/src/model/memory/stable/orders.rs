@@ -1,11 +1,14 @@
 use crate::errors::{OrderError, Result};
-use crate::model::memory::heap::{clear_order_timer, set_order_timer};
+use crate::model::memory::heap::{clear_order_timer, set_consent_poll_timer, set_order_timer};
 use crate::types::{
-    orders::{Order, OrderState, RevolutConsent},
-    PaymentProvider, TransactionAddress,
+    orders::{normalize_reference, LockInput, Order, OrderId, OrderState, UnlockReason},
+    user::User,
+    TransactionAddress,
 };
 
-use super::storage::ORDERS;
+pub const ANONYMIZED_ADDRESS: &str = "anonymized";
+
+use super::storage::{ORDERS, PAYMENT_REFERENCES};
 
 pub fn insert_order(order: &Order) -> Option<OrderState> {
     ORDERS.with_borrow_mut(|p| p.insert(order.id, OrderState::Created(order.clone())))
@@ -43,6 +46,74 @@ where
     })
 }
 
+/// Id of the locked order whose payment reference is `reference`.
+pub fn find_locked_order_by_reference(reference: &str) -> Option<OrderId> {
+    let order_id = PAYMENT_REFERENCES
+        .with_borrow(|references| references.get(&normalize_reference(reference)))?;
+    // A relocked order answers to its latest reference only
+    match get_order(&order_id).ok()? {
+        OrderState::Locked(order)
+            if order.payment_reference.is_some()
+                && order.payment_reference_matches(Some(reference)) =>
+        {
+            Some(order_id)
+        }
+        _ => None,
+    }
+}
+
+fn is_user_address(user: &User, address: &TransactionAddress) -> bool {
+    user.addresses
+        .iter()
+        .any(|user_address| user_address.address == address.address)
+}
+
+/// Orders the user takes part in, matched by user id or, for completed orders, by address.
+pub fn get_user_orders(user: &User) -> Vec<(OrderId, OrderState)> {
+    ORDERS.with_borrow(|orders| {
+        orders
+            .iter()
+            .filter(|(_, order_state)| match order_state {
+                OrderState::Created(order) => order.offramper_user_id == user.id,
+                OrderState::Locked(order) => {
+                    order.base.offramper_user_id == user.id || order.onramper.user_id == user.id
+                }
+                OrderState::Completed(order) => {
+                    is_user_address(user, &order.onramper)
+                        || is_user_address(user, &order.offramper)
+                }
+                OrderState::Cancelled(_) => false,
+            })
+            .collect()
+    })
+}
+
+/// Replaces the user addresses in their completed orders, keeping the records consistent.
+pub fn anonymize_user_orders(user: &User) {
+    ORDERS.with_borrow_mut(|orders| {
+        let completed_orders: Vec<(OrderId, OrderState)> = orders
+            .iter()
+            .filter(|(_, order_state)| matches!(order_state, OrderState::Completed(_)))
+            .collect();
+
+        for (order_id, mut order_state) in completed_orders {
+            let OrderState::Completed(order) = &mut order_state else {
+                continue;
+            };
+            let mut anonymized = false;
+            for address in [&mut order.onramper, &mut order.offramper] {
+                if is_user_address(user, address) {
+                    address.address = ANONYMIZED_ADDRESS.to_string();
+                    anonymized = true;
+                }
+            }
+            if anonymized {
+                orders.insert(order_id, order_state);
+            }
+        }
+    });
+}
+
 pub fn mutate_order<F, R>(order_id: &u64, f: F) -> Result<R>
 where
     F: FnOnce(&mut OrderState) -> R,
@@ -58,33 +129,28 @@ where
     })
 }
 
-pub fn lock_order(
-    order_id: u64,
-    price: u64,
-    offramper_fee: u64,
-    onramper_user_id: u64,
-    onramper_provider: PaymentProvider,
-    onramper_address: TransactionAddress,
-    revolut_consent: Option<RevolutConsent>,
-) -> Result<()> {
+pub fn lock_order(order_id: u64, lock_input: LockInput) -> Result<()> {
+    let has_consent = lock_input.prepared_payment.revolut_consent.is_some();
+    let payment_reference = lock_input.prepared_payment.payment_reference.clone();
     mutate_order(&order_id, |order_state| -> Result<()> {
         match order_state {
             OrderState::Created(order) => {
-                *order_state = OrderState::Locked(order.clone().lock(
-                    price,
-                    offramper_fee,
-                    onramper_user_id,
-                    onramper_provider,
-                    onramper_address,
-                    revolut_consent,
-                )?);
+                *order_state = OrderState::Locked(order.clone().lock(lock_input)?);
                 Ok(())
             }
             _ => Err(OrderError::InvalidOrderState(order_state.to_string()))?,
         }
     })??;
 
+    if let Some(reference) = payment_reference {
+        PAYMENT_REFERENCES.with_borrow_mut(|references| {
+            references.insert(normalize_reference(&reference), order_id)
+        });
+    }
     set_order_timer(order_id);
+    if has_consent {
+        set_consent_poll_timer(order_id);
+    }
     Ok(())
 }
 
@@ -100,16 +166,34 @@ pub fn unlock_order(order_id: u64) -> Result<()> {
     mutate_order(&order_id, |order_state| -> Result<()> {
         match order_state {
             OrderState::Locked(order) => {
-                super::users::mutate_user(order.onramper.user_id, |user| {
-                    user.decrease_score();
-                })?;
-                ic_cdk::println!(
-                    "[unlock_order] score decreased for user #{:?}",
-                    order.onramper.user_id
-                );
+                // orders unlocked before reasons were recorded all timed out
+                if order
+                    .unlock_reason
+                    .as_ref()
+                    .is_none_or(UnlockReason::penalizes_onramper)
+                {
+                    super::users::mutate_user(order.onramper.user_id, |user| {
+                        user.record_timeout(ic_cdk::api::time());
+                    })?;
+                    ic_cdk::println!(
+                        "[unlock_order] timeout recorded for user #{:?}",
+                        order.onramper.user_id
+                    );
+                }
+                if let Some(reservation) = &order.onramper_kyc_reservation {
+                    super::users::mutate_user(order.onramper.user_id, |user| {
+                        user.release_kyc_volume(reservation);
+                    })?;
+                }
 
                 let mut base_order = order.base.clone();
                 base_order.unset_processing();
+                if let Some(consent) = order.revolut_consent.clone() {
+                    base_order
+                        .closed_consents
+                        .get_or_insert_with(Vec::new)
+                        .push(consent.closed());
+                }
 
                 *order_state = OrderState::Created(base_order);
                 Ok(())
@@ -124,7 +208,12 @@ pub fn unlock_order(order_id: u64) -> Result<()> {
 pub fn cancel_order(order_id: u64) -> Result<()> {
     mutate_order(&order_id, |order_state| -> Result<()> {
         match order_state {
-            OrderState::Created(_) => {
+            OrderState::Created(order) => {
+                if let Some(reservation) = &order.offramper_kyc_reservation {
+                    super::users::mutate_user(order.offramper_user_id, |user| {
+                        user.release_kyc_volume(reservation);
+                    })?;
+                }
                 *order_state = OrderState::Cancelled(order_id);
                 Ok(())
             }
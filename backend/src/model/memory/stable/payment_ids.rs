@@ -0,0 +1,63 @@
+use super::storage::CONSUMED_PAYMENT_IDS;
+use std::collections::HashSet;
+
+use crate::{
+    errors::{OrderError, Result},
+    types::{orders::OrderId, user::PaymentReceipt, PaymentProviderType},
+};
+
+// Provider payment ids are only unique within their provider. Unlike
+// processed transaction hashes, consumed ids are never discarded.
+fn payment_key(provider_type: &PaymentProviderType, payment_id: &str) -> String {
+    format!("{:?}:{}", provider_type, payment_id)
+}
+
+/// The order a provider payment was consumed by, if any.
+pub fn get_payment_id_consumer(
+    provider_type: &PaymentProviderType,
+    payment_id: &str,
+) -> Option<OrderId> {
+    CONSUMED_PAYMENT_IDS.with_borrow(|ids| ids.get(&payment_key(provider_type, payment_id)))
+}
+
+/// Fails if the provider payment was already consumed by an order other than `order_id`.
+pub fn check_payment_id_unused(
+    provider_type: &PaymentProviderType,
+    payment_id: &str,
+    order_id: OrderId,
+) -> Result<()> {
+    match get_payment_id_consumer(provider_type, payment_id) {
+        Some(consumer) if consumer != order_id => Err(OrderError::PaymentIdAlreadyUsed.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Binds the provider payment to `order_id`, failing if another order already used it.
+pub fn consume_payment_id(
+    provider_type: &PaymentProviderType,
+    payment_id: &str,
+    order_id: OrderId,
+) -> Result<()> {
+    check_payment_id_unused(provider_type, payment_id, order_id)?;
+    CONSUMED_PAYMENT_IDS.with_borrow_mut(|ids| {
+        ids.insert(payment_key(provider_type, payment_id), order_id);
+    });
+    Ok(())
+}
+
+/// Provider payments consumed by any of `order_ids`.
+pub fn get_order_payments(order_ids: &HashSet<OrderId>) -> Vec<PaymentReceipt> {
+    CONSUMED_PAYMENT_IDS.with_borrow(|ids| {
+        ids.iter()
+            .filter(|(_, order_id)| order_ids.contains(order_id))
+            .filter_map(|(key, order_id)| {
+                let (provider, payment_id) = key.split_once(':')?;
+                Some(PaymentReceipt {
+                    order_id,
+                    provider: provider.to_string(),
+                    payment_id: payment_id.to_string(),
+                })
+            })
+            .collect()
+    })
+}
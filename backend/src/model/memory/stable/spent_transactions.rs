@@ -12,6 +12,26 @@ pub fn mark_tx_hash_as_processed(tx_hash: String) {
     });
 }
 
+/// Marks a transaction as processed unless it already is, returning whether it
+/// was. Reserving a deposit before any await keeps concurrent calls from both
+/// creating an order out of it.
+pub fn reserve_tx_hash(tx_hash: &String) -> bool {
+    PROCESSED_TX_HASHES.with_borrow_mut(|hashes| {
+        if hashes.contains_key(tx_hash) {
+            return false;
+        }
+        hashes.insert(tx_hash.clone(), ic_cdk::api::time() / 1_000_000_000);
+        true
+    })
+}
+
+/// Releases a reserved transaction whose order could not be created.
+pub fn release_tx_hash(tx_hash: &String) {
+    PROCESSED_TX_HASHES.with_borrow_mut(|hashes| {
+        hashes.remove(tx_hash);
+    });
+}
+
 pub fn discard_old_transactions() {
     PROCESSED_TX_HASHES.with_borrow_mut(|hashes| {
         let keys_to_remove: Vec<String> = hashes
@@ -4,6 +4,9 @@ use std::cell::RefCell;
 
 use crate::model::memory::heap::upgrade::SerializableHeap;
 use crate::types::{
+    audit::AuditEntry,
+    evm::deposit::DepositIntent,
+    login_attempts::LoginAttempts,
     orders::{OrderId, OrderState},
     user::User,
 };
@@ -37,4 +40,35 @@ thread_local! {
             MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(3)))
         )
     );
+
+    pub static LOGIN_ATTEMPTS: RefCell<StableBTreeMap<String, LoginAttempts, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(4)))
+        )
+    );
+
+    pub static AUDIT_LOG: RefCell<StableBTreeMap<(u64, u64), AuditEntry, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(5)))
+        )
+    );
+
+    pub static CONSUMED_PAYMENT_IDS: RefCell<StableBTreeMap<String, OrderId, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(6)))
+        )
+    );
+
+    pub static DEPOSIT_INTENTS: RefCell<StableBTreeMap<String, DepositIntent, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(7)))
+        )
+    );
+
+    // normalized payment reference -> order locked with it
+    pub static PAYMENT_REFERENCES: RefCell<StableBTreeMap<String, OrderId, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(8)))
+        )
+    );
 }
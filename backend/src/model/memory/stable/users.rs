@@ -37,7 +37,7 @@ pub fn get_user(user_id: &u64) -> Result<User> {
 pub fn find_user_by_login_address(login_address: &LoginAddress) -> Result<u64> {
     USERS.with(|users| {
         for (id, user) in users.borrow().iter() {
-            if user.login == *login_address {
+            if user.has_login(login_address) {
                 return Ok(id);
             }
         }
@@ -45,12 +45,29 @@ pub fn find_user_by_login_address(login_address: &LoginAddress) -> Result<u64> {
     })
 }
 
+/// Email verification codes to deliver, as `(user_id, email, code)`.
+pub fn get_pending_email_links() -> Vec<(u64, String, String)> {
+    let now = ic_cdk::api::time();
+    USERS.with_borrow(|users| {
+        users
+            .iter()
+            .filter_map(|(id, user)| {
+                let pending_link = user.pending_email_link?;
+                let LoginAddress::Email { email } = pending_link.login_address else {
+                    return None;
+                };
+                (pending_link.expires_at >= now).then_some((id, email, pending_link.code))
+            })
+            .collect()
+    })
+}
+
 pub fn reset_password_user(login_address: &LoginAddress, password: String) -> Result<u64> {
     USERS.with_borrow_mut(|users| {
         let mut user_to_update = None;
 
         for (_, user) in users.iter() {
-            if user.login == *login_address {
+            if user.has_login(login_address) {
                 user_to_update = Some(User {
                     hashed_password: Some(password.clone()),
                     ..user
@@ -0,0 +1,73 @@
+use std::borrow::Cow;
+
+use candid::{CandidType, Decode, Deserialize, Encode, Principal};
+use ic_stable_structures::{storable::Bound, Storable};
+
+use super::kyc::KycTier;
+use crate::model::errors::{Result, SystemError};
+
+const MAX_AUDIT_ENTRY_SIZE: u32 = 1000;
+// leaves room in the entry for the rest of the action
+pub const MAX_REASON_LENGTH: usize = 500;
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum AdminAction {
+    Freeze { reason: String },
+    Unfreeze,
+    AdjustReputation { delta: i32, reason: String },
+    RecordDisputeLost,
+    ForceUnlockOrder { order_id: u64 },
+    ResolveDispute { order_id: u64, paid: bool },
+    ResolvePartialPaymentDispute { order_id: u64, refunded: bool },
+    ResolveUnconfirmedRelease { order_id: u64, released: bool },
+    RefundOrder { order_id: u64 },
+    SetKycTier(KycTier),
+    UnlockLogins,
+    RemoveUser,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct AuditEntry {
+    pub user_id: u64,
+    pub admin: Principal,
+    pub action: AdminAction,
+    pub timestamp: u64, // nanoseconds
+}
+
+impl AuditEntry {
+    pub fn new(user_id: u64, action: AdminAction) -> Self {
+        AuditEntry {
+            user_id,
+            admin: ic_cdk::caller(),
+            action,
+            timestamp: ic_cdk::api::time(),
+        }
+    }
+}
+
+/// Rejects admin reasons too long to fit in an audit entry.
+pub fn validate_reason(reason: &str) -> Result<()> {
+    if reason.len() > MAX_REASON_LENGTH {
+        return Err(SystemError::InvalidInput(format!(
+            "Reason is longer than {} bytes",
+            MAX_REASON_LENGTH
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+impl Storable for AuditEntry {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: MAX_AUDIT_ENTRY_SIZE,
+        is_fixed_size: false,
+    };
+}
@@ -47,6 +47,20 @@ impl Crypto {
         }
     }
 
+    /// Splits off the share `part / total` of the amount and of the fee, keeping the rest.
+    pub fn split_off(&mut self, part: u64, total: u64) -> Crypto {
+        let share = |value: u128| value * part as u128 / total.max(1) as u128;
+        let (amount, fee) = (share(self.amount), share(self.fee));
+        self.amount -= amount;
+        self.fee -= fee;
+        Crypto {
+            blockchain: self.blockchain.clone(),
+            token: self.token.clone(),
+            amount,
+            fee,
+        }
+    }
+
     fn get_decimals(&self) -> Result<u8> {
         match &self.blockchain {
             Blockchain::EVM { chain_id } => {
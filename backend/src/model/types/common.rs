@@ -53,6 +53,19 @@ impl LoginAddress {
         Ok(())
     }
 
+    pub fn login_type(&self) -> std::mem::Discriminant<Self> {
+        std::mem::discriminant(self)
+    }
+
+    pub fn to_key(&self) -> String {
+        match self {
+            LoginAddress::Email { email } => format!("email:{}", email.to_lowercase()),
+            LoginAddress::EVM { address } => format!("evm:{}", address.to_lowercase()),
+            LoginAddress::ICP { principal_id } => format!("icp:{}", principal_id),
+            LoginAddress::Solana { address } => format!("solana:{}", address),
+        }
+    }
+
     pub fn to_transaction_address(&self) -> Result<TransactionAddress> {
         match self {
             LoginAddress::Email { .. } => Err(SystemError::InvalidInput(
@@ -120,4 +133,5 @@ impl TransactionAddress {
 pub struct AuthenticationData {
     pub password: Option<String>,  // For Email
     pub signature: Option<String>, // For EVM
+    pub totp_code: Option<String>, // For Email with TOTP enabled, or a recovery code
 }
@@ -5,7 +5,7 @@ use evm_rpc_canister_types::RpcServices;
 
 use crate::{
     errors::{BlockchainError, Result, SystemError},
-    model::memory::heap::read_state,
+    model::memory::heap::{mutate_state, read_state},
 };
 
 use super::{gas::ChainGasTracking, nonce::NonceManagement, token::Token};
@@ -20,6 +20,8 @@ pub struct ChainState {
     pub(super) nonce_manager: NonceManagement,
     pub approved_tokens: HashMap<String, Token>,
     pub gas_tracking: ChainGasTracking,
+    pub last_scanned_block: Option<u64>, // vault logs are scanned for deposits from the next block
+    pub reserved_fees: Option<HashMap<String, u128>>, // referral rewards owed out of the vault fees, by token
 }
 
 impl ChainState {
@@ -36,6 +38,8 @@ impl ChainState {
             nonce_manager: NonceManagement::new(),
             approved_tokens: HashMap::new(),
             gas_tracking: ChainGasTracking::default(),
+            last_scanned_block: None,
+            reserved_fees: None,
         }
     }
 }
@@ -98,3 +102,69 @@ pub fn chain_is_supported(chain_id: u64) -> Result<()> {
         }
     })
 }
+
+/// Retrieves the last block whose vault logs were scanned for deposits, if any.
+pub fn get_last_scanned_block(chain_id: u64) -> Result<Option<u64>> {
+    read_state(|state| {
+        state
+            .chains
+            .get(&chain_id)
+            .map(|chain_state| chain_state.last_scanned_block)
+            .ok_or_else(|| BlockchainError::ChainIdNotFound(chain_id).into())
+    })
+}
+
+/// Records the last block scanned for deposits. `None` stops the scanning
+/// until a new deposit is awaited.
+pub fn set_last_scanned_block(chain_id: u64, block: Option<u64>) {
+    mutate_state(|state| {
+        if let Some(chain_state) = state.chains.get_mut(&chain_id) {
+            chain_state.last_scanned_block = block;
+        }
+    });
+}
+
+// native currency fees are kept in the vault under the zero address
+fn fee_token_key(token: &Option<String>) -> String {
+    token
+        .as_deref()
+        .unwrap_or("0x0000000000000000000000000000000000000000")
+        .to_lowercase()
+}
+
+/// Vault fees owed to referrers, which cannot be withdrawn by the admin.
+pub fn get_reserved_fees(chain_id: u64, token: &Option<String>) -> u128 {
+    read_state(|state| {
+        state
+            .chains
+            .get(&chain_id)
+            .and_then(|chain_state| chain_state.reserved_fees.as_ref())
+            .and_then(|reserved| reserved.get(&fee_token_key(token)).copied())
+            .unwrap_or(0)
+    })
+}
+
+pub fn reserve_fees(chain_id: u64, token: &Option<String>, amount: u128) {
+    mutate_state(|state| {
+        if let Some(chain_state) = state.chains.get_mut(&chain_id) {
+            *chain_state
+                .reserved_fees
+                .get_or_insert_with(HashMap::new)
+                .entry(fee_token_key(token))
+                .or_insert(0) += amount;
+        }
+    });
+}
+
+pub fn release_reserved_fees(chain_id: u64, token: &Option<String>, amount: u128) {
+    mutate_state(|state| {
+        if let Some(reserved) = state
+            .chains
+            .get_mut(&chain_id)
+            .and_then(|chain_state| chain_state.reserved_fees.as_mut())
+            .and_then(|reserved| reserved.get_mut(&fee_token_key(token)))
+        {
+            *reserved = reserved.saturating_sub(amount);
+        }
+    });
+}
@@ -0,0 +1,94 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use candid::{CandidType, Decode, Deserialize, Encode};
+use ic_stable_structures::{storable::Bound, Storable};
+
+use crate::types::{PaymentProvider, PaymentProviderType};
+
+const MAX_DEPOSIT_INTENT_SIZE: u32 = 4000;
+
+pub(crate) const DEPOSIT_INTENT_EXPIRATION_SECONDS: u64 = 24 * 3600;
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct DepositIntentInput {
+    pub offramper_user_id: u64,
+    pub offramper_address: String,
+    pub chain_id: u64,
+    pub token: Option<String>,
+    pub amount: u128,
+    pub currency: String,
+    pub offramper_providers: HashMap<PaymentProviderType, PaymentProvider>,
+}
+
+/// An EVM order announced by its offramper before depositing in the vault. The
+/// deposit scanner creates the order once it finds the matching deposit.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct DepositIntent {
+    pub offramper_user_id: u64,
+    pub offramper_address: String,
+    pub chain_id: u64,
+    pub token: Option<String>,
+    pub amount: u128,
+    pub currency: String,
+    pub offramper_providers: HashMap<PaymentProviderType, PaymentProvider>,
+    pub created_at: u64,
+}
+
+impl DepositIntent {
+    pub fn new(input: DepositIntentInput) -> Self {
+        DepositIntent {
+            offramper_user_id: input.offramper_user_id,
+            offramper_address: input.offramper_address,
+            chain_id: input.chain_id,
+            token: input.token,
+            amount: input.amount,
+            currency: input.currency,
+            offramper_providers: input.offramper_providers,
+            created_at: ic_cdk::api::time(),
+        }
+    }
+
+    /// Deposits are matched to intents by chain, depositor, token and amount.
+    pub fn key(
+        chain_id: u64,
+        offramper_address: &str,
+        token: Option<&str>,
+        amount: u128,
+    ) -> String {
+        format!(
+            "{}:{}:{}:{}",
+            chain_id,
+            offramper_address.to_lowercase(),
+            token.unwrap_or_default().to_lowercase(),
+            amount
+        )
+    }
+
+    pub fn intent_key(&self) -> String {
+        Self::key(
+            self.chain_id,
+            &self.offramper_address,
+            self.token.as_deref(),
+            self.amount,
+        )
+    }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        now.saturating_sub(self.created_at) > DEPOSIT_INTENT_EXPIRATION_SECONDS * 1_000_000_000
+    }
+}
+
+impl Storable for DepositIntent {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: MAX_DEPOSIT_INTENT_SIZE,
+        is_fixed_size: false,
+    };
+}
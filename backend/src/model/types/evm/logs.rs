@@ -18,6 +18,7 @@ pub enum TransactionStatus {
     BroadcastError(RampError),
     Confirmed(TransactionReceipt),
     Failed(String),
+    Reverted(TransactionReceipt),
     Pending,
     Unresolved(String, SignRequestCandid),
 }
@@ -1,4 +1,5 @@
 pub mod chains;
+pub mod deposit;
 pub mod gas;
 pub mod logs;
 pub mod nonce;
@@ -0,0 +1,53 @@
+use candid::{CandidType, Deserialize};
+use serde_bytes::ByteBuf;
+
+/// Request served through the canister HTTP interface (`http_request`).
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct GatewayRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: ByteBuf,
+}
+
+impl GatewayRequest {
+    /// Request path, without the query string.
+    pub fn path(&self) -> &str {
+        self.url.split('?').next().unwrap_or_default()
+    }
+
+    /// Value of the header `name`, compared case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct GatewayResponse {
+    pub status_code: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: ByteBuf,
+    pub upgrade: Option<bool>,
+}
+
+impl GatewayResponse {
+    pub fn new(status_code: u16, body: &str) -> Self {
+        Self {
+            status_code,
+            headers: vec![("Content-Type".to_string(), "text/plain".to_string())],
+            body: ByteBuf::from(body.as_bytes()),
+            upgrade: None,
+        }
+    }
+
+    /// Asks the boundary node to replay the request as an update call.
+    pub fn upgrade() -> Self {
+        Self {
+            upgrade: Some(true),
+            ..Self::new(200, "")
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use candid::{CandidType, Deserialize};
+
+use crate::model::errors::{Result, UserError};
+
+// limits are expressed in cents of this currency
+pub const KYC_LIMITS_CURRENCY: &str = "EUR";
+pub const KYC_VOLUME_WINDOW_DAYS: u64 = 30;
+const DAY_NANOS: u64 = 24 * 3600 * 1_000_000_000;
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Default)]
+pub enum KycTier {
+    #[default]
+    Unverified,
+    Basic,
+    Full,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct KycLimits {
+    pub per_order: u64,
+    pub per_window: u64,
+}
+
+impl KycTier {
+    pub fn limits(&self) -> Option<KycLimits> {
+        match self {
+            KycTier::Unverified => Some(KycLimits {
+                per_order: 10_000,  // 100 EUR
+                per_window: 50_000, // 500 EUR
+            }),
+            KycTier::Basic => Some(KycLimits {
+                per_order: 100_000,  // 1000 EUR
+                per_window: 500_000, // 5000 EUR
+            }),
+            KycTier::Full => None,
+        }
+    }
+}
+
+/// Volume booked for an order that is not settled yet, given back if the order
+/// is unlocked or cancelled.
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct KycReservation {
+    pub amount: u64,
+    pub day: u64,
+}
+
+impl KycReservation {
+    /// Splits off the share `part / total` of the reservation, keeping the rest.
+    pub fn split_off(&mut self, part: u64, total: u64) -> KycReservation {
+        let amount = (self.amount as u128 * part as u128 / total.max(1) as u128) as u64;
+        self.amount -= amount;
+        KycReservation {
+            amount,
+            day: self.day,
+        }
+    }
+}
+
+/// Traded volume bucketed by day, so that the rolling window stays bounded in size.
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct KycVolume {
+    pub daily: Vec<(u64, u64)>, // (day, amount in cents)
+}
+
+impl KycVolume {
+    fn prune(&mut self, now: u64) {
+        let first_day = (now / DAY_NANOS).saturating_sub(KYC_VOLUME_WINDOW_DAYS - 1);
+        self.daily.retain(|(day, _)| *day >= first_day);
+    }
+
+    pub fn window_total(&self, now: u64) -> u64 {
+        let first_day = (now / DAY_NANOS).saturating_sub(KYC_VOLUME_WINDOW_DAYS - 1);
+        self.daily
+            .iter()
+            .filter(|(day, _)| *day >= first_day)
+            .map(|(_, amount)| amount)
+            .sum()
+    }
+
+    pub fn record(&mut self, amount: u64, now: u64) -> KycReservation {
+        self.prune(now);
+        let today = now / DAY_NANOS;
+        match self.daily.iter_mut().find(|(day, _)| *day == today) {
+            Some((_, total)) => *total += amount,
+            None => self.daily.push((today, amount)),
+        }
+        KycReservation { amount, day: today }
+    }
+
+    /// Gives back a reservation, unless its day already left the window.
+    pub fn release(&mut self, reservation: &KycReservation) {
+        if let Some((_, total)) = self
+            .daily
+            .iter_mut()
+            .find(|(day, _)| *day == reservation.day)
+        {
+            *total = total.saturating_sub(reservation.amount);
+        }
+    }
+
+    pub fn check_limits(&self, tier: &KycTier, amount: u64, now: u64) -> Result<()> {
+        let Some(limits) = tier.limits() else {
+            return Ok(());
+        };
+
+        if amount > limits.per_order {
+            return Err(UserError::KycOrderLimitExceeded(limits.per_order).into());
+        }
+        if self.window_total(now) + amount > limits.per_window {
+            return Err(UserError::KycVolumeLimitExceeded(limits.per_window).into());
+        }
+        Ok(())
+    }
+}
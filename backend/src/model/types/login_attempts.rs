@@ -0,0 +1,72 @@
+use std::borrow::Cow;
+
+use candid::{CandidType, Decode, Deserialize, Encode};
+use ic_stable_structures::{storable::Bound, Storable};
+
+use crate::model::errors::{Result, UserError};
+
+const MAX_LOGIN_ATTEMPTS_SIZE: u32 = 200;
+
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct LoginAttempts {
+    pub failed_attempts: u32,
+    pub last_failed_at: u64,       // nanoseconds
+    pub locked_until: Option<u64>, // nanoseconds
+}
+
+impl LoginAttempts {
+    /// Failed attempts allowed before the first lockout kicks in.
+    pub(crate) const FREE_ATTEMPTS: u32 = 3;
+    pub(crate) const BASE_LOCKOUT_SECS: u64 = 30;
+    pub(crate) const MAX_LOCKOUT_SECS: u64 = 86400; // 24h
+    /// Counters are forgotten after this quiet period without failures.
+    pub(crate) const RESET_AFTER_SECS: u64 = 86400; // 24h
+
+    pub fn check_locked(&self, now: u64) -> Result<()> {
+        match self.locked_until {
+            Some(locked_until) if now < locked_until => {
+                Err(UserError::LoginLocked((locked_until - now).div_ceil(1_000_000_000)).into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn is_stale(&self, now: u64) -> bool {
+        now.saturating_sub(self.last_failed_at) >= Self::RESET_AFTER_SECS * 1_000_000_000
+            && self.check_locked(now).is_ok()
+    }
+
+    /// Registers a failed attempt and, once the free attempts are exhausted,
+    /// locks the login with a lockout that doubles on every further failure.
+    pub fn record_failure(&mut self, now: u64) {
+        if self.is_stale(now) {
+            *self = Self::default();
+        }
+
+        self.failed_attempts += 1;
+        self.last_failed_at = now;
+
+        if self.failed_attempts >= Self::FREE_ATTEMPTS {
+            let exponent = (self.failed_attempts - Self::FREE_ATTEMPTS).min(32);
+            let lockout_secs = Self::BASE_LOCKOUT_SECS
+                .saturating_mul(1 << exponent)
+                .min(Self::MAX_LOCKOUT_SECS);
+            self.locked_until = Some(now + lockout_secs * 1_000_000_000);
+        }
+    }
+}
+
+impl Storable for LoginAttempts {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: MAX_LOGIN_ATTEMPTS_SIZE,
+        is_fixed_size: false,
+    };
+}
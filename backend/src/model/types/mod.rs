@@ -1,10 +1,16 @@
+pub mod audit;
 mod blockchain;
 mod common;
 pub mod evm;
 pub mod exchange_rate;
+pub mod gateway;
 pub mod icp;
+pub mod kyc;
+pub mod login_attempts;
 pub mod orders;
 pub mod payment;
+pub mod referral;
+pub mod reputation;
 pub mod session;
 pub mod user;
 
@@ -14,8 +20,11 @@ pub use payment::providers::{contains_provider_type, PaymentProvider, PaymentPro
 
 #[cfg(test)]
 mod tests {
+    use crate::management::totp;
     use crate::model::types::common::{LoginAddress, TransactionAddress};
-    use crate::model::types::user::User;
+    use crate::model::types::kyc::KycTier;
+    use crate::model::types::login_attempts::LoginAttempts;
+    use crate::model::types::user::{TotpConfig, User};
     use crate::types::{common::AddressType, user::UserType, PaymentProvider};
 
     use candid::Principal;
@@ -154,4 +163,928 @@ mod tests {
         assert_eq!(user1.addresses, retrieved_user1.addresses);
         assert_eq!(user2.addresses, retrieved_user2.addresses);
     }
+
+    #[test]
+    fn test_link_login_addresses() {
+        let login_address = LoginAddress::EVM {
+            address: (format!("{:#x}", EthAddress::random())),
+        };
+        let mut user = User::new(UserType::Onramper, login_address.clone(), None).unwrap();
+
+        let icp_login = LoginAddress::ICP {
+            principal_id: Principal::anonymous().to_string(),
+        };
+        user.link_login(icp_login.clone()).unwrap();
+        assert!(user.has_login(&login_address));
+        assert!(user.has_login(&icp_login));
+        assert_eq!(user.addresses.len(), 2);
+
+        // Only one login per type
+        let other_evm_login = LoginAddress::EVM {
+            address: (format!("{:#x}", EthAddress::random())),
+        };
+        assert!(user.link_login(other_evm_login.clone()).is_err());
+        assert!(!user.has_login(&other_evm_login));
+
+        // The primary login cannot be removed
+        assert!(user.unlink_login(&login_address).is_err());
+
+        user.unlink_login(&icp_login).unwrap();
+        assert!(!user.has_login(&icp_login));
+        assert!(user.unlink_login(&icp_login).is_err());
+        // along with the address it added
+        assert_eq!(user.addresses.len(), 1);
+    }
+
+    #[test]
+    fn test_login_attempts_backoff() {
+        let second = 1_000_000_000;
+        let now = 1_700_000_000 * second;
+        let mut attempts = LoginAttempts::default();
+
+        for _ in 1..LoginAttempts::FREE_ATTEMPTS {
+            attempts.record_failure(now);
+            assert!(attempts.check_locked(now).is_ok());
+        }
+
+        attempts.record_failure(now);
+        assert!(attempts.check_locked(now).is_err());
+        assert!(attempts
+            .check_locked(now + LoginAttempts::BASE_LOCKOUT_SECS * second)
+            .is_ok());
+
+        // Every further failure doubles the lockout
+        attempts.record_failure(now);
+        assert!(attempts
+            .check_locked(now + LoginAttempts::BASE_LOCKOUT_SECS * second)
+            .is_err());
+        assert!(attempts
+            .check_locked(now + 2 * LoginAttempts::BASE_LOCKOUT_SECS * second)
+            .is_ok());
+
+        // Lockout is capped
+        for _ in 0..64 {
+            attempts.record_failure(now);
+        }
+        assert_eq!(
+            attempts.locked_until,
+            Some(now + LoginAttempts::MAX_LOCKOUT_SECS * second)
+        );
+
+        // Counters reset after a quiet period
+        let later = now + (LoginAttempts::RESET_AFTER_SECS + 1) * second;
+        assert!(attempts.is_stale(later));
+        attempts.record_failure(later);
+        assert_eq!(attempts.failed_attempts, 1);
+        assert!(attempts.check_locked(later).is_ok());
+    }
+
+    #[test]
+    fn test_totp_rfc6238_vectors() {
+        let secret = b"12345678901234567890";
+        let vectors = [
+            (59, "287082"),
+            (1111111109, "081804"),
+            (1234567890, "005924"),
+            (2000000000, "279037"),
+        ];
+        for (time, code) in vectors {
+            assert_eq!(
+                totp::generate_code(secret, time / totp::TOTP_STEP_SECS),
+                code
+            );
+        }
+    }
+
+    #[test]
+    fn test_verify_totp_and_recovery_codes() {
+        let login_address = LoginAddress::Email {
+            email: "user@example.com".to_string(),
+        };
+        let mut user = User::new(UserType::Onramper, login_address, None).unwrap();
+        user.totp = Some(TotpConfig {
+            secret: "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ".to_string(),
+            enabled: true,
+            last_used_step: None,
+            recovery_codes: vec![totp::hash_recovery_code("a1b2c3d4e5f6")],
+        });
+
+        assert!(user.verify_totp("005924", 1234567890).is_ok());
+        // the same code cannot be replayed
+        assert!(user.verify_totp("005924", 1234567890).is_err());
+        assert!(user.verify_totp("000000", 1234567890).is_err());
+
+        // recovery codes are single use
+        assert!(user.verify_totp("A1B2C3D4E5F6", 1234567890).is_ok());
+        assert!(user.verify_totp("a1b2c3d4e5f6", 1234567890).is_err());
+
+        // credentials never leave the canister
+        user.hashed_password = Some("$pbkdf2-sha256$hash".to_string());
+        let redacted = user.redacted();
+        let totp = redacted.totp.unwrap();
+        assert!(totp.enabled);
+        assert!(totp.secret.is_empty() && totp.recovery_codes.is_empty());
+        assert!(redacted.hashed_password.is_none() && redacted.session.is_none());
+    }
+
+    #[test]
+    fn test_reputation_score_decay() {
+        use crate::model::types::orders::UnlockReason;
+
+        let day = 24 * 3600 * 1_000_000_000;
+        let now = 1_700_000_000 * 1_000_000_000;
+        let login_address = LoginAddress::EVM {
+            address: (format!("{:#x}", EthAddress::random())),
+        };
+        let mut user = User::new(UserType::Onramper, login_address, None).unwrap();
+
+        user.record_onramper_trade(120, now);
+        user.record_onramper_trade(60, now);
+        assert_eq!(user.score, 3);
+        let reputation = user.reputation.clone().unwrap();
+        assert_eq!(reputation.onramper_trades, 2);
+        assert_eq!(reputation.average_payment_secs, Some(90));
+
+        user.record_dispute_lost(now);
+        assert_eq!(user.score, 1);
+        user.record_timeout(now);
+        user.record_timeout(now);
+        assert_eq!(user.score, -1);
+
+        // penalties fade away over time
+        assert_eq!(user.current_score(now + 720 * day), 1);
+        user.refresh_score(now + 720 * day);
+        assert_eq!(user.score, 1);
+
+        // Only expired locks count as timeouts, lost disputes are penalized once
+        // and refunded onrampers not at all
+        assert!(UnlockReason::Timeout.penalizes_onramper());
+        assert!(!UnlockReason::Admin.penalizes_onramper());
+        assert!(!UnlockReason::DisputeLost.penalizes_onramper());
+        assert!(!UnlockReason::Refunded.penalizes_onramper());
+    }
+
+    #[test]
+    fn test_kyc_limits_rolling_window() {
+        let day = 24 * 3600 * 1_000_000_000;
+        let now = 1_700_000_000 * 1_000_000_000;
+        let login_address = LoginAddress::EVM {
+            address: (format!("{:#x}", EthAddress::random())),
+        };
+        let mut user = User::new(UserType::Onramper, login_address, None).unwrap();
+        let limits = KycTier::Unverified.limits().unwrap();
+
+        assert!(user.check_kyc_limits(limits.per_order + 1, now).is_err());
+
+        let mut reservations = vec![];
+        for _ in 0..(limits.per_window / limits.per_order) {
+            reservations.push(user.reserve_kyc_volume(limits.per_order, now).unwrap());
+        }
+        assert!(user.reserve_kyc_volume(1, now).is_err());
+
+        // unlocked or cancelled orders give their volume back
+        let mut reservation = reservations.pop().flatten().unwrap();
+        let unpaid = reservation.split_off(limits.per_order / 2, limits.per_order);
+        user.release_kyc_volume(&unpaid);
+        assert!(user.check_kyc_limits(limits.per_order / 2, now).is_ok());
+        assert!(user
+            .check_kyc_limits(limits.per_order / 2 + 1, now)
+            .is_err());
+        user.release_kyc_volume(&reservation);
+        assert!(user.check_kyc_limits(limits.per_order, now).is_ok());
+
+        // older volume leaves the window
+        assert!(user.check_kyc_limits(1, now + 30 * day).is_ok());
+
+        user.kyc_tier = Some(KycTier::Full);
+        assert!(user.check_kyc_limits(limits.per_window * 10, now).is_ok());
+    }
+
+    #[test]
+    fn test_anonymize_user_orders() {
+        use crate::model::memory::stable::{orders, storage::ORDERS};
+        use crate::model::types::orders::{CompletedOrder, OrderState};
+        use crate::model::types::Blockchain;
+
+        let login_address = LoginAddress::EVM {
+            address: (format!("{:#x}", EthAddress::random())),
+        };
+        let user = User::new(UserType::Onramper, login_address.clone(), None).unwrap();
+        let user_address = login_address.to_transaction_address().unwrap();
+        let other_address = TransactionAddress {
+            address_type: AddressType::EVM,
+            address: format!("{:#x}", EthAddress::random()),
+        };
+
+        let completed_order = |onramper: &TransactionAddress| {
+            OrderState::Completed(CompletedOrder {
+                onramper: onramper.clone(),
+                offramper: other_address.clone(),
+                price: 1000,
+                offramper_fee: 10,
+                blockchain: Blockchain::EVM { chain_id: 1 },
+                completed_at: 0,
+                fx_payment: None,
+                overpayment: None,
+                partial_fill: None,
+                revolut_consent: None,
+            })
+        };
+        ORDERS.with_borrow_mut(|orders| {
+            orders.insert(1, completed_order(&user_address));
+            orders.insert(2, completed_order(&other_address));
+        });
+
+        let user_orders = orders::get_user_orders(&user);
+        assert_eq!(user_orders.len(), 1);
+        assert_eq!(user_orders[0].0, 1);
+
+        orders::anonymize_user_orders(&user);
+        assert!(orders::get_user_orders(&user).is_empty());
+        let Ok(OrderState::Completed(order)) = orders::get_order(&1) else {
+            panic!("order should stay completed");
+        };
+        assert_eq!(order.onramper.address, orders::ANONYMIZED_ADDRESS);
+        assert_eq!(order.offramper.address, other_address.address);
+    }
+
+    #[test]
+    fn test_freeze_and_adjust_reputation() {
+        let now = 1_700_000_000 * 1_000_000_000;
+        let login_address = LoginAddress::EVM {
+            address: (format!("{:#x}", EthAddress::random())),
+        };
+        let mut user = User::new(UserType::Offramper, login_address, None).unwrap();
+
+        user.frozen = Some("chargeback investigation".to_string());
+        assert!(user.is_banned().is_err());
+
+        user.adjust_reputation(-3, now);
+        assert_eq!(user.score, -2);
+        user.adjust_reputation(5, now);
+        assert_eq!(user.score, 3);
+
+        // the longest accepted reason still fits in an audit entry
+        use crate::model::types::audit::{self, AdminAction, AuditEntry, MAX_REASON_LENGTH};
+        use ic_stable_structures::{storable::Bound, Storable};
+        let reason = "a".repeat(MAX_REASON_LENGTH);
+        assert!(audit::validate_reason(&reason).is_ok());
+        assert!(audit::validate_reason(&format!("{}a", reason)).is_err());
+        let entry = AuditEntry {
+            user_id: u64::MAX,
+            admin: Principal::anonymous(),
+            action: AdminAction::AdjustReputation {
+                delta: i32::MIN,
+                reason,
+            },
+            timestamp: u64::MAX,
+        };
+        let Bound::Bounded { max_size, .. } = AuditEntry::BOUND else {
+            unreachable!()
+        };
+        assert!(entry.to_bytes().len() <= max_size as usize);
+    }
+
+    #[test]
+    fn test_referral_rewards() {
+        use crate::model::types::orders::fees::{get_referral_fee, DEFAULT_REFERRAL_FEE_BPS};
+        use crate::model::types::Blockchain;
+
+        // 0.5% admin fee, 20% of it to the referrer
+        assert_eq!(get_referral_fee(1_000_000, DEFAULT_REFERRAL_FEE_BPS), 1_000);
+
+        let login_address = LoginAddress::EVM {
+            address: (format!("{:#x}", EthAddress::random())),
+        };
+        let mut user = User::new(UserType::Offramper, login_address, None).unwrap();
+        let blockchain = Blockchain::EVM { chain_id: 10 };
+        let token = Some("0xAbC".to_string());
+
+        user.accrue_referral_reward(&blockchain, &token, 500);
+        user.accrue_referral_reward(&blockchain, &Some("0xabc".to_string()), 250);
+        user.accrue_referral_reward(&blockchain, &None, 100);
+        assert_eq!(user.referral_rewards.as_ref().unwrap().len(), 2);
+
+        // EVM claims are debited once confirmed, keeping what accrued in between
+        assert_eq!(user.referral_reward(&blockchain, &token).unwrap(), 750);
+        user.accrue_referral_reward(&blockchain, &token, 50);
+        user.debit_referral_reward(&blockchain, &token, 750);
+        assert_eq!(user.referral_reward(&blockchain, &token).unwrap(), 50);
+
+        assert_eq!(user.take_referral_reward(&blockchain, &token).unwrap(), 50);
+        assert!(user.take_referral_reward(&blockchain, &token).is_err());
+        assert_eq!(user.take_referral_reward(&blockchain, &None).unwrap(), 100);
+    }
+
+    #[test]
+    fn test_payment_rails() {
+        use crate::management::providers;
+        use crate::types::PaymentProviderType;
+
+        assert!(PaymentProvider::PayPal {
+            id: "user@example.com".to_string()
+        }
+        .validate()
+        .is_ok());
+        assert!(PaymentProvider::PayPal { id: String::new() }
+            .validate()
+            .is_err());
+        assert!(PaymentProvider::Revolut {
+            scheme: "UK.OBIE.SortCodeAccountNumber".to_string(),
+            id: String::new(),
+            name: None,
+        }
+        .validate()
+        .is_err());
+
+        assert!(providers::supports_currency(
+            &PaymentProviderType::PayPal,
+            "usd"
+        ));
+        assert!(providers::supports_currency(
+            &PaymentProviderType::Revolut,
+            "GBP"
+        ));
+        assert!(!providers::supports_currency(
+            &PaymentProviderType::Revolut,
+            "BRL"
+        ));
+
+        assert_eq!(providers::PayPal::format_amount(1205, "USD"), "12.05");
+        assert_eq!(providers::PayPal::format_amount(150_000, "JPY"), "1500");
+        assert_eq!(providers::PayPal::format_amount(150_001, "huf"), "1501");
+    }
+
+    #[test]
+    fn test_wise_provider() {
+        use crate::outcalls::wise::transfer::{WiseRecipient, WiseTransfer, WISE_TRANSFER_SENT};
+
+        assert!(PaymentProvider::Wise {
+            profile_id: "16313".to_string(),
+            account_id: "GB33 BUKB 2020 1555 5555 55".to_string(),
+            name: None,
+        }
+        .validate()
+        .is_ok());
+        assert!(PaymentProvider::Wise {
+            profile_id: "personal".to_string(),
+            account_id: "GB33BUKB20201555555555".to_string(),
+            name: None,
+        }
+        .validate()
+        .is_err());
+
+        let transfer: WiseTransfer = serde_json::from_str(
+            r#"{"id":468956,"user":1,"targetAccount":148,"sourceAccount":null,"status":"outgoing_payment_sent","reference":"42","rate":1.0,"sourceCurrency":"EUR","sourceValue":10.5,"targetCurrency":"EUR","targetValue":10.5}"#,
+        )
+        .unwrap();
+        assert_eq!(transfer.status, WISE_TRANSFER_SENT);
+        assert_eq!(transfer.target_account, 148);
+        assert_eq!(transfer.target_value, 10.5);
+
+        let recipient: WiseRecipient = serde_json::from_str(
+            r#"{"id":148,"profile":16313,"accountHolderName":"Ann Example","currency":"EUR","country":"GB","type":"iban","details":{"iban":"GB33BUKB20201555555555","bic":"BUKBGB22"}}"#,
+        )
+        .unwrap();
+        assert!(recipient.matches_account("gb33 bukb 2020 1555 5555 55"));
+        assert!(!recipient.matches_account("GB94BARC10201530093459"));
+    }
+
+    #[test]
+    fn test_pix_brcode() {
+        use crate::types::payment::pix::{brcode_payload, crc16_ccitt, payer_matches, PixKeyType};
+
+        // Static BR Code example from the BCB PIX manual
+        let example = "00020126580014br.gov.bcb.pix0136123e4567-e12b-12d1-a456-4266554400005204000053039865802BR5913Fulano de Tal6008BRASILIA62070503***6304";
+        assert_eq!(format!("{:04X}", crc16_ccitt(example.as_bytes())), "1D3D");
+
+        // Dynamic charges point to their payload on the PSP, the txid being "***"
+        let location = "pix.example.com/qr/v2/9d36b84fc70b478fb95c12729b90ca25";
+        let payload = brcode_payload(location, "Fulano de Tal", "BRASILIA");
+        assert!(payload.contains(&format!("2554{}", location)));
+        assert!(payload.contains("010212"));
+        assert!(payload.contains("62070503***"));
+        assert!(!payload.contains("5405"));
+        let (body, crc) = payload.split_at(payload.len() - 4);
+        assert_eq!(format!("{:04X}", crc16_ccitt(body.as_bytes())), crc);
+
+        assert!(PixKeyType::Cpf.validate_key("12345678901").is_ok());
+        assert!(PixKeyType::Cpf.validate_key("123.456.789-01").is_err());
+        assert!(PixKeyType::Phone.validate_key("+5511987654321").is_ok());
+        assert!(PixKeyType::Evp
+            .validate_key("123e4567-e12b-12d1-a456-426655440000")
+            .is_ok());
+
+        let cpf = "12345678901";
+        assert!(payer_matches(
+            &PixKeyType::Cpf,
+            cpf,
+            "Fulano",
+            Some(cpf),
+            None
+        ));
+        assert!(!payer_matches(
+            &PixKeyType::Cpf,
+            cpf,
+            "Fulano",
+            Some("10987654321"),
+            Some("Fulano")
+        ));
+        assert!(payer_matches(
+            &PixKeyType::Email,
+            "f@tal.br",
+            "Fulano de Tal",
+            None,
+            Some("FULANO  DE TAL")
+        ));
+        assert!(!payer_matches(
+            &PixKeyType::Email,
+            "f@tal.br",
+            "Fulano de Tal",
+            None,
+            None
+        ));
+    }
+
+    #[test]
+    fn test_upi_intent() {
+        use crate::management::providers;
+        use crate::types::payment::upi::intent_uri;
+        use crate::types::PaymentProviderType;
+
+        assert!(PaymentProvider::Upi {
+            vpa: "asha.k@okhdfc".to_string(),
+            name: Some("Asha K".to_string()),
+        }
+        .validate()
+        .is_ok());
+        for vpa in ["asha", "a@okhdfc", "asha@", "asha k@okhdfc"] {
+            assert!(PaymentProvider::Upi {
+                vpa: vpa.to_string(),
+                name: None,
+            }
+            .validate()
+            .is_err());
+        }
+
+        assert_eq!(
+            intent_uri("asha.k@okhdfc", "Asha K", 250_005, "ICRAMP7"),
+            "upi://pay?pa=asha.k@okhdfc&pn=Asha%20K&am=2500.05&cu=INR&tr=ICRAMP7&tn=ICRAMP7"
+        );
+        assert!(providers::supports_currency(
+            &PaymentProviderType::Upi,
+            "INR"
+        ));
+    }
+
+    #[test]
+    fn test_payment_reference() {
+        use crate::types::orders::{generate_payment_reference, normalize_reference};
+
+        let locked_at = 1_700_000_000 * 1_000_000_000;
+        let reference = generate_payment_reference(42, locked_at);
+        assert!(reference.starts_with("ICR-"));
+        assert!(reference.len() <= 12);
+
+        // Unique per order and per lock of the same order
+        assert_ne!(reference, generate_payment_reference(43, locked_at));
+        assert_ne!(
+            reference,
+            generate_payment_reference(42, locked_at + 60 * 1_000_000_000)
+        );
+        assert_eq!(reference, generate_payment_reference(42, locked_at));
+        // Large ids keep their high bits
+        assert_ne!(
+            generate_payment_reference(1 << 54, locked_at),
+            generate_payment_reference(0, locked_at)
+        );
+
+        assert_eq!(
+            normalize_reference(&reference.to_lowercase()),
+            reference.replace('-', "")
+        );
+    }
+
+    #[test]
+    fn test_prepared_payment_amount() {
+        use crate::management::providers::Revolut;
+        use crate::model::types::orders::{
+            FxPayment, LockInput, Order, PartialPayment, PreparedPayment,
+        };
+        use crate::model::types::{Blockchain, Crypto};
+        use crate::types::PaymentProviderType;
+        use std::collections::HashMap;
+
+        let revolut = |id: &str| PaymentProvider::Revolut {
+            scheme: "UK.OBIE.SortCodeAccountNumber".to_string(),
+            id: id.to_string(),
+            name: Some("Jane Doe".to_string()),
+        };
+        let evm_address = || TransactionAddress {
+            address_type: AddressType::EVM,
+            address: format!("{:#x}", EthAddress::random()),
+        };
+        let order = Order {
+            id: 1,
+            created_at: 0,
+            currency: "EUR".to_string(),
+            offramper_user_id: 1,
+            offramper_address: evm_address(),
+            offramper_providers: HashMap::from([(
+                PaymentProviderType::Revolut,
+                revolut("04000400000001"),
+            )]),
+            crypto: Crypto::new(Blockchain::EVM { chain_id: 1 }, None, 10u128.pow(18), 0),
+            processing: false,
+            accepted_currencies: None,
+            offramper_kyc_reservation: None,
+            closed_consents: None,
+        };
+        let onramper_provider = revolut("04000400000002");
+        let (price, offramper_fee) = (1000, 20);
+
+        // The consent created on lock covers the payment initiated once it is authorised
+        let consent =
+            Revolut::payment_initiation(&order, &onramper_provider, price + offramper_fee, None)
+                .unwrap();
+        let locked = order
+            .lock_at(
+                LockInput {
+                    price,
+                    offramper_fee,
+                    onramper_user_id: 2,
+                    onramper_provider,
+                    onramper_address: evm_address(),
+                    prepared_payment: PreparedPayment::default(),
+                    onramper_kyc_reservation: None,
+                },
+                0,
+            )
+            .unwrap();
+        let payment = Revolut::locked_payment_initiation(&locked).unwrap();
+        assert_eq!(consent.amount, "10.2");
+        assert_eq!(consent.amount, payment.amount);
+        // Paid from the onramper's account into the offramper's
+        assert_eq!(payment.debtor_id, "04000400000002");
+        assert_eq!(payment.creditor_id, "04000400000001");
+
+        // Refunds pay back what was received rather than the order's total
+        let mut paid = locked.clone();
+        paid.received_amount = Some(1005);
+        let refund = Revolut::refund_initiation(&paid).unwrap();
+        assert_eq!(
+            (refund.amount.as_str(), refund.currency.as_str()),
+            ("10.05", "EUR")
+        );
+        assert_eq!(refund.debtor_id, "04000400000001");
+        paid.fx_payment = Some(FxPayment::new("USD".to_string(), 11.5, 0.9));
+        let refund = Revolut::refund_initiation(&paid).unwrap();
+        assert_eq!(
+            (refund.amount.as_str(), refund.currency.as_str()),
+            ("11.50", "USD")
+        );
+
+        // A topped-up order consumed its partial payments as well as the last one
+        let mut topped_up = locked.clone();
+        topped_up.partial_payment = Some(PartialPayment {
+            received: 1020,
+            payment_ids: vec!["partial".to_string(), "top-up".to_string()],
+            disputed_at: None,
+        });
+        topped_up.payment_id = Some("top-up".to_string());
+        assert_eq!(topped_up.payment_ids(), vec!["partial", "top-up"]);
+        topped_up.partial_payment = None;
+        assert_eq!(topped_up.payment_ids(), vec!["top-up"]);
+    }
+
+    #[cfg(feature = "mock-provider")]
+    #[test]
+    fn test_mock_rail() {
+        use crate::management::providers::{Mock, PaymentRail};
+        use crate::model::types::orders::{
+            generate_payment_reference, LockInput, Order, PreparedPayment,
+        };
+        use crate::model::types::payment::mock::{MockOutcome, MockPayment};
+        use crate::model::types::{Blockchain, Crypto};
+        use crate::types::PaymentProviderType;
+        use std::collections::HashMap;
+
+        let mock = |id: &str| PaymentProvider::Mock { id: id.to_string() };
+        assert!(Mock::validate_account(&mock("alice")).is_ok());
+        assert!(Mock::validate_account(&mock("")).is_err());
+
+        let evm_address = || TransactionAddress {
+            address_type: AddressType::EVM,
+            address: format!("{:#x}", EthAddress::random()),
+        };
+        let order = Order {
+            id: 7,
+            created_at: 0,
+            currency: "EUR".to_string(),
+            offramper_user_id: 1,
+            offramper_address: evm_address(),
+            offramper_providers: HashMap::from([(PaymentProviderType::Mock, mock("alice"))]),
+            crypto: Crypto::new(Blockchain::EVM { chain_id: 1 }, None, 10u128.pow(18), 0),
+            processing: false,
+            accepted_currencies: None,
+            offramper_kyc_reservation: None,
+            closed_consents: None,
+        };
+        let locked = order
+            .lock_at(
+                LockInput {
+                    price: 1000,
+                    offramper_fee: 20,
+                    onramper_user_id: 2,
+                    onramper_provider: mock("bob"),
+                    onramper_address: evm_address(),
+                    prepared_payment: PreparedPayment {
+                        payment_reference: Some(generate_payment_reference(7, 0)),
+                        ..Default::default()
+                    },
+                    onramper_kyc_reservation: None,
+                },
+                0,
+            )
+            .unwrap();
+
+        let success = MockPayment::new(&locked, "bob", "alice", MockOutcome::Success);
+        assert_eq!(success.amount, locked.outstanding_amount());
+        assert!(!success.pending);
+        assert!(success.pays(&locked, "bob", "alice"));
+        // Only the payer and payee of the order match
+        assert!(!success.pays(&locked, "alice", "bob"));
+
+        let short = MockPayment::new(&locked, "bob", "alice", MockOutcome::WrongAmount);
+        assert_eq!(short.amount, locked.outstanding_amount() - 100);
+
+        let wrong_payer = MockPayment::new(&locked, "bob", "alice", MockOutcome::WrongPayer);
+        assert!(!wrong_payer.pays(&locked, "bob", "alice"));
+
+        let pending = MockPayment::new(&locked, "bob", "alice", MockOutcome::Pending);
+        assert!(pending.pending);
+        assert!(pending.pays(&locked, "bob", "alice"));
+
+        // A payment carrying another reference or for another order does not count
+        let mut other_reference = success.clone();
+        other_reference.reference = Some(generate_payment_reference(8, 0));
+        assert!(!other_reference.pays(&locked, "bob", "alice"));
+        let mut other_order = success;
+        other_order.order_id = 8;
+        assert!(!other_order.pays(&locked, "bob", "alice"));
+    }
+
+    #[test]
+    fn test_consumed_payment_ids() {
+        use crate::model::memory::stable::payment_ids;
+        use crate::types::PaymentProviderType;
+
+        let paypal = PaymentProviderType::PayPal;
+        assert!(payment_ids::consume_payment_id(&paypal, "8MC585209K746392H", 1).is_ok());
+        // Retrying the same order is fine, another order is not
+        assert!(payment_ids::consume_payment_id(&paypal, "8MC585209K746392H", 1).is_ok());
+        assert!(payment_ids::check_payment_id_unused(&paypal, "8MC585209K746392H", 2).is_err());
+        assert_eq!(
+            payment_ids::get_payment_id_consumer(&paypal, "8MC585209K746392H"),
+            Some(1)
+        );
+
+        // Ids are scoped by provider
+        assert!(payment_ids::check_payment_id_unused(
+            &PaymentProviderType::Revolut,
+            "8MC585209K746392H",
+            2
+        )
+        .is_ok());
+
+        // Exported as receipts of the consuming order
+        let receipts = payment_ids::get_order_payments(&HashSet::from([1]));
+        assert_eq!(receipts.len(), 1);
+        assert_eq!(receipts[0].provider, "PayPal");
+        assert_eq!(receipts[0].payment_id, "8MC585209K746392H");
+    }
+
+    #[test]
+    fn test_bank_transfer_provider() {
+        use crate::management::providers;
+        use crate::types::PaymentProviderType;
+
+        assert!(PaymentProvider::BankTransfer {
+            account_holder: "Jane Doe".to_string(),
+            account_number: "DE89370400440532013000".to_string(),
+            bank_name: None,
+        }
+        .validate()
+        .is_ok());
+        assert!(PaymentProvider::BankTransfer {
+            account_holder: String::new(),
+            account_number: "DE89370400440532013000".to_string(),
+            bank_name: Some("Commerzbank".to_string()),
+        }
+        .validate()
+        .is_err());
+
+        assert!(providers::supports_currency(
+            &PaymentProviderType::BankTransfer,
+            "CHF"
+        ));
+        assert!(!providers::supports_currency(
+            &PaymentProviderType::BankTransfer,
+            "EURO"
+        ));
+    }
+
+    #[test]
+    fn test_paypal_webhook_event() {
+        use crate::model::memory::heap::try_start_webhook_verification;
+        use crate::model::types::gateway::GatewayRequest;
+        use crate::outcalls::paypal::webhook::{
+            verification_body, TransmissionHeaders, WebhookEvent,
+        };
+
+        let body = r#"{
+            "id": "WH-58D329510W468432D-8HN650336L201105X",
+            "event_type": "PAYMENT.CAPTURE.COMPLETED",
+            "resource": {
+                "id": "42311647XV020574X",
+                "status": "COMPLETED",
+                "custom_id": "ICR-1A05K",
+                "supplementary_data": { "related_ids": { "order_id": "5O190127TN364715T" } }
+            }
+        }"#;
+        let request = GatewayRequest {
+            method: "POST".to_string(),
+            url: "/webhooks/paypal?source=paypal".to_string(),
+            headers: vec![(
+                "PAYPAL-TRANSMISSION-ID".to_string(),
+                "69cd13f0-d67a-11e5-baa3-778b53f4ae55".to_string(),
+            )],
+            body: serde_bytes::ByteBuf::from(body.as_bytes()),
+        };
+        assert_eq!(request.path(), "/webhooks/paypal");
+        assert_eq!(
+            request.header("paypal-transmission-id"),
+            Some("69cd13f0-d67a-11e5-baa3-778b53f4ae55")
+        );
+        assert_eq!(request.header("paypal-transmission-sig"), None);
+
+        let event: WebhookEvent = serde_json::from_slice(&request.body).unwrap();
+        assert_eq!(event.resource.reference(), Some("ICR-1A05K"));
+        assert_eq!(event.resource.order_id(), Some("5O190127TN364715T"));
+
+        // The event is forwarded byte for byte, as signed
+        let headers = TransmissionHeaders {
+            auth_algo: "SHA256withRSA".to_string(),
+            cert_url: "https://api.paypal.com/v1/notifications/certs/CERT-360caa42".to_string(),
+            transmission_id: "69cd13f0-d67a-11e5-baa3-778b53f4ae55".to_string(),
+            transmission_sig: "lmI95Jx3Y9nhR5SJWlHVIWpg4AgFk7n9bCHSRxbrd8A9zrhdu2rMyFrmz+Zjh3s3"
+                .to_string(),
+            transmission_time: "2016-02-18T20:01:35Z".to_string(),
+        };
+        let verification = verification_body(&headers, "1JE4291016473214C", body).unwrap();
+        assert!(verification.ends_with(&format!(",\"webhook_event\":{}}}", body)));
+        let verification: serde_json::Value = serde_json::from_str(&verification).unwrap();
+        assert_eq!(verification["webhook_id"], "1JE4291016473214C");
+        assert_eq!(verification["transmission_time"], "2016-02-18T20:01:35Z");
+
+        // Deliveries for an order are verified at most once per interval
+        let now = 1_700_000_000 * 1_000_000_000;
+        assert!(try_start_webhook_verification(7, now));
+        assert!(!try_start_webhook_verification(7, now + 1_000_000_000));
+        assert!(try_start_webhook_verification(8, now + 1_000_000_000));
+        assert!(try_start_webhook_verification(7, now + 61 * 1_000_000_000));
+    }
+
+    #[test]
+    fn test_revolut_consent_status() {
+        use crate::model::types::orders::{RevolutConsent, RevolutConsentStatus};
+
+        let mut consent = RevolutConsent::new(
+            "b7a4ac0e-3e1d-4a4b-9c35-24b2a1c1d1f0".to_string(),
+            "https://sandbox-oba.revolut.com/ui/index.html".to_string(),
+        );
+        assert_eq!(
+            consent.status(),
+            RevolutConsentStatus::AwaitingAuthorisation
+        );
+        consent.status = None;
+        assert_eq!(
+            consent.status(),
+            RevolutConsentStatus::AwaitingAuthorisation
+        );
+
+        assert_eq!(
+            RevolutConsentStatus::from_api("Authorised"),
+            RevolutConsentStatus::Authorised
+        );
+        // Unknown statuses end the polling instead of failing it
+        assert!(RevolutConsentStatus::from_api("Expired").is_final());
+        assert!(RevolutConsentStatus::from_api("Cancelled").is_final());
+        assert!(!RevolutConsentStatus::Authorised.is_final());
+        assert!(RevolutConsentStatus::Rejected.is_final());
+        assert!(RevolutConsentStatus::Revoked.is_final());
+
+        // Consents of unlocked or completed orders are recorded as revoked,
+        // unless they already ended
+        consent.status = Some(RevolutConsentStatus::Authorised);
+        assert_eq!(
+            consent.clone().closed().status(),
+            RevolutConsentStatus::Revoked
+        );
+        consent.status = Some(RevolutConsentStatus::Consumed);
+        assert_eq!(consent.closed().status(), RevolutConsentStatus::Consumed);
+    }
+
+    #[test]
+    fn test_fx_payment_tolerance() {
+        use crate::model::types::orders::FxPayment;
+
+        // 108.50 USD at 0.92 EUR/USD for an order of 100 EUR
+        let fx_payment = FxPayment::new("USD".to_string(), 108.5, 0.92);
+        assert!((fx_payment.converted_amount - 99.82).abs() < 1e-9);
+        assert!(fx_payment.is_within_tolerance(100., 100));
+        assert!(!fx_payment.is_within_tolerance(100., 10));
+        assert!(!FxPayment::new("USD".to_string(), 105., 0.92).is_within_tolerance(100., 100));
+    }
+
+    #[test]
+    fn test_payment_tolerance() {
+        use crate::model::types::orders::{parse_fiat_amount, PaymentAmount, PaymentTolerance};
+        use crate::model::types::{Blockchain, Crypto};
+
+        assert_eq!(parse_fiat_amount("100.01"), Some(10_001));
+        assert_eq!(parse_fiat_amount("0"), None);
+
+        let exact = PaymentTolerance::default();
+        assert_eq!(
+            exact.classify(10_000, 9_999),
+            PaymentAmount::Underpaid { missing: 1 }
+        );
+        assert_eq!(
+            exact.classify(10_000, 10_050),
+            PaymentAmount::Overpaid { excess: 50 }
+        );
+
+        // 1% under and 0.5% over the total are settled as full payments
+        let tolerance = PaymentTolerance {
+            underpayment_bps: 100,
+            overpayment_bps: 50,
+            min_partial_fill_bps: 5_000,
+        };
+        assert_eq!(tolerance.classify(10_000, 9_900), PaymentAmount::Exact);
+        assert_eq!(tolerance.classify(10_000, 10_050), PaymentAmount::Exact);
+        assert_eq!(
+            tolerance.classify(10_000, 10_051),
+            PaymentAmount::Overpaid { excess: 51 }
+        );
+        assert!(tolerance.allows_partial_fill(10_000, 5_000));
+        assert!(!tolerance.allows_partial_fill(10_000, 4_999));
+
+        // A partial fill of 60% leaves the remainder 40% of the crypto and of its fee
+        let mut filled = Crypto::new(
+            Blockchain::ICP {
+                ledger_principal: Principal::anonymous(),
+            },
+            None,
+            1_000_000,
+            20_000,
+        );
+        let remainder = filled.split_off(4_000, 10_000);
+        assert_eq!((filled.amount, filled.fee), (600_000, 12_000));
+        assert_eq!((remainder.amount, remainder.fee), (400_000, 8_000));
+    }
+
+    #[test]
+    fn test_deposit_intent_key() {
+        use crate::model::types::evm::deposit::{DepositIntent, DEPOSIT_INTENT_EXPIRATION_SECONDS};
+        use std::collections::HashMap;
+
+        let intent = DepositIntent {
+            offramper_user_id: 1,
+            offramper_address: "0xAbC0000000000000000000000000000000000001".to_string(),
+            chain_id: 8453,
+            token: Some("0xDeF0000000000000000000000000000000000002".to_string()),
+            amount: 1_000_000,
+            currency: "EUR".to_string(),
+            offramper_providers: HashMap::new(),
+            created_at: 0,
+        };
+
+        // Deposit events carry lowercase addresses
+        let deposit_key = DepositIntent::key(
+            8453,
+            "0xabc0000000000000000000000000000000000001",
+            Some("0xdef0000000000000000000000000000000000002"),
+            1_000_000,
+        );
+        assert_eq!(intent.intent_key(), deposit_key);
+        assert_ne!(
+            intent.intent_key(),
+            DepositIntent::key(8453, &intent.offramper_address, None, 1_000_000)
+        );
+
+        let expiration = DEPOSIT_INTENT_EXPIRATION_SECONDS * 1_000_000_000;
+        assert!(!intent.is_expired(expiration));
+        assert!(intent.is_expired(expiration + 1));
+    }
 }
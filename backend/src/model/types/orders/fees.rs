@@ -1,11 +1,20 @@
 pub(crate) const OFFRAMPER_FIAT_FEE_DENOM: u64 = 40; // 2.5%
 pub(crate) const ADMIN_CRYPTO_FEE_DENOM: u128 = 200; // 0.5%
+pub(crate) const DEFAULT_REFERRAL_FEE_BPS: u16 = 2000; // 20% of the admin fee
+pub(crate) const MAX_REFERRAL_FEE_BPS: u16 = 5000; // both parties may have a referrer
 
 pub fn get_fiat_fee(fiat_amount: u64) -> u64 {
     fiat_amount / OFFRAMPER_FIAT_FEE_DENOM
 }
 
+pub fn get_admin_crypto_fee(crypto_amount: u128) -> u128 {
+    crypto_amount / ADMIN_CRYPTO_FEE_DENOM
+}
+
 pub fn get_crypto_fee(crypto_amount: u128, blockchain_fees: u128) -> u128 {
-    let admin_fee = crypto_amount / ADMIN_CRYPTO_FEE_DENOM;
-    blockchain_fees + admin_fee
+    blockchain_fees + get_admin_crypto_fee(crypto_amount)
+}
+
+pub fn get_referral_fee(crypto_amount: u128, referral_fee_bps: u16) -> u128 {
+    get_admin_crypto_fee(crypto_amount) * referral_fee_bps as u128 / 10_000
 }
@@ -10,6 +10,7 @@ pub enum OrderFilter {
     LockedByOnramper(TransactionAddress),
     ByState(OrderStateFilter),
     ByBlockchain(Blockchain),
+    ByMinOfframperScore(i32),
 }
 
 #[derive(CandidType, Clone, Deserialize)]
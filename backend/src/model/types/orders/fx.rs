@@ -0,0 +1,30 @@
+use candid::{CandidType, Deserialize};
+
+pub const DEFAULT_FX_TOLERANCE_BPS: u16 = 100; // 1%
+pub const MAX_FX_TOLERANCE_BPS: u16 = 1_000; // 10%
+
+/// Conversion applied to a payment received in another currency than the order's.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct FxPayment {
+    pub currency: String,
+    pub received_amount: f64,
+    pub rate: f64, // order currency units per unit of `currency`
+    pub converted_amount: f64,
+}
+
+impl FxPayment {
+    pub fn new(currency: String, received_amount: f64, rate: f64) -> Self {
+        FxPayment {
+            currency,
+            received_amount,
+            rate,
+            converted_amount: received_amount * rate,
+        }
+    }
+
+    /// Whether the converted amount is within `tolerance_bps` of `expected_amount`.
+    pub fn is_within_tolerance(&self, expected_amount: f64, tolerance_bps: u16) -> bool {
+        let tolerance = expected_amount * tolerance_bps as f64 / 10_000.;
+        (self.converted_amount - expected_amount).abs() <= tolerance
+    }
+}
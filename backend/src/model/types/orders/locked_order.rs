@@ -1,19 +1,26 @@
 use candid::{CandidType, Deserialize};
 
 use crate::{
-    model::memory::heap,
+    model::{memory::heap, types::kyc::KycReservation},
     types::{Blockchain, PaymentProvider, TransactionAddress},
 };
 
-use super::order::Order;
+use super::{
+    fx::FxPayment,
+    order::Order,
+    reference::normalize_reference,
+    settlement::{Overpayment, PartialFill, PartialPayment},
+};
 
+#[derive(Clone)]
 pub struct LockInput {
     pub price: u64,
     pub offramper_fee: u64,
     pub onramper_user_id: u64,
     pub onramper_provider: PaymentProvider,
     pub onramper_address: TransactionAddress,
-    pub revolut_consent: Option<RevolutConsent>,
+    pub prepared_payment: PreparedPayment,
+    pub onramper_kyc_reservation: Option<KycReservation>,
 }
 
 #[derive(CandidType, Deserialize, Clone)]
@@ -33,16 +40,124 @@ impl Onramper {
     }
 }
 
-#[derive(CandidType, Deserialize, Clone)]
+#[derive(CandidType, Deserialize, Clone, Debug)]
 pub struct RevolutConsent {
     pub id: String,
     url: String,
+    pub status: Option<RevolutConsentStatus>, // unset for consents tracked before statuses existed
 }
 
 impl RevolutConsent {
     pub fn new(id: String, url: String) -> Self {
-        RevolutConsent { id, url }
+        RevolutConsent {
+            id,
+            url,
+            status: Some(RevolutConsentStatus::AwaitingAuthorisation),
+        }
+    }
+
+    pub fn status(&self) -> RevolutConsentStatus {
+        self.status
+            .clone()
+            .unwrap_or(RevolutConsentStatus::AwaitingAuthorisation)
+    }
+
+    /// The consent as recorded once its order unlocks or completes: revoked,
+    /// unless it can no longer authorise a payment anyway.
+    ///
+    /// Revolut offers no call to revoke a domestic payment consent, which expires
+    /// unused, so the revocation only stops the canister from paying against it.
+    pub fn closed(mut self) -> Self {
+        if !self.status().is_final() {
+            self.status = Some(RevolutConsentStatus::Revoked);
+        }
+        self
+    }
+}
+
+/// Status of a Revolut payment consent, as reported by the Open Banking API.
+/// `Revoked` is set by the canister when the order leaves the locked state.
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub enum RevolutConsentStatus {
+    AwaitingAuthorisation,
+    Authorised,
+    Rejected,
+    Consumed,
+    Revoked,
+    Expired,
+}
+
+impl RevolutConsentStatus {
+    /// Maps a status reported by Revolut. Statuses unknown to the canister are
+    /// taken as expired, so that nothing is paid against such a consent.
+    pub fn from_api(status: &str) -> Self {
+        match status {
+            "AwaitingAuthorisation" => Self::AwaitingAuthorisation,
+            "Authorised" => Self::Authorised,
+            "Rejected" => Self::Rejected,
+            "Consumed" => Self::Consumed,
+            _ => Self::Expired,
+        }
     }
+
+    /// Whether the consent can no longer authorise a payment.
+    pub fn is_final(&self) -> bool {
+        matches!(
+            self,
+            Self::Rejected | Self::Consumed | Self::Revoked | Self::Expired
+        )
+    }
+}
+
+/// An onramper's claim of having sent a manual bank transfer, awaiting the
+/// offramper's confirmation until `confirm_by`.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct BankTransferClaim {
+    pub reference: String,
+    pub claimed_at: u64,
+    pub confirm_by: u64,
+    pub disputed: bool,
+    pub dispute_loser: Option<u64>, // user id, set once the lost dispute is recorded
+}
+
+/// Fiat refund of a paid order whose crypto could not be released.
+#[derive(CandidType, Deserialize, Clone)]
+pub struct FiatRefund {
+    pub requested_at: u64,
+    pub status: RefundStatus,
+}
+
+#[derive(CandidType, Deserialize, Clone)]
+pub enum RefundStatus {
+    /// The order is marked for refund, the provider has not confirmed it yet.
+    RefundPending,
+    /// The offramper has to authorize the refund payment through the consent.
+    AwaitingOfframper(RevolutConsent),
+    Refunded {
+        refund_id: String,
+    },
+    Failed(String),
+}
+
+/// Details the onramper needs to send the fiat payment of a locked order.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum PaymentInstructions {
+    Pix {
+        txid: String,
+        payload: String,
+    },
+    Upi {
+        reference: String,
+        intent_uri: String,
+    },
+}
+
+/// Output of a payment rail's preparation step, stored on the locked order.
+#[derive(Clone, Default)]
+pub struct PreparedPayment {
+    pub payment_reference: Option<String>,
+    pub revolut_consent: Option<RevolutConsent>,
+    pub instructions: Option<PaymentInstructions>,
 }
 
 #[derive(CandidType, Deserialize, Clone)]
@@ -52,10 +167,39 @@ pub struct LockedOrder {
     pub price: u64,
     pub offramper_fee: u64,
     pub onramper: Onramper,
+    pub payment_reference: Option<String>, // set for orders locked since references exist
     pub revolut_consent: Option<RevolutConsent>,
+    pub payment_instructions: Option<PaymentInstructions>,
+    pub bank_transfer: Option<BankTransferClaim>,
+    pub refund: Option<FiatRefund>,
+    pub fx_payment: Option<FxPayment>,
+    pub partial_payment: Option<PartialPayment>,
+    pub overpayment: Option<Overpayment>,
+    pub partial_fill: Option<PartialFill>,
     pub payment_id: Option<String>,
+    pub received_amount: Option<u64>, // cents credited by the payments, set once paid in full
     pub payment_done: bool,
     pub uncommited: bool,
+    pub onramper_kyc_reservation: Option<KycReservation>,
+    pub unlock_reason: Option<UnlockReason>, // set once the order is being unlocked
+    pub release_failure: Option<String>,     // set once releasing the crypto failed for good
+    pub release_unconfirmed: Option<String>, // set while a mined release awaits admin review
+}
+
+/// Why a locked order goes back to its offramper.
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub enum UnlockReason {
+    Timeout,
+    Admin,
+    DisputeLost, // already recorded as a lost dispute
+    Refunded,
+}
+
+impl UnlockReason {
+    /// Only an onramper letting the lock run out gets a timeout on their reputation.
+    pub fn penalizes_onramper(&self) -> bool {
+        matches!(self, UnlockReason::Timeout)
+    }
 }
 
 impl LockedOrder {
@@ -67,11 +211,73 @@ impl LockedOrder {
         self.uncommited = true;
     }
 
-    pub fn payment_amount_matches(&self, received_amount: &str) -> bool {
-        let total_expected_amount = (self.price + self.offramper_fee) as f64 / 100.0;
-        let received_amount_as_float = received_amount.parse::<f64>().unwrap_or(0.0);
+    /// Total the onramper pays, in cents.
+    pub fn total_amount(&self) -> u64 {
+        self.price + self.offramper_fee
+    }
+
+    /// Part of the total not covered by the partial payments received so far.
+    pub fn outstanding_amount(&self) -> u64 {
+        let received = self
+            .partial_payment
+            .as_ref()
+            .map_or(0, |payment| payment.received);
+        self.total_amount().saturating_sub(received)
+    }
+
+    /// Amount (cents) and currency the onramper actually paid. A single payment
+    /// in another currency is paid back in that currency, otherwise the amount
+    /// credited to the order is.
+    pub fn received_payment(&self) -> (u64, String) {
+        if let Some(fx_payment) = self.fx_payment.as_ref() {
+            if self.payment_ids().len() <= 1 {
+                return (
+                    (fx_payment.received_amount * 100.).round() as u64,
+                    fx_payment.currency.clone(),
+                );
+            }
+        }
+        let received = self.received_amount.unwrap_or_else(|| {
+            let excess = self
+                .overpayment
+                .as_ref()
+                .map_or(0, |overpayment| overpayment.excess);
+            self.total_amount() + excess
+        });
+        (received, self.base.currency.clone())
+    }
+
+    /// Ids of every payment the order consumed: the partial payments and the one
+    /// completing it.
+    pub fn payment_ids(&self) -> Vec<String> {
+        let mut payment_ids = self
+            .partial_payment
+            .as_ref()
+            .map_or_else(Vec::new, |payment| payment.payment_ids.clone());
+        if let Some(payment_id) = &self.payment_id {
+            if !payment_ids.contains(payment_id) {
+                payment_ids.push(payment_id.clone());
+            }
+        }
+        payment_ids
+    }
+
+    /// Whether `received` is this order's payment reference, ignoring case and
+    /// separators. Orders locked before references existed accept any.
+    pub fn payment_reference_matches(&self, received: Option<&str>) -> bool {
+        match (&self.payment_reference, received) {
+            (None, _) => true,
+            (Some(expected), Some(received)) => {
+                normalize_reference(expected) == normalize_reference(received)
+            }
+            (Some(_), None) => false,
+        }
+    }
 
-        (received_amount_as_float - total_expected_amount).abs() < f64::EPSILON
+    pub fn is_refunded(&self) -> bool {
+        self.refund
+            .as_ref()
+            .is_some_and(|refund| matches!(refund.status, RefundStatus::Refunded { .. }))
     }
 
     pub fn is_inside_lock_time(&self) -> bool {
@@ -87,6 +293,10 @@ pub struct CompletedOrder {
     pub offramper_fee: u64,
     pub blockchain: Blockchain,
     pub completed_at: u64,
+    pub fx_payment: Option<FxPayment>,
+    pub overpayment: Option<Overpayment>,
+    pub partial_fill: Option<PartialFill>,
+    pub revolut_consent: Option<RevolutConsent>,
 }
 
 impl From<LockedOrder> for CompletedOrder {
@@ -99,6 +309,10 @@ impl From<LockedOrder> for CompletedOrder {
             offramper_fee: locked_order.offramper_fee,
             blockchain: base.crypto.blockchain,
             completed_at: ic_cdk::api::time(),
+            fx_payment: locked_order.fx_payment,
+            overpayment: locked_order.overpayment,
+            partial_fill: locked_order.partial_fill,
+            revolut_consent: locked_order.revolut_consent.map(RevolutConsent::closed),
         }
     }
 }
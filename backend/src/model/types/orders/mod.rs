@@ -1,10 +1,16 @@
 pub mod fees;
 mod filter;
+mod fx;
 mod locked_order;
 mod order;
 mod order_state;
+mod reference;
+mod settlement;
 
 pub use filter::*;
+pub use fx::*;
 pub use locked_order::*;
 pub use order::*;
 pub use order_state::*;
+pub use reference::*;
+pub use settlement::*;
@@ -2,12 +2,12 @@ use std::collections::HashMap;
 
 use candid::{CandidType, Deserialize};
 
-use super::locked_order::{LockedOrder, Onramper, RevolutConsent};
+use super::locked_order::{LockInput, LockedOrder, Onramper, RevolutConsent};
 use crate::{
     errors::{OrderError, Result, SystemError},
     model::{
         memory::heap,
-        types::{common::AddressType, Crypto, PaymentProviderType},
+        types::{common::AddressType, kyc::KycReservation, Crypto, PaymentProviderType},
     },
     types::{Blockchain, PaymentProvider, TransactionAddress},
 };
@@ -22,6 +22,9 @@ pub struct Order {
     pub offramper_providers: HashMap<PaymentProviderType, PaymentProvider>,
     pub crypto: Crypto,
     pub processing: bool,
+    pub accepted_currencies: Option<Vec<String>>, // other currencies the offramper takes payment in
+    pub offramper_kyc_reservation: Option<KycReservation>,
+    pub closed_consents: Option<Vec<RevolutConsent>>, // Revolut consents of past locks
 }
 
 #[derive(CandidType, Deserialize, Clone)]
@@ -66,12 +69,26 @@ impl Order {
             offramper_providers,
             crypto: Crypto::new(blockchain, token, crypto_amount, crypto_fee),
             processing: false,
+            accepted_currencies: None,
+            offramper_kyc_reservation: None,
+            closed_consents: None,
         };
         ic_cdk::println!("[new order] order = {:?}", order);
 
         Ok(order)
     }
 
+    /// Whether the order can be paid in `currency`, converted to its own currency
+    /// when they differ.
+    pub fn accepts_currency(&self, currency: &str) -> bool {
+        self.currency.eq_ignore_ascii_case(currency)
+            || self.accepted_currencies.as_ref().is_some_and(|currencies| {
+                currencies
+                    .iter()
+                    .any(|accepted| accepted.eq_ignore_ascii_case(currency))
+            })
+    }
+
     pub fn is_processing(&self) -> Result<()> {
         if !self.processing {
             return Err(OrderError::OrderNotProcessing.into());
@@ -96,15 +113,21 @@ impl Order {
         self.processing = false;
     }
 
-    pub fn lock(
-        self,
-        price: u64,
-        offramper_fee: u64,
-        onramper_user_id: u64,
-        onramper_provider: PaymentProvider,
-        onramper_address: TransactionAddress,
-        revolut_consent: Option<RevolutConsent>,
-    ) -> Result<LockedOrder> {
+    pub fn lock(self, lock_input: LockInput) -> Result<LockedOrder> {
+        self.lock_at(lock_input, ic_cdk::api::time())
+    }
+
+    pub fn lock_at(self, lock_input: LockInput, locked_at: u64) -> Result<LockedOrder> {
+        let LockInput {
+            price,
+            offramper_fee,
+            onramper_user_id,
+            onramper_provider,
+            onramper_address,
+            prepared_payment,
+            onramper_kyc_reservation,
+        } = lock_input;
+
         // Check if the address type matches the blockchain type
         match (
             self.crypto.blockchain.clone(),
@@ -126,14 +149,27 @@ impl Order {
 
         Ok(LockedOrder {
             base: base_order,
-            locked_at: ic_cdk::api::time(),
+            locked_at,
             price,
             offramper_fee,
             onramper: Onramper::new(onramper_user_id, onramper_provider, onramper_address),
-            revolut_consent,
+            payment_reference: prepared_payment.payment_reference,
+            revolut_consent: prepared_payment.revolut_consent,
+            payment_instructions: prepared_payment.instructions,
+            bank_transfer: None,
+            refund: None,
+            fx_payment: None,
+            partial_payment: None,
+            overpayment: None,
+            partial_fill: None,
             payment_done: false,
             payment_id: None,
+            received_amount: None,
             uncommited: false,
+            onramper_kyc_reservation,
+            unlock_reason: None,
+            release_failure: None,
+            release_unconfirmed: None,
         })
     }
 }
@@ -0,0 +1,51 @@
+const SYMBOLS: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ"; // Crockford's base32
+const REFERENCE_PREFIX: &str = "ICR";
+const SALT_BITS: u32 = 10;
+
+/// Builds the human-readable payment reference of an order locked at
+/// `locked_at` (ns), e.g. `ICR-1A05K`.
+///
+/// The code encodes the order id together with a salt taken from the lock time,
+/// so that relocking an order yields a new reference, and ends with a check
+/// symbol catching mistyped characters.
+pub fn generate_payment_reference(order_id: u64, locked_at: u64) -> String {
+    // Widened so that shifting the salt in never drops bits of the order id
+    let salt = (locked_at / 1_000_000_000) % (1 << SALT_BITS);
+    let mut value = ((order_id as u128) << SALT_BITS) | salt as u128;
+
+    let mut code = Vec::new();
+    loop {
+        code.push(SYMBOLS[(value % 32) as usize]);
+        value /= 32;
+        if value == 0 {
+            break;
+        }
+    }
+    code.reverse();
+    code.push(check_symbol(&code));
+
+    format!(
+        "{}-{}",
+        REFERENCE_PREFIX,
+        String::from_utf8(code).expect("symbols are ASCII")
+    )
+}
+
+/// Uppercase alphanumeric form of a reference, as carried by rails that do not
+/// allow separators (PIX txid, UPI `tr`).
+pub fn normalize_reference(reference: &str) -> String {
+    reference
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+fn check_symbol(code: &[u8]) -> u8 {
+    let sum: usize = code
+        .iter()
+        .enumerate()
+        .map(|(i, symbol)| (i + 1) * SYMBOLS.iter().position(|s| s == symbol).unwrap_or(0))
+        .sum();
+    SYMBOLS[sum % 32]
+}
@@ -0,0 +1,122 @@
+use candid::{CandidType, Deserialize};
+
+use crate::errors::{Result, SystemError};
+
+use super::fx::FxPayment;
+
+/// Tolerance bands applied to the amount an onramper paid, in basis points of
+/// the order's total.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct PaymentTolerance {
+    /// Shortfall still settled as a full payment, e.g. rounding or bank charges.
+    pub underpayment_bps: u16,
+    /// Excess settled without recording an overpayment to refund.
+    pub overpayment_bps: u16,
+    /// Smallest share of the total an underpaid order can be partially filled with.
+    pub min_partial_fill_bps: u16,
+}
+
+impl Default for PaymentTolerance {
+    fn default() -> Self {
+        PaymentTolerance {
+            underpayment_bps: 0,
+            overpayment_bps: 0,
+            min_partial_fill_bps: 5_000,
+        }
+    }
+}
+
+pub const MAX_PAYMENT_TOLERANCE_BPS: u16 = 500; // 5%
+
+impl PaymentTolerance {
+    pub fn validate(&self) -> Result<()> {
+        if self.underpayment_bps > MAX_PAYMENT_TOLERANCE_BPS
+            || self.overpayment_bps > MAX_PAYMENT_TOLERANCE_BPS
+        {
+            return Err(
+                SystemError::InvalidInput("Payment tolerance is too high".to_string()).into(),
+            );
+        }
+        if self.min_partial_fill_bps == 0 || self.min_partial_fill_bps > 10_000 {
+            return Err(
+                SystemError::InvalidInput("Invalid minimum partial fill".to_string()).into(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Compares the amount received so far with the order's total, both in cents.
+    pub fn classify(&self, expected: u64, received: u64) -> PaymentAmount {
+        let band = |bps: u16| (expected as u128 * bps as u128 / 10_000) as u64;
+        if received + band(self.underpayment_bps) < expected {
+            PaymentAmount::Underpaid {
+                missing: expected - received,
+            }
+        } else if received > expected + band(self.overpayment_bps) {
+            PaymentAmount::Overpaid {
+                excess: received - expected,
+            }
+        } else {
+            PaymentAmount::Exact
+        }
+    }
+
+    /// Whether `received` cents are enough to partially fill an order of `expected`.
+    pub fn allows_partial_fill(&self, expected: u64, received: u64) -> bool {
+        received as u128 * 10_000 >= expected as u128 * self.min_partial_fill_bps as u128
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PaymentAmount {
+    Exact,
+    Underpaid { missing: u64 },
+    Overpaid { excess: u64 },
+}
+
+/// A payment checked by its rail, crediting `amount` cents in the order currency.
+#[derive(Clone, Debug)]
+pub struct VerifiedPayment {
+    pub amount: u64,
+    pub fx_payment: Option<FxPayment>,
+}
+
+impl VerifiedPayment {
+    pub fn new(amount: u64) -> Self {
+        VerifiedPayment {
+            amount,
+            fx_payment: None,
+        }
+    }
+}
+
+/// Parses a decimal fiat amount as reported by a provider into cents.
+pub fn parse_fiat_amount(amount: &str) -> Option<u64> {
+    let amount = amount.trim().parse::<f64>().ok()?;
+    (amount.is_finite() && amount > 0.).then(|| (amount * 100.).round() as u64)
+}
+
+/// Payments received for an order short of its total, awaiting a top-up or a
+/// partial fill.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct PartialPayment {
+    pub received: u64, // cents
+    pub payment_ids: Vec<String>,
+    pub disputed_at: Option<u64>, // set when the lock time ended and no partial fill was possible
+}
+
+/// Amount an onramper paid in excess, which the offramper has to send back.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct Overpayment {
+    pub excess: u64, // cents
+    pub offramper_user_id: u64,
+    pub refund_reference: Option<String>, // set once the offramper refunded it
+}
+
+/// Order completed for the share of the crypto its payment covered. The rest
+/// was put back on sale as the order `remainder_order_id`.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct PartialFill {
+    pub paid: u64, // cents
+    pub remainder_order_id: u64,
+}
@@ -0,0 +1,85 @@
+use candid::{CandidType, Deserialize};
+
+use crate::{
+    errors::{Result, SystemError},
+    model::memory::heap::{mutate_state, read_state, State},
+};
+
+/// Credentials and cached access token of a rail authenticating with the
+/// OAuth client-credentials grant.
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct ClientCredentialsState {
+    pub access_token: Option<String>,
+    pub token_expiration: Option<u64>,
+    pub client_id: String,
+    pub client_secret: String,
+    pub api_url: String,
+}
+
+impl ClientCredentialsState {
+    pub fn new(client_id: String, client_secret: String, api_url: String) -> Self {
+        Self {
+            access_token: None,
+            token_expiration: None,
+            client_id,
+            client_secret,
+            api_url,
+        }
+    }
+}
+
+/// The rails sharing the client-credentials token flow.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClientCredentialsRail {
+    Wise,
+    Pix,
+    Upi,
+}
+
+impl ClientCredentialsRail {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ClientCredentialsRail::Wise => "Wise",
+            ClientCredentialsRail::Pix => "PIX",
+            ClientCredentialsRail::Upi => "UPI",
+        }
+    }
+
+    fn state(self, state: &State) -> Option<&ClientCredentialsState> {
+        match self {
+            ClientCredentialsRail::Wise => state.wise.as_ref(),
+            ClientCredentialsRail::Pix => state.pix.as_ref(),
+            ClientCredentialsRail::Upi => state.upi.as_ref(),
+        }
+    }
+
+    fn state_mut(self, state: &mut State) -> Option<&mut ClientCredentialsState> {
+        match self {
+            ClientCredentialsRail::Wise => state.wise.as_mut(),
+            ClientCredentialsRail::Pix => state.pix.as_mut(),
+            ClientCredentialsRail::Upi => state.upi.as_mut(),
+        }
+    }
+}
+
+pub fn get_state(rail: ClientCredentialsRail) -> Result<ClientCredentialsState> {
+    read_state(|s| rail.state(s).cloned()).ok_or_else(|| {
+        SystemError::InternalError(format!("{} is not configured", rail.name())).into()
+    })
+}
+
+pub fn get_token(rail: ClientCredentialsRail) -> Option<(String, u64)> {
+    read_state(|s| {
+        let state = rail.state(s)?;
+        Some((state.access_token.clone()?, state.token_expiration?))
+    })
+}
+
+pub fn set_token(rail: ClientCredentialsRail, token: String, expiration: u64) {
+    mutate_state(|s| {
+        if let Some(state) = rail.state_mut(s) {
+            state.access_token = Some(token);
+            state.token_expiration = Some(expiration);
+        }
+    });
+}
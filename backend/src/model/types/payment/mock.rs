@@ -0,0 +1,87 @@
+use candid::{CandidType, Deserialize};
+
+use crate::model::{
+    memory::heap::{mutate_state, read_state},
+    types::orders::LockedOrder,
+};
+
+/// Outcome a simulated payment is set up to produce when verified.
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MockOutcome {
+    Success,
+    WrongAmount,
+    WrongPayer,
+    Pending,
+}
+
+/// Payment simulated on the mock rail. Kept in the heap state so simulated
+/// payments survive upgrades of a local deployment.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct MockPayment {
+    pub order_id: u64,
+    pub amount: u64, // cents
+    pub currency: String,
+    pub payer: String,
+    pub payee: String,
+    pub reference: Option<String>,
+    pub pending: bool,
+}
+
+impl MockPayment {
+    /// Payment of the outstanding amount of `order` from `payer` to `payee`,
+    /// altered to produce `outcome` when verified.
+    pub fn new(order: &LockedOrder, payer: &str, payee: &str, outcome: MockOutcome) -> Self {
+        let mut payment = MockPayment {
+            order_id: order.base.id,
+            amount: order.outstanding_amount(),
+            currency: order.base.currency.clone(),
+            payer: payer.to_string(),
+            payee: payee.to_string(),
+            reference: order.payment_reference.clone(),
+            pending: false,
+        };
+        match outcome {
+            MockOutcome::Success => {}
+            MockOutcome::WrongAmount => payment.amount -= payment.amount.min(100),
+            MockOutcome::WrongPayer => payment.payer = format!("not-{}", payment.payer),
+            MockOutcome::Pending => payment.pending = true,
+        }
+        payment
+    }
+
+    /// Whether this payment went from `payer` to `payee` for `order`. The amount
+    /// is checked separately as it may be paid in another currency.
+    pub fn pays(&self, order: &LockedOrder, payer: &str, payee: &str) -> bool {
+        self.order_id == order.base.id
+            && self.payer == payer
+            && self.payee == payee
+            && order.payment_reference_matches(self.reference.as_deref())
+    }
+}
+
+pub fn insert_payment(transaction_id: String, payment: MockPayment) {
+    mutate_state(|state| {
+        state
+            .mock_payments
+            .get_or_insert_with(Default::default)
+            .insert(transaction_id, payment);
+    });
+}
+
+pub fn get_payment(transaction_id: &str) -> Option<MockPayment> {
+    read_state(|state| {
+        state
+            .mock_payments
+            .as_ref()
+            .and_then(|payments| payments.get(transaction_id).cloned())
+    })
+}
+
+/// Marks a pending payment as settled, returning the id of the order it pays.
+pub fn settle_payment(transaction_id: &str) -> Option<u64> {
+    mutate_state(|state| {
+        let payment = state.mock_payments.as_mut()?.get_mut(transaction_id)?;
+        payment.pending = false;
+        Some(payment.order_id)
+    })
+}
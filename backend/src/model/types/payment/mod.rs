@@ -1,3 +1,8 @@
+pub mod client_credentials;
+#[cfg(feature = "mock-provider")]
+pub mod mock;
 pub mod paypal;
+pub mod pix;
 pub(super) mod providers;
 pub mod revolut;
+pub mod upi;
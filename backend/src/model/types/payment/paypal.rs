@@ -9,6 +9,7 @@ pub struct PayPalState {
     pub client_id: String,
     pub client_secret: String,
     pub api_url: String,
+    pub webhook_id: Option<String>, // id of the registered webhook, required to verify its events
 }
 
 pub fn get_paypal_token() -> Option<(String, u64)> {
@@ -0,0 +1,115 @@
+use candid::{CandidType, Deserialize};
+
+use crate::{
+    errors::{Result, SystemError},
+    helpers,
+};
+
+const PIX_GUI: &str = "br.gov.bcb.pix";
+const MAX_MERCHANT_NAME_LEN: usize = 25;
+const MAX_MERCHANT_CITY_LEN: usize = 15;
+
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub enum PixKeyType {
+    Cpf,
+    Cnpj,
+    Email,
+    Phone,
+    Evp, // random key issued by the PSP
+}
+
+impl PixKeyType {
+    pub fn validate_key(&self, key: &str) -> Result<()> {
+        let is_digits =
+            |s: &str, len: usize| s.len() == len && s.chars().all(|c| c.is_ascii_digit());
+        let valid = match self {
+            PixKeyType::Cpf => is_digits(key, 11),
+            PixKeyType::Cnpj => is_digits(key, 14),
+            PixKeyType::Email => helpers::validate_email(key).is_ok(),
+            PixKeyType::Phone => key
+                .strip_prefix("+55")
+                .is_some_and(|number| is_digits(number, 10) || is_digits(number, 11)),
+            PixKeyType::Evp => uuid_like(key),
+        };
+        if !valid {
+            return Err(SystemError::InvalidInput(format!("Invalid PIX {:?} key", self)).into());
+        }
+        Ok(())
+    }
+}
+
+/// Whether the payer the PSP reported for a PIX is the holder of the onramper's
+/// key: by tax id for CPF and CNPJ keys, by name for the other key types.
+pub fn payer_matches(
+    key_type: &PixKeyType,
+    key: &str,
+    name: &str,
+    payer_document: Option<&str>,
+    payer_name: Option<&str>,
+) -> bool {
+    match key_type {
+        PixKeyType::Cpf | PixKeyType::Cnpj => payer_document == Some(key),
+        _ => payer_name.is_some_and(|payer_name| {
+            let normalize = |name: &str| name.split_whitespace().collect::<Vec<_>>().join(" ");
+            normalize(payer_name).eq_ignore_ascii_case(&normalize(name))
+        }),
+    }
+}
+
+fn uuid_like(key: &str) -> bool {
+    let groups: Vec<&str> = key.split('-').collect();
+    groups.iter().map(|g| g.len()).eq([8, 4, 4, 4, 12])
+        && groups
+            .iter()
+            .all(|g| g.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// Builds the BR Code (EMV QR "copia e cola") payload of a dynamic PIX charge.
+/// The payer's bank fetches the amount and txid of the charge from `location`,
+/// the URL of its payload on the PSP without scheme.
+pub fn brcode_payload(location: &str, name: &str, city: &str) -> String {
+    let merchant_account = format!("{}{}", emv_field("00", PIX_GUI), emv_field("25", location));
+
+    let mut payload = [
+        emv_field("00", "01"),
+        emv_field("01", "12"),
+        emv_field("26", &merchant_account),
+        emv_field("52", "0000"),
+        emv_field("53", "986"),
+        emv_field("58", "BR"),
+        emv_field("59", &emv_text(name, MAX_MERCHANT_NAME_LEN)),
+        emv_field("60", &emv_text(city, MAX_MERCHANT_CITY_LEN)),
+        emv_field("62", &emv_field("05", "***")),
+    ]
+    .concat();
+
+    payload.push_str("6304");
+    let crc = crc16_ccitt(payload.as_bytes());
+    payload.push_str(&format!("{:04X}", crc));
+    payload
+}
+
+fn emv_field(id: &str, value: &str) -> String {
+    format!("{}{:02}{}", id, value.len(), value)
+}
+
+/// Restricts free text to the ASCII subset allowed in a BR Code.
+fn emv_text(text: &str, max_len: usize) -> String {
+    text.chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == ' ')
+        .take(max_len)
+        .collect()
+}
+
+/// CRC16/CCITT-FALSE, the checksum of EMV merchant-presented QR codes.
+pub fn crc16_ccitt(data: &[u8]) -> u16 {
+    data.iter().fold(0xFFFF, |crc, byte| {
+        (0..8).fold(crc ^ ((*byte as u16) << 8), |crc, _| {
+            if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
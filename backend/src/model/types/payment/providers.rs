@@ -5,12 +5,19 @@ use std::{
 
 use candid::{CandidType, Deserialize};
 
-use crate::errors::{Result, SystemError};
+use super::pix::PixKeyType;
+use crate::{errors::Result, management::providers};
 
 #[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq, Hash)]
 pub enum PaymentProviderType {
     PayPal,
     Revolut,
+    Wise,
+    Pix,
+    Upi,
+    BankTransfer,
+    #[cfg(feature = "mock-provider")]
+    Mock,
 }
 
 #[derive(CandidType, Deserialize, Clone, Debug, Eq)]
@@ -23,6 +30,30 @@ pub enum PaymentProvider {
         id: String,
         name: Option<String>,
     },
+    Wise {
+        profile_id: String,
+        account_id: String, // IBAN or local account number transfers arrive at
+        name: Option<String>,
+    },
+    Pix {
+        key_type: PixKeyType,
+        key: String,
+        name: String, // receiver name and city are part of the BR Code
+        city: String,
+    },
+    Upi {
+        vpa: String,
+        name: Option<String>, // payee name shown in the onramper's UPI app
+    },
+    BankTransfer {
+        account_holder: String,
+        account_number: String, // IBAN or local account number
+        bank_name: Option<String>,
+    },
+    #[cfg(feature = "mock-provider")]
+    Mock {
+        id: String,
+    },
 }
 
 impl PartialEq for PaymentProvider {
@@ -42,25 +73,17 @@ impl PaymentProvider {
         match self {
             PaymentProvider::PayPal { .. } => PaymentProviderType::PayPal,
             PaymentProvider::Revolut { .. } => PaymentProviderType::Revolut,
+            PaymentProvider::Wise { .. } => PaymentProviderType::Wise,
+            PaymentProvider::Pix { .. } => PaymentProviderType::Pix,
+            PaymentProvider::Upi { .. } => PaymentProviderType::Upi,
+            PaymentProvider::BankTransfer { .. } => PaymentProviderType::BankTransfer,
+            #[cfg(feature = "mock-provider")]
+            PaymentProvider::Mock { .. } => PaymentProviderType::Mock,
         }
     }
 
     pub fn validate(&self) -> Result<()> {
-        match self {
-            PaymentProvider::PayPal { id } => {
-                if id.is_empty() {
-                    return Err(SystemError::InvalidInput("Paypal ID is empty".to_string()).into());
-                }
-            }
-            PaymentProvider::Revolut { scheme, id, .. } => {
-                if scheme.is_empty() || id.is_empty() {
-                    return Err(
-                        SystemError::InvalidInput("Revolut details are empty".to_string()).into(),
-                    );
-                }
-            }
-        }
-        Ok(())
+        providers::validate_account(self)
     }
 }
 
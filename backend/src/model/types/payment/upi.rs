@@ -0,0 +1,47 @@
+use crate::errors::{Result, SystemError};
+
+const MAX_VPA_LEN: usize = 255;
+
+/// Checks a virtual payment address of the form `name@handle`.
+pub fn validate_vpa(vpa: &str) -> Result<()> {
+    let valid = vpa.len() <= MAX_VPA_LEN
+        && vpa.split_once('@').is_some_and(|(name, handle)| {
+            name.len() >= 2
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+                && handle.len() >= 2
+                && handle.chars().all(|c| c.is_ascii_alphanumeric())
+        });
+    if !valid {
+        return Err(SystemError::InvalidInput("Invalid UPI VPA".to_string()).into());
+    }
+    Ok(())
+}
+
+/// Builds the `upi://pay` intent the onramper's UPI app opens to pay `amount`
+/// cents (paise) to `payee_vpa`, shown under `payee_name`.
+pub fn intent_uri(payee_vpa: &str, payee_name: &str, amount: u64, reference: &str) -> String {
+    format!(
+        "upi://pay?pa={}&pn={}&am={}.{:02}&cu=INR&tr={}&tn={}",
+        payee_vpa,
+        encode_query_value(payee_name),
+        amount / 100,
+        amount % 100,
+        reference,
+        reference
+    )
+}
+
+/// Percent-encodes everything but unreserved characters.
+fn encode_query_value(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
@@ -0,0 +1,77 @@
+use candid::{CandidType, Deserialize};
+
+use super::Blockchain;
+use crate::model::memory::heap::{mutate_state, read_state};
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct ReferralReward {
+    pub blockchain: Blockchain,
+    pub token: Option<String>, // For EVM tokens, this will be the contract address
+    pub amount: u128,
+}
+
+impl ReferralReward {
+    pub fn is_asset(&self, blockchain: &Blockchain, token: &Option<String>) -> bool {
+        self.blockchain == *blockchain
+            && self.token.as_ref().map(|t| t.to_lowercase())
+                == token.as_ref().map(|t| t.to_lowercase())
+    }
+}
+
+/// EVM referral reward on its way to the referrer: first withdrawn from the vault
+/// fees to the canister address, then transferred. The reward balance is only
+/// debited once the transfer is confirmed.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct ReferralClaim {
+    pub user_id: u64,
+    pub chain_id: u64,
+    pub token: Option<String>,
+    pub amount: u128,
+    pub address: String,
+}
+
+impl ReferralClaim {
+    pub fn blockchain(&self) -> Blockchain {
+        Blockchain::EVM {
+            chain_id: self.chain_id,
+        }
+    }
+}
+
+pub fn insert_claim(claim_id: u64, claim: ReferralClaim) {
+    mutate_state(|state| {
+        state
+            .referral_claims
+            .get_or_insert_with(Default::default)
+            .insert(claim_id, claim);
+    });
+}
+
+pub fn get_claim(claim_id: u64) -> Option<ReferralClaim> {
+    read_state(|state| {
+        state
+            .referral_claims
+            .as_ref()
+            .and_then(|claims| claims.get(&claim_id).cloned())
+    })
+}
+
+pub fn remove_claim(claim_id: u64) -> Option<ReferralClaim> {
+    mutate_state(|state| {
+        state
+            .referral_claims
+            .as_mut()
+            .and_then(|claims| claims.remove(&claim_id))
+    })
+}
+
+pub fn has_pending_claim(user_id: u64, chain_id: u64, token: &Option<String>) -> bool {
+    read_state(|state| {
+        state.referral_claims.iter().flatten().any(|(_, claim)| {
+            claim.user_id == user_id
+                && claim.chain_id == chain_id
+                && claim.token.as_ref().map(|t| t.to_lowercase())
+                    == token.as_ref().map(|t| t.to_lowercase())
+        })
+    })
+}
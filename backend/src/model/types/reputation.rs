@@ -0,0 +1,86 @@
+use candid::{CandidType, Deserialize};
+
+// weights halve every 180 days, so old behaviour weighs less than recent one
+const HALF_LIFE_SECS: f64 = 180. * 24. * 3600.;
+const TRADE_REWARD: f64 = 1.;
+const TIMEOUT_PENALTY: f64 = 1.;
+const DISPUTE_PENALTY: f64 = 2.;
+
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct Reputation {
+    pub onramper_trades: u32,
+    pub offramper_trades: u32,
+    pub timeouts: u32,
+    pub disputes_lost: u32,
+    pub total_payment_secs: u64, // from lock to payment, as onramper
+    pub average_payment_secs: Option<u64>,
+    pub positive_weight: f64, // decayed up to `updated_at`
+    pub negative_weight: f64, // decayed up to `updated_at`
+    pub updated_at: u64,      // nanoseconds
+}
+
+impl Reputation {
+    /// Seeds the weights from the score users had before reputation was tracked.
+    pub fn from_score(score: i32, now: u64) -> Self {
+        Reputation {
+            positive_weight: (score - 1).max(0) as f64,
+            negative_weight: (1 - score).max(0) as f64,
+            updated_at: now,
+            ..Default::default()
+        }
+    }
+
+    fn decay_factor(&self, now: u64) -> f64 {
+        let elapsed_secs = now.saturating_sub(self.updated_at) as f64 / 1_000_000_000.;
+        0.5_f64.powf(elapsed_secs / HALF_LIFE_SECS)
+    }
+
+    fn decay(&mut self, now: u64) {
+        let factor = self.decay_factor(now);
+        self.positive_weight *= factor;
+        self.negative_weight *= factor;
+        self.updated_at = now;
+    }
+
+    pub fn record_onramper_trade(&mut self, payment_secs: u64, now: u64) {
+        self.decay(now);
+        self.onramper_trades += 1;
+        self.total_payment_secs += payment_secs;
+        self.average_payment_secs = Some(self.total_payment_secs / self.onramper_trades as u64);
+        self.positive_weight += TRADE_REWARD;
+    }
+
+    pub fn record_offramper_trade(&mut self, now: u64) {
+        self.decay(now);
+        self.offramper_trades += 1;
+        self.positive_weight += TRADE_REWARD;
+    }
+
+    pub fn record_timeout(&mut self, now: u64) {
+        self.decay(now);
+        self.timeouts += 1;
+        self.negative_weight += TIMEOUT_PENALTY;
+    }
+
+    pub fn record_dispute_lost(&mut self, now: u64) {
+        self.decay(now);
+        self.disputes_lost += 1;
+        self.negative_weight += DISPUTE_PENALTY;
+    }
+
+    /// Manual correction by an admin, decaying like any other event.
+    pub fn adjust(&mut self, delta: i32, now: u64) {
+        self.decay(now);
+        if delta >= 0 {
+            self.positive_weight += delta as f64;
+        } else {
+            self.negative_weight += delta.unsigned_abs() as f64;
+        }
+    }
+
+    /// Score derived from the decayed weights, starting at 1 for new users.
+    pub fn score(&self, now: u64) -> i32 {
+        let factor = self.decay_factor(now);
+        (1. + (self.positive_weight - self.negative_weight) * factor).round() as i32
+    }
+}
@@ -7,17 +7,22 @@ use std::{
 
 use super::{
     common::{LoginAddress, TransactionAddress},
+    evm::logs::EvmTransactionLog,
+    kyc::{KycReservation, KycTier, KycVolume},
+    orders::{OrderId, OrderState},
+    referral::ReferralReward,
+    reputation::Reputation,
     session::Session,
-    AuthenticationData, PaymentProvider,
+    AuthenticationData, Blockchain, PaymentProvider,
 };
 use crate::{
     errors::{BlockchainError, Result, SystemError, UserError},
     evm::signer,
-    management::random,
+    management::{random, totp},
     model::memory,
 };
 
-const MAX_USER_SIZE: u32 = 1000;
+const MAX_USER_SIZE: u32 = 4000;
 
 #[derive(CandidType, Deserialize, Clone, Debug)]
 pub enum UserType {
@@ -32,13 +37,60 @@ pub struct User {
     pub payment_providers: HashSet<PaymentProvider>,
     pub addresses: HashSet<TransactionAddress>,
     pub fiat_amounts: HashMap<String, u64>, // offramped or onramped funds
-    pub score: i32,
+    pub score: i32,                         // derived from `reputation`
+    pub reputation: Option<Reputation>,
+    pub kyc_tier: Option<KycTier>,     // unverified if not set
+    pub kyc_volume: Option<KycVolume>, // traded volume for the tier limits
+    pub frozen: Option<String>,        // reason given by the admin
+    pub referrer_id: Option<u64>,
+    pub referral_rewards: Option<Vec<ReferralReward>>, // claimable share of referees' fees
     pub login: LoginAddress,
-    pub hashed_password: Option<String>,  // for email login
-    pub evm_auth_message: Option<String>, // for EVM login, unique per session
+    pub linked_logins: Option<Vec<LoginAddress>>, // additional login methods
+    pub pending_email_link: Option<PendingEmailLink>, // email login awaiting its code
+    pub hashed_password: Option<String>,          // for email login
+    pub evm_auth_message: Option<String>,         // for EVM login, unique per session
+    pub totp: Option<TotpConfig>,                 // second factor for email login
     pub session: Option<Session>,
 }
 
+#[derive(CandidType, Clone)]
+pub struct UserDataExport {
+    pub user: User,
+    pub orders: Vec<(OrderId, OrderState)>,
+    pub payment_receipts: Vec<PaymentReceipt>,
+    pub transaction_logs: Vec<EvmTransactionLog>, // for orders with a recent EVM transaction
+}
+
+/// Provider payment consumed by one of the user's orders.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct PaymentReceipt {
+    pub order_id: OrderId,
+    pub provider: String,
+    pub payment_id: String,
+}
+
+/// Email login the user asked to link, linked once they confirm the code sent
+/// to the address.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct PendingEmailLink {
+    pub login_address: LoginAddress,
+    pub hashed_password: String,
+    pub code: String,
+    pub expires_at: u64,
+}
+
+impl PendingEmailLink {
+    pub(crate) const EXPIRATION_SECS: u64 = 1800;
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct TotpConfig {
+    pub secret: String, // base32
+    pub enabled: bool,
+    pub last_used_step: Option<u64>,
+    pub recovery_codes: Vec<String>, // sha256 hashes, single use
+}
+
 impl User {
     pub fn new(
         user_type: UserType,
@@ -59,9 +111,18 @@ impl User {
             payment_providers: HashSet::new(),
             fiat_amounts: HashMap::new(),
             score: 1,
+            reputation: None,
+            kyc_tier: None,
+            kyc_volume: None,
+            frozen: None,
+            referrer_id: None,
+            referral_rewards: None,
             login: login_address,
+            linked_logins: None,
+            pending_email_link: None,
             hashed_password,
             evm_auth_message: None,
+            totp: None,
             addresses,
             session: None,
         })
@@ -81,8 +142,81 @@ impl User {
         }
     }
 
-    pub fn verify_user_auth(&self, auth_data: Option<AuthenticationData>) -> Result<()> {
-        match &self.login {
+    pub fn logins(&self) -> Vec<LoginAddress> {
+        let mut logins = vec![self.login.clone()];
+        logins.extend(self.linked_logins.clone().unwrap_or_default());
+        logins
+    }
+
+    pub fn has_login(&self, login_address: &LoginAddress) -> bool {
+        self.logins().contains(login_address)
+    }
+
+    /// Attaches an additional login method to the user.
+    ///
+    /// Only one login address per type is allowed, so that each authentication
+    /// path (password, signature, principal) resolves to a single credential.
+    pub fn link_login(&mut self, login_address: LoginAddress) -> Result<()> {
+        if self
+            .logins()
+            .iter()
+            .any(|login| login.login_type() == login_address.login_type())
+        {
+            return Err(UserError::LoginTypeAlreadyLinked.into());
+        }
+
+        if let Ok(address) = login_address.to_transaction_address() {
+            if !self.addresses.contains(&address) {
+                self.addresses.insert(address);
+            }
+        }
+
+        self.linked_logins
+            .get_or_insert_with(Vec::new)
+            .push(login_address);
+        Ok(())
+    }
+
+    pub fn unlink_login(&mut self, login_address: &LoginAddress) -> Result<()> {
+        if self.login == *login_address {
+            return Err(UserError::CannotUnlinkPrimaryLogin.into());
+        }
+
+        let linked_logins = self.linked_logins.get_or_insert_with(Vec::new);
+        let index = linked_logins
+            .iter()
+            .position(|login| login == login_address)
+            .ok_or(UserError::LoginAddressNotLinked)?;
+        linked_logins.remove(index);
+
+        // drop the address `link_login` added, unless the user replaced it since
+        if let Ok(address) = login_address.to_transaction_address() {
+            if self
+                .addresses
+                .get(&address)
+                .is_some_and(|existing| existing.address == address.address)
+            {
+                self.addresses.remove(&address);
+            }
+        }
+
+        if let LoginAddress::Email { .. } = login_address {
+            self.hashed_password = None;
+            self.totp = None;
+        }
+        Ok(())
+    }
+
+    pub fn verify_user_auth(
+        &self,
+        login_address: &LoginAddress,
+        auth_data: Option<AuthenticationData>,
+    ) -> Result<()> {
+        if !self.has_login(login_address) {
+            return Err(UserError::LoginAddressNotLinked.into());
+        }
+
+        match login_address {
             LoginAddress::Email { .. } => {
                 let password = auth_data
                     .ok_or(UserError::PasswordRequired)?
@@ -137,6 +271,35 @@ impl User {
         Ok(())
     }
 
+    pub fn is_totp_enabled(&self) -> bool {
+        self.totp.as_ref().is_some_and(|totp| totp.enabled)
+    }
+
+    /// Verifies a TOTP code or, failing that, consumes a matching recovery code.
+    pub fn verify_totp(&mut self, code: &str, now: u64) -> Result<()> {
+        let totp = self.totp.as_mut().ok_or(UserError::TotpNotEnabled)?;
+
+        if let Some(step) = totp::verify_code(&totp.secret, code, now)? {
+            if totp
+                .last_used_step
+                .is_some_and(|last_step| step <= last_step)
+            {
+                return Err(UserError::InvalidTotpCode.into());
+            }
+            totp.last_used_step = Some(step);
+            return Ok(());
+        }
+
+        let hashed_code = totp::hash_recovery_code(code);
+        let index = totp
+            .recovery_codes
+            .iter()
+            .position(|recovery_code| *recovery_code == hashed_code)
+            .ok_or(UserError::InvalidTotpCode)?;
+        totp.recovery_codes.remove(index);
+        Ok(())
+    }
+
     pub fn validate_session(&self, token: &str) -> Result<()> {
         self.session
             .as_ref()
@@ -148,16 +311,165 @@ impl User {
         *self.fiat_amounts.entry(currency.to_string()).or_insert(0) += amount;
     }
 
-    pub fn decrease_score(&mut self) {
-        self.score -= 1;
+    fn reputation_mut(&mut self, now: u64) -> &mut Reputation {
+        let score = self.score;
+        self.reputation
+            .get_or_insert_with(|| Reputation::from_score(score, now))
+    }
+
+    pub fn current_score(&self, now: u64) -> i32 {
+        self.reputation
+            .as_ref()
+            .map_or(self.score, |reputation| reputation.score(now))
+    }
+
+    /// Copy of the user safe to return to clients, without its credentials.
+    ///
+    /// The session is dropped as well; endpoints issuing a new session put it back.
+    pub fn redacted(mut self) -> Self {
+        self.hashed_password = None;
+        if let Some(totp) = self.totp.as_mut() {
+            totp.secret.clear();
+            totp.recovery_codes.clear();
+        }
+        if let Some(link) = self.pending_email_link.as_mut() {
+            link.hashed_password.clear();
+            link.code.clear();
+        }
+        self.session = None;
+        self
+    }
+
+    /// Applies the time decay to the exposed `score`.
+    pub fn refresh_score(&mut self, now: u64) {
+        self.score = self.current_score(now);
+    }
+
+    pub fn record_onramper_trade(&mut self, payment_secs: u64, now: u64) {
+        self.reputation_mut(now)
+            .record_onramper_trade(payment_secs, now);
+        self.refresh_score(now);
+    }
+
+    pub fn record_offramper_trade(&mut self, now: u64) {
+        self.reputation_mut(now).record_offramper_trade(now);
+        self.refresh_score(now);
     }
 
-    pub fn increase_score(&mut self) {
-        self.score += 1;
+    pub fn record_timeout(&mut self, now: u64) {
+        self.reputation_mut(now).record_timeout(now);
+        self.refresh_score(now);
+    }
+
+    pub fn record_dispute_lost(&mut self, now: u64) {
+        self.reputation_mut(now).record_dispute_lost(now);
+        self.refresh_score(now);
+    }
+
+    pub fn adjust_reputation(&mut self, delta: i32, now: u64) {
+        self.reputation_mut(now).adjust(delta, now);
+        self.refresh_score(now);
+    }
+
+    pub fn kyc_tier(&self) -> KycTier {
+        self.kyc_tier.clone().unwrap_or_default()
+    }
+
+    /// Checks an order amount (in cents of `KYC_LIMITS_CURRENCY`) against the tier limits.
+    pub fn check_kyc_limits(&self, amount: u64, now: u64) -> Result<()> {
+        self.kyc_volume
+            .clone()
+            .unwrap_or_default()
+            .check_limits(&self.kyc_tier(), amount, now)
+    }
+
+    /// Checks an order amount against the tier limits and books it in the same step,
+    /// so that concurrent orders cannot all pass the same remaining volume.
+    pub fn reserve_kyc_volume(&mut self, amount: u64, now: u64) -> Result<Option<KycReservation>> {
+        if self.kyc_tier().limits().is_none() {
+            return Ok(None);
+        }
+        self.check_kyc_limits(amount, now)?;
+        Ok(Some(
+            self.kyc_volume
+                .get_or_insert_with(KycVolume::default)
+                .record(amount, now),
+        ))
+    }
+
+    pub fn release_kyc_volume(&mut self, reservation: &KycReservation) {
+        if let Some(volume) = self.kyc_volume.as_mut() {
+            volume.release(reservation);
+        }
+    }
+
+    pub fn accrue_referral_reward(
+        &mut self,
+        blockchain: &Blockchain,
+        token: &Option<String>,
+        amount: u128,
+    ) {
+        let rewards = self.referral_rewards.get_or_insert_with(Vec::new);
+        match rewards
+            .iter_mut()
+            .find(|reward| reward.is_asset(blockchain, token))
+        {
+            Some(reward) => reward.amount += amount,
+            None => rewards.push(ReferralReward {
+                blockchain: blockchain.clone(),
+                token: token.clone(),
+                amount,
+            }),
+        }
+    }
+
+    pub fn referral_reward(&self, blockchain: &Blockchain, token: &Option<String>) -> Result<u128> {
+        self.referral_rewards
+            .iter()
+            .flatten()
+            .find(|reward| reward.is_asset(blockchain, token) && reward.amount > 0)
+            .map(|reward| reward.amount)
+            .ok_or(UserError::NoReferralRewards.into())
+    }
+
+    /// Debits a confirmed payout, keeping the rewards accrued in the meantime.
+    pub fn debit_referral_reward(
+        &mut self,
+        blockchain: &Blockchain,
+        token: &Option<String>,
+        amount: u128,
+    ) {
+        if let Some(reward) = self
+            .referral_rewards
+            .iter_mut()
+            .flatten()
+            .find(|reward| reward.is_asset(blockchain, token))
+        {
+            reward.amount = reward.amount.saturating_sub(amount);
+        }
+    }
+
+    /// Empties the reward balance for the given asset, returning the claimed amount.
+    pub fn take_referral_reward(
+        &mut self,
+        blockchain: &Blockchain,
+        token: &Option<String>,
+    ) -> Result<u128> {
+        let reward = self
+            .referral_rewards
+            .iter_mut()
+            .flatten()
+            .find(|reward| reward.is_asset(blockchain, token) && reward.amount > 0)
+            .ok_or(UserError::NoReferralRewards)?;
+
+        Ok(std::mem::take(&mut reward.amount))
     }
 
     pub fn is_banned(&self) -> Result<()> {
-        if self.score < 0 {
+        if let Some(reason) = &self.frozen {
+            return Err(UserError::UserFrozen(reason.clone()).into());
+        }
+        if self.current_score(ic_cdk::api::time()) < 0 {
             return Err(UserError::UserBanned.into());
         }
         Ok(())
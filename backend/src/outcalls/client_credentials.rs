@@ -0,0 +1,89 @@
+use base64::{engine::general_purpose, Engine as _};
+use serde::{Deserialize, Serialize};
+
+use ic_cdk::api::management_canister::http_request::{
+    http_request, CanisterHttpRequestArgument, HttpHeader, HttpMethod,
+};
+use ic_cdk::api::time;
+
+use crate::{
+    errors::{Result, SystemError},
+    model::memory::heap::read_state,
+    types::payment::client_credentials::{self, ClientCredentialsRail},
+};
+
+#[derive(Serialize, Deserialize)]
+struct AccessTokenResponse {
+    access_token: String,
+    token_type: String,
+    expires_in: u64,
+}
+
+/// Returns the rail's cached access token, fetching a new one through the
+/// proxy once it expired.
+pub async fn get_access_token(rail: ClientCredentialsRail) -> Result<String> {
+    if let Some((token, expiration)) = client_credentials::get_token(rail) {
+        if time() / 1_000_000_000 < expiration {
+            return Ok(token);
+        }
+    }
+
+    ic_cdk::println!("[get_access_token] Fetching new token from {}", rail.name());
+    let state = client_credentials::get_state(rail)?;
+    let proxy_url = read_state(|s| s.proxy_url.clone());
+    let credentials =
+        general_purpose::STANDARD.encode(format!("{}:{}", state.client_id, state.client_secret));
+
+    let request_headers = vec![
+        HttpHeader {
+            name: "Content-Type".to_string(),
+            value: "application/x-www-form-urlencoded".to_string(),
+        },
+        HttpHeader {
+            name: "Authorization".to_string(),
+            value: format!("Basic {}", credentials),
+        },
+        HttpHeader {
+            name: "x-forwarded-host".to_string(),
+            value: state.api_url,
+        },
+        HttpHeader {
+            name: "idempotency-key".to_string(),
+            value: format!("{}-auth-key-0", rail.name().to_lowercase()),
+        },
+    ];
+
+    let request = CanisterHttpRequestArgument {
+        url: format!("{}/oauth/token", proxy_url),
+        method: HttpMethod::POST,
+        body: Some("grant_type=client_credentials".as_bytes().to_vec()),
+        max_response_bytes: Some(4096),
+        transform: None,
+        headers: request_headers,
+    };
+
+    let cycles: u128 = 21_000_000_000;
+    match http_request(request, cycles).await {
+        Ok((response,)) => {
+            let str_body = String::from_utf8(response.body).map_err(|_| SystemError::Utf8Error)?;
+
+            let access_token_response: AccessTokenResponse = serde_json::from_str(&str_body)
+                .map_err(|e| SystemError::ParseError(e.to_string()))?;
+
+            let expiration_time = access_token_response.expires_in + time() / 1_000_000_000;
+            ic_cdk::println!(
+                "[get_access_token] New {} token expiration time: {}",
+                rail.name(),
+                expiration_time
+            );
+            client_credentials::set_token(
+                rail,
+                access_token_response.access_token.clone(),
+                expiration_time,
+            );
+
+            Ok(access_token_response.access_token)
+        }
+        Err((r, m)) => Err(SystemError::HttpRequestError(r as u64, m).into()),
+    }
+}
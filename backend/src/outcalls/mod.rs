@@ -1,3 +1,7 @@
+pub mod client_credentials;
 pub mod paypal;
+pub mod pix;
 pub mod revolut;
+pub mod upi;
+pub mod wise;
 pub mod xrc_rates;
@@ -1,2 +1,4 @@
 pub mod auth;
 pub mod order;
+pub mod refund;
+pub mod webhook;
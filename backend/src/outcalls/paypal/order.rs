@@ -18,20 +18,23 @@ pub struct PayPalOrderDetails {
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct PurchaseUnit {
+    pub custom_id: Option<String>,
+    pub invoice_id: Option<String>,
     pub amount: Amount,
     pub payee: Payee,
+    #[serde(default)]
     pub payments: Payments,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Default)]
 pub struct Payments {
     pub captures: Vec<Capture>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Capture {
-    id: String,
-    status: String,
+    pub id: String,
+    pub status: String,
     pub amount: Amount,
 }
 
@@ -98,3 +101,78 @@ pub async fn fetch_paypal_order(access_token: &str, order_id: &str) -> Result<Pa
         Err((r, m)) => Err(SystemError::HttpRequestError(r as u64, m).into()),
     }
 }
+
+#[derive(Serialize, Debug)]
+pub struct CreateOrderRequest {
+    pub intent: String,
+    pub purchase_units: Vec<PurchaseUnitRequest>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PurchaseUnitRequest {
+    pub custom_id: Option<String>,
+    pub invoice_id: Option<String>,
+    pub amount: Amount,
+    pub payee: Payee,
+}
+
+#[derive(Deserialize, Debug)]
+struct CreatedOrder {
+    id: String,
+}
+
+/// Creates a PayPal order for the onramper to approve, returning its id.
+pub async fn create_paypal_order(
+    access_token: &str,
+    order_request: &CreateOrderRequest,
+    idempotency_key: &str,
+) -> Result<String> {
+    let (api_url, proxy_url) = read_state(|s| (s.paypal.api_url.clone(), s.proxy_url.clone()));
+
+    let request_headers = vec![
+        HttpHeader {
+            name: "Content-Type".to_string(),
+            value: "application/json".to_string(),
+        },
+        HttpHeader {
+            name: "Authorization".to_string(),
+            value: format!("Bearer {}", access_token),
+        },
+        HttpHeader {
+            name: "x-forwarded-host".to_string(),
+            value: api_url,
+        },
+        HttpHeader {
+            name: "PayPal-Request-Id".to_string(),
+            value: idempotency_key.to_string(),
+        },
+        HttpHeader {
+            name: "idempotency-key".to_string(),
+            value: idempotency_key.to_string(),
+        },
+    ];
+
+    let body = serde_json::to_vec(order_request).map_err(SystemError::from)?;
+    let request = CanisterHttpRequestArgument {
+        url: format!("{}/v2/checkout/orders", proxy_url),
+        method: HttpMethod::POST,
+        body: Some(body),
+        max_response_bytes: Some(4096),
+        transform: None,
+        headers: request_headers,
+    };
+
+    let cycles = 10_000_000_000;
+    match http_request(request, cycles).await {
+        Ok((response,)) => {
+            let str_body = String::from_utf8(response.body).map_err(|_| SystemError::Utf8Error)?;
+            ic_cdk::println!("[create_paypal_order] str_body = {:?}", str_body);
+
+            let created_order: CreatedOrder = serde_json::from_str(&str_body)
+                .map_err(|e| SystemError::ParseError(e.to_string()))?;
+
+            Ok(created_order.id)
+        }
+        Err((r, m)) => Err(SystemError::HttpRequestError(r as u64, m).into()),
+    }
+}
@@ -0,0 +1,89 @@
+use base64::{engine::general_purpose, Engine as _};
+use ic_cdk::api::management_canister::http_request::{
+    http_request, CanisterHttpRequestArgument, HttpHeader, HttpMethod,
+};
+use serde::Deserialize;
+
+use crate::{
+    errors::{Result, SystemError},
+    model::memory::heap::read_state,
+};
+
+#[derive(Deserialize, Debug)]
+pub struct CaptureRefund {
+    pub id: String,
+    pub status: String,
+}
+
+/// Assertion letting the platform act on behalf of the merchant `payee`.
+fn auth_assertion(client_id: &str, payee: &str) -> String {
+    let header = general_purpose::URL_SAFE_NO_PAD.encode(r#"{"alg":"none"}"#);
+    let payload = general_purpose::URL_SAFE_NO_PAD
+        .encode(serde_json::json!({ "iss": client_id, "email": payee }).to_string());
+    format!("{}.{}.", header, payload)
+}
+
+/// Refunds in full the capture `capture_id`, received by the merchant `payee`.
+pub async fn refund_capture(
+    access_token: &str,
+    capture_id: &str,
+    payee: &str,
+) -> Result<CaptureRefund> {
+    let (client_id, api_url, proxy_url) = read_state(|s| {
+        (
+            s.paypal.client_id.clone(),
+            s.paypal.api_url.clone(),
+            s.proxy_url.clone(),
+        )
+    });
+
+    let request_headers = vec![
+        HttpHeader {
+            name: "Content-Type".to_string(),
+            value: "application/json".to_string(),
+        },
+        HttpHeader {
+            name: "Authorization".to_string(),
+            value: format!("Bearer {}", access_token),
+        },
+        HttpHeader {
+            name: "PayPal-Auth-Assertion".to_string(),
+            value: auth_assertion(&client_id, payee),
+        },
+        HttpHeader {
+            name: "x-forwarded-host".to_string(),
+            value: api_url,
+        },
+        HttpHeader {
+            name: "PayPal-Request-Id".to_string(),
+            value: format!("refund-{}", capture_id),
+        },
+        HttpHeader {
+            name: "idempotency-key".to_string(),
+            value: format!("refund-key-{}", capture_id),
+        },
+    ];
+
+    let request = CanisterHttpRequestArgument {
+        url: format!("{}/v2/payments/captures/{}/refund", proxy_url, capture_id),
+        method: HttpMethod::POST,
+        body: Some(b"{}".to_vec()),
+        max_response_bytes: Some(4096),
+        transform: None,
+        headers: request_headers,
+    };
+
+    let cycles = 10_000_000_000;
+    match http_request(request, cycles).await {
+        Ok((response,)) => {
+            let str_body = String::from_utf8(response.body).map_err(|_| SystemError::Utf8Error)?;
+            ic_cdk::println!("[refund_capture] str_body = {:?}", str_body);
+
+            let refund: CaptureRefund = serde_json::from_str(&str_body)
+                .map_err(|e| SystemError::ParseError(e.to_string()))?;
+
+            Ok(refund)
+        }
+        Err((r, m)) => Err(SystemError::HttpRequestError(r as u64, m).into()),
+    }
+}
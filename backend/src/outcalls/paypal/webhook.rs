@@ -0,0 +1,148 @@
+use ic_cdk::api::management_canister::http_request::{
+    http_request, CanisterHttpRequestArgument, HttpHeader, HttpMethod,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+
+use crate::{
+    errors::{Result, SystemError},
+    model::memory::heap::read_state,
+};
+
+pub const PAYMENT_CAPTURE_COMPLETED: &str = "PAYMENT.CAPTURE.COMPLETED";
+const VERIFICATION_SUCCESS: &str = "SUCCESS";
+
+#[derive(Deserialize, Debug)]
+pub struct WebhookEvent {
+    pub id: String,
+    pub event_type: String,
+    pub resource: CaptureResource,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CaptureResource {
+    pub id: String,
+    pub custom_id: Option<String>,
+    pub invoice_id: Option<String>,
+    pub supplementary_data: Option<SupplementaryData>,
+}
+
+impl CaptureResource {
+    /// Payment reference the onramper attached to the PayPal order.
+    pub fn reference(&self) -> Option<&str> {
+        self.custom_id.as_deref().or(self.invoice_id.as_deref())
+    }
+
+    /// Id of the PayPal order the capture belongs to.
+    pub fn order_id(&self) -> Option<&str> {
+        self.supplementary_data
+            .as_ref()
+            .and_then(|data| data.related_ids.order_id.as_deref())
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SupplementaryData {
+    pub related_ids: RelatedIds,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RelatedIds {
+    pub order_id: Option<String>,
+}
+
+/// Transmission headers PayPal signs each webhook delivery with.
+#[derive(Serialize, Debug)]
+pub struct TransmissionHeaders {
+    pub auth_algo: String,
+    pub cert_url: String,
+    pub transmission_id: String,
+    pub transmission_sig: String,
+    pub transmission_time: String,
+}
+
+#[derive(Serialize)]
+struct VerificationRequest<'a> {
+    #[serde(flatten)]
+    headers: &'a TransmissionHeaders,
+    webhook_id: &'a str,
+    webhook_event: &'a RawValue,
+}
+
+#[derive(Deserialize)]
+struct VerificationResponse {
+    verification_status: String,
+}
+
+/// Body of a verify-webhook-signature request.
+pub fn verification_body(
+    headers: &TransmissionHeaders,
+    webhook_id: &str,
+    raw_event: &str,
+) -> Result<String> {
+    let webhook_event: &RawValue =
+        serde_json::from_str(raw_event).map_err(|e| SystemError::ParseError(e.to_string()))?;
+    serde_json::to_string(&VerificationRequest {
+        headers,
+        webhook_id,
+        webhook_event,
+    })
+    .map_err(|e| SystemError::ParseError(e.to_string()).into())
+}
+
+/// Checks the delivery signature with PayPal's verify-webhook-signature API.
+///
+/// `raw_event` is embedded as received, since re-serializing the event would
+/// change the payload the signature was computed over.
+pub async fn verify_webhook_signature(
+    access_token: &str,
+    webhook_id: &str,
+    headers: &TransmissionHeaders,
+    raw_event: &str,
+) -> Result<bool> {
+    let (api_url, proxy_url) = read_state(|s| (s.paypal.api_url.clone(), s.proxy_url.clone()));
+
+    let body = verification_body(headers, webhook_id, raw_event)?;
+
+    let request_headers = vec![
+        HttpHeader {
+            name: "Content-Type".to_string(),
+            value: "application/json".to_string(),
+        },
+        HttpHeader {
+            name: "Authorization".to_string(),
+            value: format!("Bearer {}", access_token),
+        },
+        HttpHeader {
+            name: "x-forwarded-host".to_string(),
+            value: api_url,
+        },
+        HttpHeader {
+            name: "idempotency-key".to_string(),
+            value: format!("webhook-key-{}", headers.transmission_id),
+        },
+    ];
+
+    let request = CanisterHttpRequestArgument {
+        url: format!("{}/v1/notifications/verify-webhook-signature", proxy_url),
+        method: HttpMethod::POST,
+        body: Some(body.into_bytes()),
+        max_response_bytes: Some(1024),
+        transform: None,
+        headers: request_headers,
+    };
+
+    let cycles = 10_000_000_000;
+    match http_request(request, cycles).await {
+        Ok((response,)) => {
+            let str_body = String::from_utf8(response.body).map_err(|_| SystemError::Utf8Error)?;
+            ic_cdk::println!("[verify_webhook_signature] str_body = {:?}", str_body);
+
+            let verification: VerificationResponse = serde_json::from_str(&str_body)
+                .map_err(|e| SystemError::ParseError(e.to_string()))?;
+
+            Ok(verification.verification_status == VERIFICATION_SUCCESS)
+        }
+        Err((r, m)) => Err(SystemError::HttpRequestError(r as u64, m).into()),
+    }
+}
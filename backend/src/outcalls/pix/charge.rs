@@ -0,0 +1,103 @@
+use ic_cdk::api::management_canister::http_request::{
+    http_request, CanisterHttpRequestArgument, HttpHeader, HttpMethod,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errors::{Result, SystemError},
+    model::memory::heap::read_state,
+    types::payment::client_credentials::{self, ClientCredentialsRail},
+};
+
+/// Body of `POST /v2/cob` of the BCB PIX API, an immediate charge.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PixChargeRequest {
+    pub calendario: PixChargeCalendar,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub devedor: Option<PixDebtor>,
+    pub valor: PixChargeValue,
+    pub chave: String,
+    pub solicitacao_pagador: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PixChargeCalendar {
+    pub expiracao: u64, // seconds
+}
+
+/// The payer expected by a charge, identified by tax id.
+#[derive(Serialize, Debug)]
+pub struct PixDebtor {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpf: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cnpj: Option<String>,
+    pub nome: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PixChargeValue {
+    pub original: String,
+}
+
+/// A charge created by the PSP, paid through the payload served at `location`.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PixCharge {
+    pub txid: String,
+    pub location: String,
+    pub status: String,
+    pub pix_copia_e_cola: Option<String>,
+}
+
+/// Creates an immediate charge on the PSP, which assigns its txid.
+pub async fn create_pix_charge(
+    access_token: &str,
+    charge_request: &PixChargeRequest,
+    idempotency_key: &str,
+) -> Result<PixCharge> {
+    let api_url = client_credentials::get_state(ClientCredentialsRail::Pix)?.api_url;
+    let proxy_url = read_state(|s| s.proxy_url.clone());
+
+    let request_headers = vec![
+        HttpHeader {
+            name: "Content-Type".to_string(),
+            value: "application/json".to_string(),
+        },
+        HttpHeader {
+            name: "Authorization".to_string(),
+            value: format!("Bearer {}", access_token),
+        },
+        HttpHeader {
+            name: "x-forwarded-host".to_string(),
+            value: api_url,
+        },
+        HttpHeader {
+            name: "idempotency-key".to_string(),
+            value: idempotency_key.to_string(),
+        },
+    ];
+
+    let body = serde_json::to_vec(charge_request).map_err(SystemError::from)?;
+    let request = CanisterHttpRequestArgument {
+        url: format!("{}/v2/cob", proxy_url),
+        method: HttpMethod::POST,
+        body: Some(body),
+        max_response_bytes: Some(4096),
+        transform: None,
+        headers: request_headers,
+    };
+
+    let cycles = 10_000_000_000;
+    match http_request(request, cycles).await {
+        Ok((response,)) => {
+            let str_body = String::from_utf8(response.body).map_err(|_| SystemError::Utf8Error)?;
+            ic_cdk::println!("[create_pix_charge] str_body = {:?}", str_body);
+
+            serde_json::from_str(&str_body)
+                .map_err(|e| SystemError::ParseError(e.to_string()).into())
+        }
+        Err((r, m)) => Err(SystemError::HttpRequestError(r as u64, m).into()),
+    }
+}
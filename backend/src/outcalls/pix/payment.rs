@@ -0,0 +1,77 @@
+use ic_cdk::api::management_canister::http_request::{
+    http_request, CanisterHttpRequestArgument, HttpHeader, HttpMethod,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errors::{Result, SystemError},
+    model::memory::heap::read_state,
+    types::payment::client_credentials::{self, ClientCredentialsRail},
+};
+
+/// A settled PIX as returned by `GET /v2/pix/{e2eid}` of the BCB PIX API.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PixPayment {
+    pub end_to_end_id: String,
+    pub txid: Option<String>,
+    pub valor: String,
+    pub chave: String,
+    pub horario: String,
+    pub pagador: Option<PixPayer>,
+}
+
+/// The payer of a PIX, as reported by the receiving PSP.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PixPayer {
+    pub cpf: Option<String>,
+    pub cnpj: Option<String>,
+    pub nome: Option<String>,
+}
+
+impl PixPayer {
+    pub fn document(&self) -> Option<&str> {
+        self.cpf.as_deref().or(self.cnpj.as_deref())
+    }
+}
+
+pub async fn fetch_pix_payment(access_token: &str, end_to_end_id: &str) -> Result<PixPayment> {
+    let api_url = client_credentials::get_state(ClientCredentialsRail::Pix)?.api_url;
+    let proxy_url = read_state(|s| s.proxy_url.clone());
+
+    let request_headers = vec![
+        HttpHeader {
+            name: "Content-Type".to_string(),
+            value: "application/json".to_string(),
+        },
+        HttpHeader {
+            name: "Authorization".to_string(),
+            value: format!("Bearer {}", access_token),
+        },
+        HttpHeader {
+            name: "x-forwarded-host".to_string(),
+            value: api_url,
+        },
+    ];
+
+    let request = CanisterHttpRequestArgument {
+        url: format!("{}/v2/pix/{}", proxy_url, end_to_end_id),
+        method: HttpMethod::GET,
+        body: None,
+        max_response_bytes: Some(4096),
+        transform: None,
+        headers: request_headers,
+    };
+
+    let cycles = 10_000_000_000;
+    match http_request(request, cycles).await {
+        Ok((response,)) => {
+            let str_body = String::from_utf8(response.body).map_err(|_| SystemError::Utf8Error)?;
+            ic_cdk::println!("[fetch_pix_payment] str_body = {:?}", str_body);
+
+            serde_json::from_str(&str_body)
+                .map_err(|e| SystemError::ParseError(e.to_string()).into())
+        }
+        Err((r, m)) => Err(SystemError::HttpRequestError(r as u64, m).into()),
+    }
+}
@@ -1,8 +1,10 @@
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 
 use crate::{
     errors::{Result, SystemError},
     model::memory::heap::read_state,
+    types::orders::RevolutConsentStatus,
 };
 
 use ic_cdk::api::management_canister::http_request::{
@@ -10,7 +12,7 @@ use ic_cdk::api::management_canister::http_request::{
     TransformContext, TransformFunc,
 };
 
-use super::jws;
+use super::{initiation::PaymentInitiation, jws};
 
 #[derive(Serialize, Deserialize, Debug)]
 struct ConsentIdResponse {
@@ -44,15 +46,7 @@ struct ErrorDetail {
     message: String,
 }
 
-pub async fn create_account_access_consent(
-    amount: &str,
-    currency: &str,
-    debtor_scheme: &str,
-    debtor_id: &str,
-    creditor_scheme: &str,
-    creditor_id: &str,
-    creditor_name: &str,
-) -> Result<String> {
+pub async fn create_account_access_consent(initiation: &PaymentInitiation) -> Result<String> {
     let access_token = super::auth::get_revolut_access_token().await?;
     let (api_url, kid, tan) = read_state(|s| {
         (
@@ -63,35 +57,15 @@ pub async fn create_account_access_consent(
     });
 
     let jws_header = jws::JWSHeader::new(&kid, &tan);
-    let jws_payload = format!(
-        r#"
-        {{
-        "Data": {{
-            "Initiation": {{
-                "InstructionIdentification": "ID412",
-                "EndToEndIdentification": "E2E123",
-                "InstructedAmount": {{
-                    "Amount": "{}",
-                    "Currency": "{}"
-                }},
-                "DebtorAccount": {{
-                    "SchemeName": "{}",
-                    "Identification": "{}"
-                }},
-                "CreditorAccount": {{
-                    "SchemeName": "{}",
-                    "Identification": "{}",
-                    "Name": "{}"
-                }}
-            }}
-        }},
-        "Risk": {{
+    let jws_payload = json!({
+        "Data": {
+            "Initiation": initiation.to_json()
+        },
+        "Risk": {
             "PaymentContextCode": "PartyToParty"
-        }}
-    }}
-    "#,
-        amount, currency, debtor_scheme, debtor_id, creditor_scheme, creditor_id, creditor_name
-    );
+        }
+    })
+    .to_string();
 
     let jws_signature = jws::create_jws_signature(&jws_payload, &jws_header).await?;
     let idempotency_key = ic_cdk::api::time().to_string();
@@ -175,3 +149,75 @@ fn transform_revolut_consent_response(args: TransformArgs) -> HttpResponse {
 
     response
 }
+
+#[derive(Serialize, Deserialize, Debug)]
+struct ConsentStatus {
+    #[serde(rename = "Status")]
+    status: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct ConsentStatusResponse {
+    #[serde(rename = "Data")]
+    data: ConsentStatus,
+}
+
+/// Fetches the current status of a domestic payment consent.
+pub async fn fetch_consent_status(consent_id: &str) -> Result<RevolutConsentStatus> {
+    let access_token = super::auth::get_revolut_access_token().await?;
+    let api_url = read_state(|s| s.revolut.api_url.clone());
+
+    let request_headers = vec![
+        HttpHeader {
+            name: "Authorization".to_string(),
+            value: format!("Bearer {}", access_token),
+        },
+        HttpHeader {
+            name: "x-fapi-financial-id".to_string(),
+            value: "001580000103UAvAAM".to_string(),
+        },
+    ];
+
+    let request = CanisterHttpRequestArgument {
+        url: format!("{}/domestic-payment-consents/{}", api_url, consent_id),
+        method: HttpMethod::GET,
+        body: None,
+        max_response_bytes: Some(8192),
+        transform: Some(TransformContext {
+            function: TransformFunc(candid::Func {
+                principal: ic_cdk::api::id(),
+                method: "transform_revolut_consent_status_response".to_string(),
+            }),
+            context: vec![],
+        }),
+        headers: request_headers,
+    };
+
+    let cycles: u128 = 10_000_000_000;
+    match http_request(request, cycles).await {
+        Ok((response,)) => {
+            let str_body = String::from_utf8(response.body).map_err(|_| SystemError::Utf8Error)?;
+            ic_cdk::println!("[fetch_consent_status] Response body: {}", str_body);
+
+            let consent_status: ConsentStatus = serde_json::from_str(&str_body)
+                .map_err(|e| SystemError::ParseError(e.to_string()))?;
+            Ok(RevolutConsentStatus::from_api(&consent_status.status))
+        }
+        Err((r, m)) => Err(SystemError::HttpRequestError(r as u64, m).into()),
+    }
+}
+
+#[ic_cdk::query]
+fn transform_revolut_consent_status_response(args: TransformArgs) -> HttpResponse {
+    let mut response = args.response;
+    response.headers.clear();
+
+    // Keep only the Data.Status field, the rest differs between replicas
+    if let Ok(status_response) = serde_json::from_slice::<ConsentStatusResponse>(&response.body) {
+        if let Ok(status_json) = serde_json::to_vec(&status_response.data) {
+            response.body = status_json;
+        }
+    }
+
+    response
+}
@@ -0,0 +1,46 @@
+use serde_json::{json, Value};
+
+/// The `Initiation` of an OBIE domestic payment.
+///
+/// A payment has to repeat the initiation of its consent exactly, so both
+/// payloads are built from the same value.
+#[derive(Clone, Debug)]
+pub struct PaymentInitiation {
+    pub amount: String,
+    pub currency: String,
+    pub debtor_scheme: String,
+    pub debtor_id: String,
+    pub creditor_scheme: String,
+    pub creditor_id: String,
+    pub creditor_name: String,
+    pub reference: Option<String>,
+}
+
+impl PaymentInitiation {
+    pub fn to_json(&self) -> Value {
+        let mut initiation = json!({
+            "InstructionIdentification": "ID412",
+            "EndToEndIdentification": "E2E123",
+            "InstructedAmount": {
+                "Amount": self.amount,
+                "Currency": self.currency
+            },
+            "DebtorAccount": {
+                "SchemeName": self.debtor_scheme,
+                "Identification": self.debtor_id
+            },
+            "CreditorAccount": {
+                "SchemeName": self.creditor_scheme,
+                "Identification": self.creditor_id,
+                "Name": self.creditor_name
+            }
+        });
+        if let Some(reference) = &self.reference {
+            initiation["RemittanceInformation"] = json!({
+                "Reference": reference,
+                "Unstructured": reference
+            });
+        }
+        initiation
+    }
+}
@@ -1,6 +1,7 @@
 pub mod auth;
 pub mod authorize;
 pub mod consent;
+pub mod initiation;
 pub mod jws;
 pub mod pay;
 pub mod token;
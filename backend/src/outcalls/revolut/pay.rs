@@ -3,10 +3,11 @@ use ic_cdk::api::management_canister::http_request::{
     TransformContext, TransformFunc,
 };
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 
 use crate::{errors::SystemError, model::memory::heap::read_state, Result};
 
-use super::jws;
+use super::{initiation::PaymentInitiation, jws};
 
 #[derive(Serialize, Deserialize)]
 struct PaymentResponse {
@@ -23,52 +24,18 @@ struct PaymentData {
 pub async fn initiate_domestic_payment(
     consent_id: &str,
     access_token: &str,
-    amount: &str,
-    currency: &str,
-    onramper_scheme: &str,
-    onramper_id: &str,
-    offramper_scheme: &str,
-    offramper_id: &str,
-    offramper_name: &str,
+    initiation: &PaymentInitiation,
 ) -> Result<String> {
-    let jws_payload = format!(
-        r#"
-        {{
-        "Data": {{
-            "ConsentId": "{}",
-            "Initiation": {{
-                "InstructionIdentification": "ID412",
-                "EndToEndIdentification": "E2E123",
-                "InstructedAmount": {{
-                    "Amount": "{}",
-                    "Currency": "{}"
-                }},
-                "DebtorAccount": {{
-                    "SchemeName": "{}",
-                    "Identification": "{}"
-                    }},
-                    "CreditorAccount": {{
-                    "SchemeName": "{}",
-                    "Identification": "{}",
-                    "Name": "{}"
-                }}
-
-            }}
-        }},
-        "Risk": {{
+    let jws_payload = json!({
+        "Data": {
+            "ConsentId": consent_id,
+            "Initiation": initiation.to_json()
+        },
+        "Risk": {
             "PaymentContextCode": "PartyToParty"
-        }}
-    }}
-    "#,
-        consent_id,
-        amount,
-        currency,
-        onramper_scheme,
-        onramper_id,
-        offramper_scheme,
-        offramper_id,
-        offramper_name
-    );
+        }
+    })
+    .to_string();
 
     let (api_url, kid, tan) = read_state(|s| {
         (
@@ -7,13 +7,13 @@ use ic_cdk::api::management_canister::http_request::{
 
 use crate::{
     errors::{BlockchainError, OrderError, Result, SystemError},
-    management::order,
+    management::{consent, order, providers::Revolut},
     model::{
         helpers,
         memory::{heap::read_state, stable},
     },
     outcalls::revolut::pay,
-    types::PaymentProvider,
+    types::orders::RevolutConsentStatus,
 };
 
 pub async fn get_revolut_access_token(consent_id: String) -> Result<String> {
@@ -79,58 +79,28 @@ pub async fn wait_for_revolut_access_token(
     let user = stable::users::get_user(&order.onramper.user_id)?;
     user.validate_session(session_token)?;
 
-    let (
-        consent_id,
-        amount,
-        currency,
-        debtor_scheme,
-        debtor_id,
-        creditor_scheme,
-        creditor_id,
-        creditor_name,
-    ) = {
-        let PaymentProvider::Revolut { scheme, id, name } = order.onramper.provider else {
-            return Err(OrderError::InvalidOnramperProvider.into());
-        };
-        let name = match name.clone() {
-            Some(name) => name,
-            None => return Err(OrderError::InvalidOnramperProvider.into()),
-        };
-        let consent_id = match order.revolut_consent {
-            Some(consent_id) => consent_id.id,
-            None => return Err(OrderError::InvalidOnramperProvider.into()),
-        };
-
-        (
-            consent_id.clone(),
-            (order.price as f64 / 100.).to_string(),
-            order.base.currency,
-            scheme.clone(),
-            id.clone(),
-            scheme.clone(),
-            id.clone(),
-            name.clone(),
-        )
+    let consent_id = match &order.revolut_consent {
+        Some(consent) if consent.status().is_final() => {
+            return Err(OrderError::RevolutConsentUnusable.into())
+        }
+        Some(consent) => consent.id.clone(),
+        None => return Err(OrderError::InvalidOnramperProvider.into()),
     };
+    let initiation = Revolut::locked_payment_initiation(&order)?;
 
     for attempt in 0..max_attempts {
         match get_revolut_access_token(consent_id.clone()).await {
             Ok(access_token) => {
                 ic_cdk::println!("[wait_for_access_token] Access token retrieved.");
-                let payment_id = pay::initiate_domestic_payment(
-                    &consent_id,
-                    &access_token,
-                    &amount,
-                    &currency,
-                    &debtor_scheme,
-                    &debtor_id,
-                    &creditor_scheme,
-                    &creditor_id,
-                    &creditor_name,
-                )
-                .await?;
+                // The order may have been unlocked or completed while waiting
+                if !consent::has_open_consent(order_id) {
+                    return Err(OrderError::RevolutConsentUnusable.into());
+                }
+                let payment_id =
+                    pay::initiate_domestic_payment(&consent_id, &access_token, &initiation).await?;
 
                 order::set_payment_id(order_id, payment_id.clone())?;
+                consent::set_consent_status(order_id, RevolutConsentStatus::Consumed)?;
 
                 // Automatically verify the transaction after setting the payment ID
                 ic_cdk::println!("[wait_for_access_token] Verifying transaction...");
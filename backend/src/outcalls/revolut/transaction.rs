@@ -31,6 +31,14 @@ pub struct InitiationDetails {
     pub debtor_account: Option<AccountDetails>,
     #[serde(rename = "CreditorAccount")]
     pub creditor_account: AccountDetails,
+    #[serde(rename = "RemittanceInformation")]
+    pub remittance_information: Option<RemittanceInformation>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RemittanceInformation {
+    #[serde(rename = "Reference")]
+    pub reference: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
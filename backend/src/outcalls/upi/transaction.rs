@@ -0,0 +1,65 @@
+use ic_cdk::api::management_canister::http_request::{
+    http_request, CanisterHttpRequestArgument, HttpHeader, HttpMethod,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errors::{Result, SystemError},
+    model::memory::heap::read_state,
+    types::payment::client_credentials::{self, ClientCredentialsRail},
+};
+
+pub const UPI_TRANSACTION_SUCCESS: &str = "SUCCESS";
+
+/// A UPI transaction as reported by the gateway, looked up by its UTR.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UpiTransaction {
+    pub utr: String,
+    pub status: String,
+    pub amount: String,
+    pub currency: String,
+    pub payer_vpa: String,
+    pub payee_vpa: String,
+    pub reference: Option<String>,
+}
+
+pub async fn fetch_upi_transaction(access_token: &str, utr: &str) -> Result<UpiTransaction> {
+    let api_url = client_credentials::get_state(ClientCredentialsRail::Upi)?.api_url;
+    let proxy_url = read_state(|s| s.proxy_url.clone());
+
+    let request_headers = vec![
+        HttpHeader {
+            name: "Content-Type".to_string(),
+            value: "application/json".to_string(),
+        },
+        HttpHeader {
+            name: "Authorization".to_string(),
+            value: format!("Bearer {}", access_token),
+        },
+        HttpHeader {
+            name: "x-forwarded-host".to_string(),
+            value: api_url,
+        },
+    ];
+
+    let request = CanisterHttpRequestArgument {
+        url: format!("{}/v1/upi/transactions/{}", proxy_url, utr),
+        method: HttpMethod::GET,
+        body: None,
+        max_response_bytes: Some(4096),
+        transform: None,
+        headers: request_headers,
+    };
+
+    let cycles = 10_000_000_000;
+    match http_request(request, cycles).await {
+        Ok((response,)) => {
+            let str_body = String::from_utf8(response.body).map_err(|_| SystemError::Utf8Error)?;
+            ic_cdk::println!("[fetch_upi_transaction] str_body = {:?}", str_body);
+
+            serde_json::from_str(&str_body)
+                .map_err(|e| SystemError::ParseError(e.to_string()).into())
+        }
+        Err((r, m)) => Err(SystemError::HttpRequestError(r as u64, m).into()),
+    }
+}
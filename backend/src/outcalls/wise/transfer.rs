@@ -0,0 +1,110 @@
+use ic_cdk::api::management_canister::http_request::{
+    http_request, CanisterHttpRequestArgument, HttpHeader, HttpMethod,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    errors::{Result, SystemError},
+    model::memory::heap::read_state,
+    types::payment::client_credentials::{self, ClientCredentialsRail},
+};
+
+/// Status of a transfer whose funds have left Wise towards the recipient.
+pub const WISE_TRANSFER_SENT: &str = "outgoing_payment_sent";
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct WiseTransfer {
+    pub id: u64,
+    pub target_account: u64,
+    pub status: String,
+    #[serde(default)]
+    pub reference: String,
+    pub target_currency: String,
+    pub target_value: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct WiseRecipient {
+    pub id: u64,
+    pub profile: u64, // profile of the sender who owns the recipient entry
+    pub account_holder_name: String,
+    pub currency: String,
+    pub details: RecipientDetails,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RecipientDetails {
+    pub iban: Option<String>,
+    pub account_number: Option<String>,
+}
+
+impl WiseRecipient {
+    /// Compares the recipient's IBAN or account number against `account_id`,
+    /// ignoring spaces and letter case.
+    pub fn matches_account(&self, account_id: &str) -> bool {
+        let normalize = |s: &str| {
+            s.chars()
+                .filter(|c| !c.is_whitespace())
+                .collect::<String>()
+                .to_uppercase()
+        };
+        let expected = normalize(account_id);
+
+        [&self.details.iban, &self.details.account_number]
+            .into_iter()
+            .flatten()
+            .any(|account| normalize(account) == expected)
+    }
+}
+
+pub async fn fetch_wise_transfer(access_token: &str, transfer_id: &str) -> Result<WiseTransfer> {
+    fetch_wise_resource(access_token, &format!("/v1/transfers/{}", transfer_id)).await
+}
+
+pub async fn fetch_wise_recipient(access_token: &str, account_id: u64) -> Result<WiseRecipient> {
+    fetch_wise_resource(access_token, &format!("/v1/accounts/{}", account_id)).await
+}
+
+async fn fetch_wise_resource<T: DeserializeOwned>(access_token: &str, path: &str) -> Result<T> {
+    let api_url = client_credentials::get_state(ClientCredentialsRail::Wise)?.api_url;
+    let proxy_url = read_state(|s| s.proxy_url.clone());
+
+    let request_headers = vec![
+        HttpHeader {
+            name: "Content-Type".to_string(),
+            value: "application/json".to_string(),
+        },
+        HttpHeader {
+            name: "Authorization".to_string(),
+            value: format!("Bearer {}", access_token),
+        },
+        HttpHeader {
+            name: "x-forwarded-host".to_string(),
+            value: api_url,
+        },
+    ];
+
+    let request = CanisterHttpRequestArgument {
+        url: format!("{}{}", proxy_url, path),
+        method: HttpMethod::GET,
+        body: None,
+        max_response_bytes: Some(8192),
+        transform: None,
+        headers: request_headers,
+    };
+
+    let cycles = 10_000_000_000;
+    match http_request(request, cycles).await {
+        Ok((response,)) => {
+            let str_body = String::from_utf8(response.body).map_err(|_| SystemError::Utf8Error)?;
+            ic_cdk::println!("[fetch_wise_resource] {} = {:?}", path, str_body);
+
+            serde_json::from_str(&str_body)
+                .map_err(|e| SystemError::ParseError(e.to_string()).into())
+        }
+        Err((r, m)) => Err(SystemError::HttpRequestError(r as u64, m).into()),
+    }
+}
@@ -8,6 +8,15 @@ source "$DIR/../.env" || {
   exit
 }
 
+# Pass --mock-provider to deploy the backend built with the simulated payment
+# rail, as the backend_mock canister serving backend/backend_mock.did
+BACKEND=backend
+for arg in "$@"; do
+  case $arg in
+    --mock-provider) BACKEND=backend_mock ;;
+  esac
+done
+
 # cargo build --release --target wasm32-unknown-unknown --package backend
 
 # candid-extractor target/wasm32-unknown-unknown/release/backend.wasm > backend/backend.did
@@ -74,7 +83,7 @@ dfx generate backend
 
 # dfx_test_key, test_key_1
 # api-m.paypal.com, api-m.sandbox.paypal.com
-dfx deploy backend --argument "(
+dfx deploy "$BACKEND" --argument "(
   variant { 
     Reinstall = record {
       ecdsa_key_id = record {
@@ -159,20 +168,20 @@ dfx deploy backend --argument "(
   }
 )"
 
-dfx canister call backend register_icp_tokens '(vec { "ryjl3-tyaaa-aaaaa-aaaba-cai"; "mc6ru-gyaaa-aaaar-qaaaq-cai" })'
-dfx canister call backend register_evm_tokens '(11155111 : nat64, vec {
+dfx canister call "$BACKEND" register_icp_tokens '(vec { "ryjl3-tyaaa-aaaaa-aaaba-cai"; "mc6ru-gyaaa-aaaar-qaaaq-cai" })'
+dfx canister call "$BACKEND" register_evm_tokens '(11155111 : nat64, vec {
     record { "0x1c7D4B196Cb0C7B01d743Fbc6116a902379C7238"; 6 : nat8; "USD"; opt "Sepolia Official USDC" };
     record { "0x08210F9170F89Ab7658F0B5E3fF39b0E03C594D4"; 6 : nat8; "EUR"; opt "Sepolia Official EURC" };
     record { "0x878bfCfbB8EAFA8A2189fd616F282E1637E06bcF"; 18 : nat8; "USD"; opt "Custom USDT deployed by me" };
 })'
-dfx canister call backend register_evm_tokens '(84532 : nat64, vec {
+dfx canister call "$BACKEND" register_evm_tokens '(84532 : nat64, vec {
     record { "0x036CbD53842c5426634e7929541eC2318f3dCF7e"; 6 : nat8; "USD"; opt "Base Sepolia Official USDC" };
     record { "0x808456652fdb597867f38412077A9182bf77359F"; 6 : nat8; "EUR"; opt "Sepolia Official EURC" };
 })'
-dfx canister call backend register_evm_tokens '(11155420 : nat64, vec {
+dfx canister call "$BACKEND" register_evm_tokens '(11155420 : nat64, vec {
     record { "0x5fd84259d66Cd46123540766Be93DFE6D43130D7"; 6 : nat8; "USD"; opt "Optimism Sepolia Official USDC" };
 })'
-dfx canister call backend register_evm_tokens '(421614 : nat64, vec {
+dfx canister call "$BACKEND" register_evm_tokens '(421614 : nat64, vec {
     record { "0x75faf114eafb1BDbe2F0316DF893fd58CE46AA4d"; 6 : nat8; "USD"; opt "Arbitrum Sepolia Official USDC" };
 })'
 
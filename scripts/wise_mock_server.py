@@ -0,0 +1,74 @@
+#!/usr/bin/env python3
+"""Minimal Wise API mock for testing Wise payment verification locally.
+
+Deploy the backend with `proxy_url = "http://localhost:8099"` and a `wise` config,
+lock a Wise order and call `verify_transaction` with transfer id `1`.
+The transfer fixture is configured through the environment:
+
+  WISE_MOCK_REFERENCE  payment_reference of the locked order (default "ICR-00")
+  WISE_MOCK_AMOUNT     target amount in major units (default 10.0)
+  WISE_MOCK_CURRENCY   target currency (default "EUR")
+  WISE_MOCK_PROFILE    onramper's Wise profile id (default 1)
+  WISE_MOCK_ACCOUNT    offramper's IBAN (default "GB33BUKB20201555555555")
+  WISE_MOCK_NAME       offramper's account holder name (default "Offramper")
+"""
+
+import json
+import os
+import re
+from http.server import BaseHTTPRequestHandler, HTTPServer
+
+PORT = int(os.environ.get("WISE_MOCK_PORT", "8099"))
+
+
+def transfer(transfer_id):
+    return {
+        "id": transfer_id,
+        "user": 1,
+        "targetAccount": 148,
+        "status": "outgoing_payment_sent",
+        "reference": os.environ.get("WISE_MOCK_REFERENCE", "ICR-00"),
+        "targetCurrency": os.environ.get("WISE_MOCK_CURRENCY", "EUR"),
+        "targetValue": float(os.environ.get("WISE_MOCK_AMOUNT", "10.0")),
+    }
+
+
+def recipient(account_id):
+    return {
+        "id": account_id,
+        "profile": int(os.environ.get("WISE_MOCK_PROFILE", "1")),
+        "accountHolderName": os.environ.get("WISE_MOCK_NAME", "Offramper"),
+        "currency": os.environ.get("WISE_MOCK_CURRENCY", "EUR"),
+        "details": {"iban": os.environ.get("WISE_MOCK_ACCOUNT", "GB33BUKB20201555555555")},
+    }
+
+
+class WiseMock(BaseHTTPRequestHandler):
+    def reply(self, status, body):
+        payload = json.dumps(body).encode()
+        self.send_response(status)
+        self.send_header("Content-Type", "application/json")
+        self.send_header("Content-Length", str(len(payload)))
+        self.end_headers()
+        self.wfile.write(payload)
+
+    def do_POST(self):
+        if self.path == "/oauth/token":
+            self.reply(200, {"access_token": "mock-token", "token_type": "bearer", "expires_in": 43199})
+        else:
+            self.reply(404, {"error": "not found"})
+
+    def do_GET(self):
+        if self.headers.get("Authorization") != "Bearer mock-token":
+            return self.reply(401, {"error": "unauthorized"})
+        if m := re.fullmatch(r"/v1/transfers/(\d+)", self.path):
+            self.reply(200, transfer(int(m.group(1))))
+        elif m := re.fullmatch(r"/v1/accounts/(\d+)", self.path):
+            self.reply(200, recipient(int(m.group(1))))
+        else:
+            self.reply(404, {"error": "not found"})
+
+
+if __name__ == "__main__":
+    print(f"Wise mock listening on :{PORT}")
+    HTTPServer(("0.0.0.0", PORT), WiseMock).serve_forever()
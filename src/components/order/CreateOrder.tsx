@@ -239,6 +239,22 @@ const CreateOrder: React.FC = () => {
                 return;
             }
 
+            // the vault can't give a deposit back once the order is refused
+            setLoadingMessage("Checking order limits");
+            const limitsResult = await backend.check_order_kyc_limits(
+                user.id,
+                sessionToken,
+                currency,
+                selectedBlockchain,
+                selectedToken.isNative ? [] : [selectedToken.address],
+                cryptoAmountUnits,
+            );
+            if ('Err' in limitsResult) {
+                setMessage(rampErrorToString(limitsResult.Err));
+                setIsLoading(false);
+                return;
+            }
+
             let evmOrderInput: [EvmOrderInput] | [] = []
             const blockchain = blockchainToBlockchainType(selectedBlockchain);
             if (blockchain === 'EVM') {
@@ -216,7 +216,8 @@ export const UserProvider = ({ children }: { children: ReactNode }) => {
         backend.refetch_user(user.id, sessionToken)
             .then((result) => {
                 if ('Ok' in result) {
-                    const updatedUser = result.Ok;
+                    // the backend does not send the session back
+                    const updatedUser = { ...result.Ok, session: user.session };
                     setUser(updatedUser);
                     fetchBalances();
 